@@ -1,4 +1,4 @@
 <?xml version="1.0" encoding="UTF-8"?>
 <tileset version="1.5" tiledversion="1.6.0" name="Overworld - Forest - Flat 256x128" tilewidth="256" tileheight="128" tilecount="18" columns="3">
- <image source="../Overworld - Forest - Flat 256x128.png" trans="000000" width="768" height="768"/>
+ <image source="../tiles/forest_256x128.png" trans="000000" width="768" height="768"/>
 </tileset>
@@ -0,0 +1,531 @@
+//! Headless map generator: runs a template's pipeline without starting Bevy.
+//!
+//!     cargo run --bin mapgen -- assets/maps/mt_breyer.ron --seed 3RJ0 --out out
+//!
+//! `--emit tmx` also writes `map.tmx` for touching the map up in Tiled.
+//!
+//! With `--count N` it sweeps N seeds in parallel and writes `batch.csv`
+//! (ranked by fairness) and `contact_sheet.png` (thumbnails in rank order).
+//!
+//! Exit status: 0 on success, 1 when the template is invalid or generation
+//! fails, 2 on bad arguments.
+
+use std::io::Write;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use image::RgbaImage;
+
+use serde::Serialize;
+
+use possessive::terrain::classes::TerrainClasses;
+use possessive::terrain::debug_png::{terrain_with_objects_image, thumbnail, write_contact_sheet};
+use possessive::terrain::generate::template_pipeline;
+use possessive::terrain::pipeline::{GenParams, GeneratedMap};
+use possessive::terrain::seed::MapSeed;
+use possessive::terrain::template::MapTemplate;
+use possessive::terrain::tmx::{relative_path, write_tmx, TmxExport};
+
+const USAGE: &str = "\
+usage: mapgen <template.ron> [options]
+
+options:
+  --seed CODE     map seed code (default: random, printed)
+  --bases N       number of bases (default 6)
+  --angle DEG     direction of base 0 in degrees (default 0)
+  --out DIR       output directory (default out)
+  --emit LIST     comma-separated artefacts: pngs, map, stats, tmx (default all)
+  --tilesets DIR  tilesets the TMX refers to (default assets/tilesets)
+  --count N       sweep N seeds: --seed, --seed + 1, ... (default 1)
+  --jobs N        worker threads for a sweep (default: all cores)
+  --thumb PX      contact sheet thumbnail size (default 128)
+  -h, --help      show this help
+
+In a sweep, pngs writes contact_sheet.png, map writes map_<seed>.json per
+seed, tmx writes map_<seed>.tmx per seed and stats writes batch.csv.";
+
+/// What to write into the output directory.
+#[derive(Clone, Copy)]
+struct Emit {
+    /// Per-phase debug PNGs (and the fairness report, if the template asks).
+    pngs: bool,
+    /// `map.json`: final classes, heights, objects, rivers, bases, shrines.
+    map: bool,
+    /// `summary.json`, also printed to stdout.
+    stats: bool,
+    /// `map.tmx` for Tiled.
+    tmx: bool,
+}
+
+struct Args {
+    template: PathBuf,
+    seed: MapSeed,
+    num_bases: usize,
+    start_angle_deg: f32,
+    out_dir: PathBuf,
+    emit: Emit,
+    tilesets: PathBuf,
+    count: usize,
+    jobs: usize,
+    thumb: u32,
+}
+
+fn whole(name: &str, v: &str) -> Result<usize, String> {
+    v.parse().ok().filter(|&n| n >= 1).ok_or(format!("{name} {v}: need a whole number >= 1"))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut template = None;
+    let mut seed = None;
+    let mut num_bases = 6;
+    let mut start_angle_deg = 0.0;
+    let mut out_dir = PathBuf::from("out");
+    let mut emit = Emit { pngs: true, map: true, stats: true, tmx: true };
+    let mut tilesets = PathBuf::from("assets/tilesets");
+    let mut count = 1;
+    let mut jobs = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut thumb = 128;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or(format!("{name} needs a value"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--seed" => {
+                let v = value("--seed")?;
+                seed = Some(v.parse::<MapSeed>().map_err(|e| format!("--seed {v}: {e}"))?);
+            }
+            "--bases" => num_bases = whole("--bases", &value("--bases")?)?,
+            "--count" => count = whole("--count", &value("--count")?)?,
+            "--jobs" => jobs = whole("--jobs", &value("--jobs")?)?,
+            "--thumb" => thumb = whole("--thumb", &value("--thumb")?)? as u32,
+            "--angle" => {
+                let v = value("--angle")?;
+                start_angle_deg = v.parse().map_err(|_| format!("--angle {v}: not a number"))?;
+            }
+            "--out" => out_dir = PathBuf::from(value("--out")?),
+            "--tilesets" => tilesets = PathBuf::from(value("--tilesets")?),
+            "--emit" => {
+                emit = Emit { pngs: false, map: false, stats: false, tmx: false };
+                for item in value("--emit")?.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                    match item {
+                        "pngs" => emit.pngs = true,
+                        "map" => emit.map = true,
+                        "stats" => emit.stats = true,
+                        "tmx" => emit.tmx = true,
+                        other => return Err(format!("--emit: unknown artefact `{other}` (pngs, map, stats, tmx)")),
+                    }
+                }
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option `{flag}`")),
+            path if template.is_none() => template = Some(PathBuf::from(path)),
+            extra => return Err(format!("unexpected argument `{extra}`")),
+        }
+    }
+
+    let template = template.ok_or("missing template path")?;
+    let seed = seed.unwrap_or_else(|| MapSeed(fastrand::u64(..)));
+    Ok(Some(Args { template, seed, num_bases, start_angle_deg, out_dir, emit, tilesets, count, jobs, thumb }))
+}
+
+#[derive(Serialize)]
+struct ObjectOut<'a> {
+    kind: &'a str,
+    x: i32,
+    y: i32,
+}
+
+#[derive(Serialize)]
+struct MapData<'a> {
+    seed: MapSeed,
+    width: i32,
+    height: i32,
+    /// Class names; `tiles` holds indices into this list.
+    classes: Vec<&'a str>,
+    tiles: &'a [u8],
+    elevation: &'a [f32],
+    bases: Vec<(i32, i32)>,
+    shrines: Vec<(i32, i32)>,
+    objects: Vec<ObjectOut<'a>>,
+    rivers: Vec<Vec<(i32, i32)>>,
+}
+
+#[derive(Serialize)]
+struct ClassShare<'a> {
+    class: &'a str,
+    fraction: f32,
+}
+
+#[derive(Serialize)]
+struct ObjectCount<'a> {
+    kind: &'a str,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct Summary<'a> {
+    template: String,
+    seed: MapSeed,
+    size: (i32, i32),
+    bases: usize,
+    shrines: usize,
+    terrain: Vec<ClassShare<'a>>,
+    objects: Vec<ObjectCount<'a>>,
+    rivers: usize,
+    /// None when the pipeline has no Connectivity step.
+    connected: Option<bool>,
+    /// Fairness imbalance (None without a Fairness step).
+    imbalance: Option<f32>,
+    millis: u128,
+}
+
+fn object_name(tpl: &MapTemplate, kind: u16) -> &str {
+    tpl.objects.types.get(kind as usize).map_or("?", |t| t.name.as_str())
+}
+
+fn map_data<'a>(tpl: &'a MapTemplate, map: &'a GeneratedMap) -> MapData<'a> {
+    let xy = |p: glam::IVec2| (p.x, p.y);
+    MapData {
+        seed: map.seed,
+        width: map.classes.w,
+        height: map.classes.h,
+        classes: tpl.terrain.classes.iter().map(|c| c.name.as_str()).collect(),
+        tiles: map.classes.as_slice(),
+        elevation: map.height.as_slice(),
+        bases: map.bases.base_centers.iter().copied().map(xy).collect(),
+        shrines: map.ley.shrines.iter().copied().map(xy).collect(),
+        objects: map.objects.iter()
+            .map(|o| ObjectOut { kind: object_name(tpl, o.kind), x: o.pos.x, y: o.pos.y })
+            .collect(),
+        rivers: map.rivers.iter().map(|r| r.points.iter().copied().map(xy).collect()).collect(),
+    }
+}
+
+fn summary<'a>(args: &Args, tpl: &'a MapTemplate, map: &GeneratedMap, millis: u128) -> Summary<'a> {
+    let tiles = map.classes.as_slice();
+    let classes = &tpl.terrain.classes;
+    let mut per_class = vec![0usize; classes.len()];
+    for &c in tiles {
+        if let Some(n) = per_class.get_mut(c as usize) { *n += 1; }
+    }
+    let mut per_kind = vec![0usize; tpl.objects.types.len()];
+    for o in &map.objects {
+        if let Some(n) = per_kind.get_mut(o.kind as usize) { *n += 1; }
+    }
+    Summary {
+        template: args.template.display().to_string(),
+        seed: map.seed,
+        size: (map.classes.w, map.classes.h),
+        bases: map.bases.base_centers.len(),
+        shrines: map.ley.shrines.len(),
+        terrain: per_class.iter().enumerate()
+            .map(|(c, &n)| ClassShare {
+                class: classes[c].name.as_str(),
+                fraction: n as f32 / tiles.len().max(1) as f32,
+            })
+            .collect(),
+        objects: per_kind.iter().enumerate()
+            .map(|(k, &count)| ObjectCount { kind: object_name(tpl, k as u16), count })
+            .collect(),
+        rivers: map.rivers.len(),
+        connected: map.connectivity.as_ref().map(|c| c.connected),
+        imbalance: map.fairness.as_ref().map(|f| f.imbalance),
+        millis,
+    }
+}
+
+fn print_summary(s: &Summary) {
+    println!("{} seed {} ({}x{}) in {} ms", s.template, s.seed, s.size.0, s.size.1, s.millis);
+    println!("  bases {}, shrines {}, rivers {}", s.bases, s.shrines, s.rivers);
+    for t in &s.terrain {
+        println!("  {:<12} {:5.1}%", t.class, t.fraction * 100.0);
+    }
+    for o in &s.objects {
+        println!("  {:<12} {}", o.kind, o.count);
+    }
+    if let Some(c) = s.connected { println!("  connected    {c}"); }
+    if let Some(i) = s.imbalance { println!("  imbalance    {i:.3}"); }
+}
+
+fn write_json(path: &Path, value: &impl Serialize, pretty: bool) -> Result<(), String> {
+    let text = if pretty { serde_json::to_string_pretty(value) } else { serde_json::to_string(value) };
+    let text = text.map_err(|e| format!("{}: {e}", path.display()))?;
+    std::fs::write(path, text).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn export_tmx(args: &Args, tpl: &MapTemplate, map: &GeneratedMap, path: &Path) -> Result<(), String> {
+    let tilesets_ref = relative_path(&args.out_dir, &args.tilesets).to_string_lossy().replace('\\', "/");
+    let ex = TmxExport { tilesets_dir: &args.tilesets, tilesets_ref };
+    write_tmx(path, tpl, map, &ex).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+fn generate(args: &Args, tpl: &MapTemplate, seed: MapSeed, out_dir: Option<PathBuf>) -> Result<(GeneratedMap, u128), String> {
+    let params = GenParams { num_bases: args.num_bases, start_angle_deg: args.start_angle_deg, seed, out_dir };
+    let start = Instant::now();
+    let map = template_pipeline(tpl).generate(tpl, params).map_err(|e| format!("seed {seed}: {e}"))?;
+    for s in &map.object_shortfalls { eprintln!("seed {seed}: objects: {s}"); }
+    Ok((map, start.elapsed().as_millis()))
+}
+
+fn run(args: &Args) -> Result<(), String> {
+    let loaded = MapTemplate::load(&args.template).map_err(|e| e.to_string())?;
+    for w in &loaded.warnings { eprintln!("{}: {w}", args.template.display()); }
+    let tpl = loaded.template;
+    std::fs::create_dir_all(&args.out_dir).map_err(|e| format!("{}: {e}", args.out_dir.display()))?;
+    if args.count > 1 {
+        return run_batch(args, &tpl);
+    }
+
+    let (map, millis) = generate(args, &tpl, args.seed, args.emit.pngs.then(|| args.out_dir.clone()))?;
+
+    if args.emit.map {
+        // Tile arrays are large; keep map.json compact.
+        write_json(&args.out_dir.join("map.json"), &map_data(&tpl, &map), false)?;
+    }
+    if args.emit.tmx {
+        export_tmx(args, &tpl, &map, &args.out_dir.join("map.tmx"))?;
+    }
+    if args.emit.stats {
+        let s = summary(args, &tpl, &map, millis);
+        write_json(&args.out_dir.join("summary.json"), &s, true)?;
+        print_summary(&s);
+    } else {
+        println!("{} seed {}", args.template.display(), map.seed);
+    }
+    Ok(())
+}
+
+/// One finished map of a sweep.
+struct Entry<'a> {
+    summary: Summary<'a>,
+    thumb: Option<RgbaImage>,
+}
+
+impl Entry<'_> {
+    /// Disconnected maps sort last, then by fairness imbalance (lower is better).
+    fn rank_key(&self) -> (bool, f32) {
+        let s = &self.summary;
+        (s.connected == Some(false), s.imbalance.unwrap_or(f32::INFINITY))
+    }
+
+    /// Sweep order: `rank_key`, ties broken by seed.
+    fn rank(&self, other: &Self) -> std::cmp::Ordering {
+        let (ka, kb) = (self.rank_key(), other.rank_key());
+        ka.0.cmp(&kb.0).then(ka.1.total_cmp(&kb.1)).then(self.summary.seed.0.cmp(&other.summary.seed.0))
+    }
+}
+
+/// Generate and export one seed of a sweep.
+fn sweep_one<'a>(args: &Args, tpl: &'a MapTemplate, palette: &[[u8; 4]], seed: MapSeed) -> Result<Entry<'a>, String> {
+    let (map, millis) = generate(args, tpl, seed, None)?;
+    if args.emit.map {
+        write_json(&args.out_dir.join(format!("map_{seed}.json")), &map_data(tpl, &map), false)?;
+    }
+    if args.emit.tmx {
+        export_tmx(args, tpl, &map, &args.out_dir.join(format!("map_{seed}.tmx")))?;
+    }
+    // Shrink now so a sweep holds thumbnails, not full-size renders.
+    let thumb = args.emit.pngs
+        .then(|| thumbnail(&terrain_with_objects_image(&map.classes, palette, &map.objects, tpl), args.thumb));
+    Ok(Entry { summary: summary(args, tpl, &map, millis), thumb })
+}
+
+/// The message of a caught panic, if it was a string.
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    panic.downcast_ref::<&str>().copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic")
+}
+
+fn run_batch(args: &Args, tpl: &MapTemplate) -> Result<(), String> {
+    let seeds: Vec<MapSeed> = (0..args.count as u64).map(|i| MapSeed(args.seed.0.wrapping_add(i))).collect();
+    let palette = TerrainClasses::from_template(tpl).palette();
+    let next = AtomicUsize::new(0);
+    let start = Instant::now();
+
+    // Workers pull the next seed index until the list runs out.
+    let work = || {
+        let mut done = Vec::new();
+        while let Some(&seed) = seeds.get(next.fetch_add(1, Ordering::Relaxed)) {
+            // A panicking seed counts as failed instead of taking the worker
+            // (and every seed it had left) down with it.
+            let entry = catch_unwind(AssertUnwindSafe(|| sweep_one(args, tpl, &palette, seed)))
+                .unwrap_or_else(|panic| Err(format!("seed {seed}: panicked: {}", panic_message(&*panic))));
+            match &entry {
+                Ok(e) => eprintln!("  {} imbalance {:.3}", seed, e.summary.imbalance.unwrap_or(f32::NAN)),
+                Err(msg) => eprintln!("  {msg}"),
+            }
+            done.push(entry);
+        }
+        done
+    };
+    let results: Vec<Result<Entry, String>> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..args.jobs.min(seeds.len())).map(|_| scope.spawn(work)).collect();
+        workers.into_iter().flat_map(|w| w.join().unwrap_or_default()).collect()
+    });
+
+    // Seeds without an entry failed (errors, panics, or a lost worker).
+    let mut entries: Vec<Entry> = results.into_iter().flatten().collect();
+    let failed = seeds.len() - entries.len();
+    entries.sort_by(Entry::rank);
+
+    if args.emit.stats {
+        write_csv(&args.out_dir.join("batch.csv"), tpl, &entries)?;
+    }
+    if args.emit.pngs && !entries.is_empty() {
+        // Red frame: not connected; white: connected (or unchecked).
+        let tiles: Vec<_> = entries.iter()
+            .filter_map(|e| {
+                let frame = if e.summary.connected == Some(false) { [220, 40, 40, 255] } else { [230, 230, 230, 255] };
+                e.thumb.clone().map(|t| (t, frame))
+            })
+            .collect();
+        let cols = (tiles.len() as f32).sqrt().ceil() as usize;
+        write_contact_sheet(&args.out_dir.join("contact_sheet.png").to_string_lossy(), &tiles, cols, args.thumb);
+    }
+
+    println!(
+        "{}: {} seeds from {} in {} ms ({} failed)",
+        args.template.display(), seeds.len(), args.seed, start.elapsed().as_millis(), failed,
+    );
+    for (rank, e) in entries.iter().take(10).enumerate() {
+        let s = &e.summary;
+        let note = if s.connected == Some(false) { "  (disconnected)" } else { "" };
+        println!("  {:>3}. {:<14} imbalance {:.3}{note}", rank + 1, s.seed.to_string(), s.imbalance.unwrap_or(f32::NAN));
+    }
+    if failed > 0 {
+        return Err(format!("{failed} of {} seeds failed", seeds.len()));
+    }
+    Ok(())
+}
+
+/// One row per map in rank order; `rank` is also the thumbnail's position on
+/// the contact sheet (row-major, starting at 1).
+fn write_csv(path: &Path, tpl: &MapTemplate, entries: &[Entry]) -> Result<(), String> {
+    let io = |e: std::io::Error| format!("{}: {e}", path.display());
+    let mut out = std::io::BufWriter::new(std::fs::File::create(path).map_err(io)?);
+    let mut header = vec!["rank".to_string(), "seed".into(), "imbalance".into(), "connected".into(), "shrines".into(), "rivers".into()];
+    header.extend(tpl.terrain.classes.iter().map(|c| format!("{}_pct", c.name.to_lowercase())));
+    header.extend(tpl.objects.types.iter().map(|t| t.name.to_lowercase()));
+    header.push("millis".into());
+    writeln!(out, "{}", header.join(",")).map_err(io)?;
+
+    for (rank, e) in entries.iter().enumerate() {
+        let s = &e.summary;
+        let mut row = vec![
+            (rank + 1).to_string(),
+            s.seed.to_string(),
+            s.imbalance.map_or(String::new(), |v| format!("{v:.4}")),
+            s.connected.map_or(String::new(), |v| v.to_string()),
+            s.shrines.to_string(),
+            s.rivers.to_string(),
+        ];
+        row.extend(s.terrain.iter().map(|t| format!("{:.2}", t.fraction * 100.0)));
+        row.extend(s.objects.iter().map(|o| o.count.to_string()));
+        row.push(s.millis.to_string());
+        writeln!(out, "{}", row.join(",")).map_err(io)?;
+    }
+    out.flush().map_err(io)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("mapgen: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("mapgen: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Args>, String> {
+        parse_args(args.iter().map(|a| a.to_string()))
+    }
+
+    fn emitted(args: &[&str]) -> (bool, bool, bool, bool) {
+        let e = parse(args).unwrap().unwrap().emit;
+        (e.pngs, e.map, e.stats, e.tmx)
+    }
+
+    #[test]
+    fn emit_lists_pick_artefacts() {
+        assert_eq!(emitted(&["t.ron"]), (true, true, true, true));
+        assert_eq!(emitted(&["t.ron", "--emit", "tmx,map"]), (false, true, false, true));
+        assert_eq!(emitted(&["t.ron", "--emit", " stats , ,pngs,"]), (true, false, true, false));
+        assert_eq!(emitted(&["t.ron", "--emit", ""]), (false, false, false, false));
+        assert_eq!(
+            parse(&["t.ron", "--emit", "map,json"]).err().unwrap(),
+            "--emit: unknown artefact `json` (pngs, map, stats, tmx)",
+        );
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        assert_eq!(parse(&["t.ron", "--frobnicate"]).err().unwrap(), "unknown option `--frobnicate`");
+        assert_eq!(parse(&["t.ron", "--count", "0"]).err().unwrap(), "--count 0: need a whole number >= 1");
+        assert_eq!(parse(&["t.ron", "--count"]).err().unwrap(), "--count needs a value");
+        assert_eq!(parse(&["t.ron", "u.ron"]).err().unwrap(), "unexpected argument `u.ron`");
+        assert_eq!(parse(&["--seed", "3RJ0"]).err().unwrap(), "missing template path");
+        assert!(matches!(parse(&["t.ron", "--help"]), Ok(None)));
+    }
+
+    #[test]
+    fn panics_become_messages() {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(|_| {}));
+        let str_panic = catch_unwind(|| panic!("plain")).unwrap_err();
+        let fmt_panic = catch_unwind(|| panic!("seed {}", 7)).unwrap_err();
+        std::panic::set_hook(hook);
+        assert_eq!(panic_message(&*str_panic), "plain");
+        assert_eq!(panic_message(&*fmt_panic), "seed 7");
+    }
+
+    #[test]
+    fn options_land_in_args() {
+        let a = parse(&["--seed", "3rj0", "t.ron", "--bases", "4", "--count", "8", "--out", "o"]).unwrap().unwrap();
+        assert_eq!(a.seed, "3RJ0".parse().unwrap());
+        assert_eq!((a.num_bases, a.count), (4, 8));
+        assert_eq!((a.template.as_path(), a.out_dir.as_path()), (Path::new("t.ron"), Path::new("o")));
+    }
+
+    fn entry(seed: u64, connected: Option<bool>, imbalance: Option<f32>) -> Entry<'static> {
+        let summary = Summary {
+            template: "t.ron".into(), seed: MapSeed(seed), size: (8, 8), bases: 2, shrines: 0,
+            terrain: Vec::new(), objects: Vec::new(), rivers: 0, connected, imbalance, millis: 0,
+        };
+        Entry { summary, thumb: None }
+    }
+
+    #[test]
+    fn ranking_puts_fair_connected_maps_first() {
+        let mut entries = [
+            entry(1, Some(false), Some(0.01)),
+            entry(2, Some(true), None),
+            entry(3, Some(true), Some(0.30)),
+            entry(4, None, Some(0.10)),
+            entry(5, Some(true), Some(0.10)),
+            entry(6, Some(false), None),
+        ];
+        entries.sort_by(Entry::rank);
+        let order: Vec<u64> = entries.iter().map(|e| e.summary.seed.0).collect();
+        // Unchecked connectivity ranks with connected; equal imbalance falls back to the seed.
+        assert_eq!(order, [4, 5, 3, 2, 1, 6]);
+    }
+}
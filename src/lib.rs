@@ -0,0 +1,8 @@
+//! Map generation and the wildlife sim, shared by the game (`main.rs`) and
+//! the headless `mapgen` tool.
+
+pub mod terrain;
+pub mod units;
+pub mod tilemap_bridge;
+pub mod map_plugin;
+pub mod tmx_import;
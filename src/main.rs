@@ -1,17 +1,15 @@
-mod terrain;
-mod units;
-mod tilemap_bridge;
-
-use terrain::generate::{generate_all_phases};
-use terrain::grid::Grid;
 use bevy::prelude::*;
-use units::base::*;
-use units::world::{TileMap, Terrain, TILE_SIZE, plants_regrow_system};
-use units::creature::{CreatureBundle, WildlifeSimPlugin};
-use units::simview::SimViewPlugin;
-use tilemap_bridge::{classes_to_tilemap, apply_objects_to_tilemap};
+use possessive::units::base::*;
+use possessive::units::world::{SimRng, TileMap, TILE_SIZE, plants_regrow_system};
+use possessive::terrain::seed::MapSeed;
+use possessive::terrain::template::ClassRole;
+use possessive::units::creature::{CreatureBundle, WildlifeSimPlugin};
+use possessive::units::isoview::IsoViewPlugin;
+use possessive::units::simview::SimViewPlugin;
+use possessive::map_plugin::{MapGenPlugin, MapGenSettings, MapGenerated};
+use possessive::tmx_import::{TmxMapPlugin, TmxMapping};
 
-const TERRAIN_SEED: u32 = 123456;
+const TERRAIN_SEED: MapSeed = MapSeed(123456);
 const TERRAIN_NUM_BASES: usize = 6usize;
 const TERRAIN_BASE_START_ANGLE: f32 = 0.0;
 
@@ -23,33 +21,41 @@ const N_FOX:      usize = 80;
 const N_BEAR:     usize = 25;
 
 fn main() {
-    let terrain_map = "assets/maps/mt_breyer.ron";
-    // let TERRAIN_MAP = "assets/maps/haunted_woods.ron";
+    let terrain_map = "maps/mt_breyer.ron"; // under assets/; edits regenerate the map live
+    // let terrain_map = "maps/haunted_woods.ron";
     let terrain_out = "out"; // None to disable map stage generation
+    let arg = std::env::args().nth(1);
+    // `cargo run -- <seed code>` replays a map (the code is in out/seed.txt).
+    let seed = match arg.as_deref() {
+        Some(code) if !code.ends_with(".tmx") => code.parse().unwrap_or_else(|e| {
+            eprintln!("bad seed code `{code}`: {e}");
+            std::process::exit(2);
+        }),
+        _ => TERRAIN_SEED,
+    };
 
-    let tpl = terrain::template::MapTemplate::from_file(terrain_map);
-
-    let (_p1, _ley, _final, _objects) = generate_all_phases(
-        &tpl,
-        TERRAIN_NUM_BASES, // num_bases
-        TERRAIN_BASE_START_ANGLE, // start_angle_deg
-        None,         // ley: use template (or defaults)
-        None,         // blend: use template (or defaults)
-        None,         // fractal: use template (or defaults)
-        TERRAIN_SEED, // terrain_seed
-        Some(terrain_out), // PNG dir or None
-    );
-    
-    // Build the TileMap and apply objects
-    let mut map = classes_to_tilemap(&_final);
-    apply_objects_to_tilemap(&mut map, &tpl, &_objects);
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    match arg {
+        // `cargo run -- assets/maps/demo.tmx` runs the sim on a hand-made map.
+        Some(path) if path.ends_with(".tmx") => {
+            app.add_plugins(TmxMapPlugin { path: path.into(), mapping: TmxMapping::default() });
+        }
+        _ => {
+            app.add_plugins(MapGenPlugin(MapGenSettings {
+                template: terrain_map.to_string(),
+                num_bases: TERRAIN_NUM_BASES,
+                start_angle_deg: TERRAIN_BASE_START_ANGLE,
+                seed,
+                out_dir: Some(terrain_out.to_string()),
+            }));
+        }
+    }
 
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .insert_resource(map)
-        .add_plugins(WildlifeSimPlugin) // decision → path → movement
+    app.add_plugins(WildlifeSimPlugin) // decision → path → movement
         .add_plugins(SimViewPlugin)     // draw map + metrics UI
-        .add_systems(Startup, spawn_load_test)
+        .add_plugins(IsoViewPlugin)     // Tab: tileset view
+        .add_systems(Update, spawn_load_test.run_if(on_event::<MapGenerated>))
         .add_systems(Update, plants_regrow_system) // keeps berries/nuts rising
         .run();
 }
@@ -65,54 +71,64 @@ fn default_speed(sp: Species) -> f32 {
     }
 }
 
-/// Species → allowed terrains
-fn allowed_terrain(sp: Species, t: Terrain) -> bool {
+/// Species → allowed terrain roles
+fn allowed_terrain(sp: Species, role: ClassRole) -> bool {
     match sp {
-        Species::Squirrel => matches!(t, Terrain::Forest),
-        Species::Deer     => matches!(t, Terrain::Forest | Terrain::Grassland),
-        Species::Bird     => matches!(t, Terrain::Forest | Terrain::Grassland),
-        Species::Fox      => matches!(t, Terrain::Grassland),
-        Species::Bear     => matches!(t, Terrain::Mountain),
+        Species::Squirrel => matches!(role, ClassRole::Forest),
+        Species::Deer     => matches!(role, ClassRole::Forest | ClassRole::Ground),
+        Species::Bird     => matches!(role, ClassRole::Forest | ClassRole::Ground),
+        Species::Fox      => matches!(role, ClassRole::Ground),
+        Species::Bear     => matches!(role, ClassRole::Mountain),
     }
 }
 
 /// Pick a random cell that fits the species' home terrain (fallback to any).
-fn random_cell_for_species(map: &TileMap, sp: Species) -> IVec2 {
+fn random_cell_for_species(map: &TileMap, sp: Species, rng: &mut fastrand::Rng) -> IVec2 {
     // Try up to a few hundred cells that match preferred terrain.
     for _ in 0..400 {
-        let x = fastrand::i32(0..map.width);
-        let y = fastrand::i32(0..map.height);
+        let x = rng.i32(0..map.width);
+        let y = rng.i32(0..map.height);
         let idx = (y * map.width + x) as usize;
-        let t = map.tiles[idx].terrain;
-        if allowed_terrain(sp, t) {
+        let role = map.role_of(map.tiles()[idx].terrain);
+        if allowed_terrain(sp, role) {
             return IVec2::new(x, y);
         }
     }
     // Fallback: truly any cell.
     IVec2::new(
-        fastrand::i32(0..map.width),
-        fastrand::i32(0..map.height),
+        rng.i32(0..map.width),
+        rng.i32(0..map.height),
     )
 }
 
 /// Center of a tile in world units, plus a small random jitter so things don’t overlap perfectly.
-fn random_pos_in_cell(cell: IVec2) -> Vec2 {
+fn random_pos_in_cell(cell: IVec2, rng: &mut fastrand::Rng) -> Vec2 {
     let center = Vec2::new(
         (cell.x as f32 + 0.5) * TILE_SIZE,
         (cell.y as f32 + 0.5) * TILE_SIZE,
     );
-    let jitter = (Vec2::new(fastrand::f32() - 0.5, fastrand::f32() - 0.5)
+    let jitter = (Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5)
         .normalize_or_zero()) * (0.35 * TILE_SIZE);
     center + jitter
 }
 
-pub fn spawn_load_test(mut commands: Commands, map: Res<TileMap>) {
+/// (Re)populate the map; old creatures go with the old terrain.
+pub fn spawn_load_test(
+    mut commands: Commands,
+    map: Res<TileMap>,
+    mut rng: ResMut<SimRng>,
+    existing: Query<Entity, With<Species>>,
+) {
+    for e in &existing {
+        commands.entity(e).despawn();
+    }
+
     // helper to spawn many of one species
     let mut spawn_many = |count: usize, sp: Species| {
         let speed = default_speed(sp);
         for _ in 0..count {
-            let cell = random_cell_for_species(&map, sp);
-            let pos  = random_pos_in_cell(cell);
+            let cell = random_cell_for_species(&map, sp, &mut rng.0);
+            let pos  = random_pos_in_cell(cell, &mut rng.0);
             commands.spawn(CreatureBundle::new(sp, pos, speed));
         }
     };
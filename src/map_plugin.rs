@@ -0,0 +1,186 @@
+//! Map templates as Bevy assets. `MapGenPlugin` loads the template through the
+//! asset server, runs the terrain pipeline once it's ready and again every time
+//! the file (or anything it extends/includes) changes on disk, then swaps in the
+//! new `TileMap` and sends `MapGenerated` so views and spawners can rebuild.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use bevy::asset::io::Reader;
+use bevy::asset::{AssetLoader, AssetPath, LoadContext};
+use bevy::prelude::*;
+
+use crate::terrain::classes::TerrainClasses;
+use crate::terrain::compose::{compose_with, ComposeError};
+use crate::terrain::generate::template_pipeline;
+use crate::terrain::pipeline::{GenParams, PipelineError};
+use crate::terrain::seed::MapSeed;
+use crate::terrain::template::{MapTemplate, TemplateError};
+use crate::tilemap_bridge::{apply_objects_to_tilemap, apply_rivers_to_tilemap, classes_to_tilemap};
+use crate::units::world::{SimRng, TileMap, TileMapPlugin};
+
+/// A validated, fully composed template.
+#[derive(Asset, TypePath)]
+pub struct MapTemplateAsset(pub MapTemplate);
+
+/// Reads `.ron` map templates. `extends`/`include` files are fetched through
+/// the asset server too, so editing a base preset reloads everything built on it.
+#[derive(Default)]
+pub struct MapTemplateLoader;
+
+impl AssetLoader for MapTemplateLoader {
+    type Asset = MapTemplateAsset;
+    type Settings = ();
+    type Error = TemplateError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        ctx: &mut LoadContext<'_>,
+    ) -> Result<MapTemplateAsset, TemplateError> {
+        let root = ctx.path().to_path_buf();
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await.map_err(|source| {
+            ComposeError::Missing { path: root.clone(), from: None, source }
+        })?;
+        let mut files: HashMap<PathBuf, String> =
+            HashMap::from([(root.clone(), String::from_utf8_lossy(&bytes).into_owned())]);
+
+        // Compose from what we have; each miss names one more file to fetch.
+        loop {
+            let mut wanted: Option<PathBuf> = None;
+            let result = compose_with(&root, &mut |p: &Path| match files.get(p) {
+                Some(text) => Ok(text.clone()),
+                None => {
+                    wanted = Some(p.to_path_buf());
+                    Err(std::io::ErrorKind::NotFound.into())
+                }
+            });
+            let err = match result {
+                Ok(c) => {
+                    let loaded = MapTemplate::from_composed(&c, &root)?;
+                    for w in &loaded.warnings { warn!("{}: {w}", root.display()); }
+                    return Ok(MapTemplateAsset(loaded.template));
+                }
+                Err(e) => e,
+            };
+            let (Some(path), ComposeError::Missing { from, .. }) = (wanted, &err) else {
+                return Err(err.into());
+            };
+            match ctx.read_asset_bytes(AssetPath::from_path(&path)).await {
+                Ok(b) => { files.insert(path, String::from_utf8_lossy(&b).into_owned()); }
+                Err(e) => {
+                    return Err(ComposeError::Missing {
+                        path,
+                        from: from.clone(),
+                        source: std::io::Error::other(e.to_string()),
+                    }.into());
+                }
+            }
+        }
+    }
+
+    fn extensions(&self) -> &[&str] { &["ron"] }
+}
+
+/// Sent after a (re)generated `TileMap` has been inserted.
+#[derive(Event, Clone, Copy)]
+pub struct MapGenerated;
+
+/// Pipeline inputs that don't live in the template.
+#[derive(Resource, Clone)]
+pub struct MapGenSettings {
+    /// Asset path, relative to `assets/`.
+    pub template: String,
+    pub num_bases: usize,
+    pub start_angle_deg: f32,
+    /// Every phase seed (and the sim's RNG) is derived from this.
+    pub seed: MapSeed,
+    /// Debug PNG directory (None = skip).
+    pub out_dir: Option<String>,
+}
+
+#[derive(Resource)]
+struct MapTemplateHandle(Handle<MapTemplateAsset>);
+
+pub struct MapGenPlugin(pub MapGenSettings);
+
+impl Plugin for MapGenPlugin {
+    fn build(&self, app: &mut App) {
+        // Sim systems read the map every frame; give them an empty one until
+        // the template has loaded.
+        app.init_asset::<MapTemplateAsset>()
+            .init_asset_loader::<MapTemplateLoader>()
+            .add_event::<MapGenerated>()
+            .insert_resource(self.0.clone())
+            .insert_resource(TileMap::empty())
+            .insert_resource(SimRng(fastrand::Rng::with_seed(self.0.seed.stream("sim"))))
+            .add_systems(Startup, load_template)
+            .add_systems(PreUpdate, regenerate_on_change);
+        if !app.is_plugin_added::<TileMapPlugin>() {
+            app.add_plugins(TileMapPlugin);
+        }
+    }
+}
+
+fn load_template(mut commands: Commands, server: Res<AssetServer>, settings: Res<MapGenSettings>) {
+    let handle = server.load::<MapTemplateAsset>(settings.template.clone());
+    commands.insert_resource(MapTemplateHandle(handle));
+}
+
+fn regenerate_on_change(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<MapTemplateAsset>>,
+    mut generated: EventWriter<MapGenerated>,
+    templates: Res<Assets<MapTemplateAsset>>,
+    handle: Res<MapTemplateHandle>,
+    settings: Res<MapGenSettings>,
+) {
+    // Fires on the first load and again after every hot reload (Modified
+    // arrives a frame earlier too, so ignore it to build only once).
+    // Count rather than `any` so every event is consumed this frame.
+    let changed = events.read().filter(|ev| matches!(
+        ev, AssetEvent::LoadedWithDependencies { id } if *id == handle.0.id()
+    )).count();
+    if changed == 0 { return; }
+    let Some(tpl) = templates.get(&handle.0) else { return; };
+
+    info!("generating map from {} with seed {}", settings.template, settings.seed);
+    match build_tilemap(&tpl.0, &settings) {
+        Ok(map) => {
+            commands.insert_resource(map);
+            commands.insert_resource(SimRng(fastrand::Rng::with_seed(settings.seed.stream("sim"))));
+            generated.write(MapGenerated);
+        }
+        Err(e) => error!("{}: {e}; keeping the previous map", settings.template),
+    }
+}
+
+/// Run the template's pipeline and convert the result for the sim.
+pub fn build_tilemap(tpl: &MapTemplate, settings: &MapGenSettings) -> Result<TileMap, PipelineError> {
+    let params = GenParams {
+        num_bases: settings.num_bases,
+        start_angle_deg: settings.start_angle_deg,
+        seed: settings.seed,
+        out_dir: settings.out_dir.as_ref().map(Into::into),
+    };
+    let generated = template_pipeline(tpl)
+        .with_hook(|phase, _| {
+            debug!("map phase {} ({} #{}) took {:?}", phase.step, phase.name, phase.repeat + 1, phase.elapsed)
+        })
+        .generate(tpl, params)?;
+    if let Some(conn) = generated.connectivity.as_ref().filter(|c| !c.connected) {
+        warn!(
+            "map is not fully connected (bases cut off: {:?}, shrines cut off: {:?})",
+            conn.disconnected_bases, conn.disconnected_shrines,
+        );
+    }
+    for s in &generated.object_shortfalls { warn!("objects: {s}"); }
+
+    let reg = TerrainClasses::from_template(tpl);
+    let mut map = classes_to_tilemap(&generated.classes, &generated.height, &reg);
+    apply_objects_to_tilemap(&mut map, tpl, &generated.objects);
+    apply_rivers_to_tilemap(&mut map, &generated.rivers);
+    Ok(map)
+}
@@ -0,0 +1,192 @@
+//! Picks a tileset tile for every cell from its class and its 8 neighbours
+//! (blob autotiling). The result is one tile layer that the TMX export and
+//! the iso view both draw.
+//!
+//! The shipped 256x128 tilesets hold whole-tile variants, not transition
+//! pieces, so the stock rules only swap softer variants in along class
+//! borders (clearings at forest edges, shallows at the shore). A tileset with
+//! a full 47-tile blob set maps each mask through `ClassTiles::blob`.
+
+use super::grid::Grid;
+use super::template::{ClassRole, ClassTiles, TerrainClassDef};
+
+// Mask bits, in grid directions (N = y - 1).
+pub const N: u8 = 1;
+pub const NE: u8 = 2;
+pub const E: u8 = 4;
+pub const SE: u8 = 8;
+pub const S: u8 = 16;
+pub const SW: u8 = 32;
+pub const W: u8 = 64;
+pub const NW: u8 = 128;
+
+const NEIGHBOURS: [(i32, i32, u8); 8] = [
+    (0, -1, N), (1, -1, NE), (1, 0, E), (1, 1, SE),
+    (0, 1, S), (-1, 1, SW), (-1, 0, W), (-1, -1, NW),
+];
+
+// Each corner and the two sides it needs.
+const CORNERS: [(u8, u8); 4] = [(NE, N | E), (SE, S | E), (SW, S | W), (NW, N | W)];
+
+fn reduce(mut mask: u8) -> u8 {
+    for (corner, sides) in CORNERS {
+        if mask & sides != sides { mask &= !corner; }
+    }
+    mask
+}
+
+/// Neighbours of (x, y) in the same class, as bits. Off-map counts as the
+/// same class, and a corner only counts when both its sides do, which leaves
+/// the 47 standard blob masks (0xFF = interior).
+pub fn blob_mask(classes: &Grid<u8>, x: i32, y: i32) -> u8 {
+    let c = *classes.get(x, y);
+    let mut mask = 0;
+    for (dx, dy, bit) in NEIGHBOURS {
+        let (nx, ny) = (x + dx, y + dy);
+        let off = nx < 0 || ny < 0 || nx >= classes.w || ny >= classes.h;
+        if off || *classes.get(nx, ny) == c { mask |= bit; }
+    }
+    reduce(mask)
+}
+
+/// Whether `blob_mask` can ever return `mask`.
+pub fn is_blob_mask(mask: u8) -> bool { reduce(mask) == mask }
+
+/// Tiles for classes that don't name their own.
+pub fn stock_tiles(role: ClassRole) -> ClassTiles {
+    let (tileset, ids, edge): (&str, &[u32], &[u32]) = match role {
+        ClassRole::Ground => ("terrain1_256x128", &[2, 9, 10], &[0, 1, 3]),
+        ClassRole::Forest => ("forest_256x128", &[2, 8, 9], &[4, 10, 13]),
+        ClassRole::Water => ("water_256x128", &[0, 3], &[2, 5]),
+        ClassRole::Mountain => ("terrain3_256x128", &[0, 2, 3], &[5, 6]),
+        ClassRole::None => ("terrain2_256x128", &[0], &[]),
+    };
+    ClassTiles { tileset: tileset.to_string(), ids: ids.to_vec(), edge: edge.to_vec(), blob: Vec::new() }
+}
+
+/// Each class's own tiles, or the stock ones for its role.
+pub fn class_tiles(defs: &[TerrainClassDef]) -> Vec<ClassTiles> {
+    defs.iter().map(|d| d.tiles.clone().unwrap_or_else(|| stock_tiles(d.role))).collect()
+}
+
+/// Stable per-cell variant pick, so re-exports don't shuffle tiles.
+pub fn variant(x: i32, y: i32, n: usize) -> usize {
+    let h = (x as u32).wrapping_mul(0x9E37_79B1) ^ (y as u32).wrapping_mul(0x85EB_CA77);
+    (h ^ (h >> 15)) as usize % n.max(1)
+}
+
+/// One tile: a local id in `TileLayer::tilesets[tileset]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileRef {
+    pub tileset: u16,
+    pub id: u32,
+}
+
+pub struct TileLayer {
+    /// Tileset file stems, in first-use order by class.
+    pub tilesets: Vec<String>,
+    /// None for cells whose class has no entry in the class table.
+    pub tiles: Grid<Option<TileRef>>,
+}
+
+/// Tile for the cell at (x, y) with neighbour `mask`, from `tiles`.
+pub fn pick_tile(tiles: &ClassTiles, mask: u8, x: i32, y: i32) -> Option<u32> {
+    if let Some(&(_, id)) = tiles.blob.iter().find(|&&(m, _)| m == mask) {
+        return Some(id);
+    }
+    let pool = if mask != 0xFF && !tiles.edge.is_empty() { &tiles.edge } else { &tiles.ids };
+    pool.get(variant(x, y, pool.len())).copied()
+}
+
+/// Tile every cell of `classes`; `tiles` is indexed by class (see `class_tiles`).
+pub fn autotile(classes: &Grid<u8>, tiles: &[ClassTiles]) -> TileLayer {
+    let mut tilesets: Vec<String> = Vec::new();
+    let set_of: Vec<u16> = tiles.iter().map(|t| {
+        let k = tilesets.iter().position(|s| *s == t.tileset).unwrap_or_else(|| {
+            tilesets.push(t.tileset.clone());
+            tilesets.len() - 1
+        });
+        k as u16
+    }).collect();
+
+    let mut out = Grid::new(classes.w, classes.h);
+    for y in 0..classes.h {
+        for x in 0..classes.w {
+            let c = *classes.get(x, y) as usize;
+            let Some(t) = tiles.get(c) else { continue };
+            let id = pick_tile(t, blob_mask(classes, x, y), x, y);
+            out.set(x, y, id.map(|id| TileRef { tileset: set_of[c], id }));
+        }
+    }
+    TileLayer { tilesets, tiles: out }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(w: i32, h: i32, cells: &[u8]) -> Grid<u8> {
+        let mut g = Grid::new(w, h);
+        for (i, &c) in cells.iter().enumerate() { g.set(i as i32 % w, i as i32 / w, c); }
+        g
+    }
+
+    fn tiles() -> ClassTiles {
+        ClassTiles { tileset: "t".to_string(), ids: vec![1, 2], edge: vec![7, 8], blob: Vec::new() }
+    }
+
+    #[test]
+    fn every_neighbourhood_lands_on_one_of_47_masks() {
+        assert_eq!((0..=255).filter(|&m| is_blob_mask(m)).count(), 47);
+        let mut seen = [false; 256];
+        for bits in 0..=255u8 {
+            // The centre's neighbours are class 1 where `bits` says so.
+            let mut g = grid(3, 3, &[0; 9]);
+            g.set(1, 1, 1);
+            for (dx, dy, bit) in NEIGHBOURS {
+                if bits & bit != 0 { g.set(1 + dx, 1 + dy, 1); }
+            }
+            let mask = blob_mask(&g, 1, 1);
+            assert!(is_blob_mask(mask), "{bits:#04x} gave {mask:#04x}");
+            seen[mask as usize] = true;
+        }
+        assert_eq!(seen.iter().filter(|&&s| s).count(), 47);
+    }
+
+    #[test]
+    fn off_map_counts_as_the_same_class() {
+        assert_eq!(blob_mask(&grid(1, 1, &[3]), 0, 0), 0xFF);
+        assert_eq!(blob_mask(&grid(2, 2, &[3; 4]), 1, 1), 0xFF);
+        // Only the east side differs, which also rules out both east corners.
+        assert_eq!(blob_mask(&grid(2, 1, &[0, 1]), 0, 0), N | S | SW | W | NW);
+    }
+
+    #[test]
+    fn edge_tiles_only_on_borders() {
+        let t = tiles();
+        assert!(t.ids.contains(&pick_tile(&t, 0xFF, 3, 4).unwrap()));
+        for mask in (0..0xFF).filter(|&m| is_blob_mask(m)) {
+            assert!(t.edge.contains(&pick_tile(&t, mask, 3, 4).unwrap()), "mask {mask:#04x}");
+        }
+        let blob = ClassTiles { blob: vec![(N | S, 30)], ..t.clone() };
+        assert_eq!(pick_tile(&blob, N | S, 0, 0), Some(30));
+        assert!(blob.edge.contains(&pick_tile(&blob, N, 0, 0).unwrap()));
+
+        // A 2x2 island of class 1 in a 6x6 field of class 0.
+        let mut cells = [0u8; 36];
+        for i in [14, 15, 20, 21] { cells[i] = 1; }
+        let classes = grid(6, 6, &cells);
+        let layer = autotile(&classes, &[t.clone(), t.clone()]);
+        for y in 0..6 {
+            for x in 0..6 {
+                let c = *classes.get(x, y);
+                let border = NEIGHBOURS.iter().any(|&(dx, dy, _)| {
+                    let (nx, ny) = (x + dx, y + dy);
+                    (0..6).contains(&nx) && (0..6).contains(&ny) && *classes.get(nx, ny) != c
+                });
+                let id = layer.tiles.get(x, y).unwrap().id;
+                assert_eq!(t.edge.contains(&id), border, "cell ({x}, {y})");
+            }
+        }
+    }
+}
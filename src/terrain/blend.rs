@@ -1,14 +1,17 @@
 use super::grid::Grid;
 use super::template::MapTemplate;
 use glam::IVec2;
-use super::landscape::{TERRAIN_GRASSLAND};
+use super::classes::TerrainClasses;
+use super::noise::{fbm_2d, lerp};
+use super::ley::LeyCorridor;
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct BlendSettings {
     /// Number of blur→relabel iterations (2–4 is typical).
     pub iterations: usize,
     /// Per-class blur radii (tiles). e.g., forest a bit fuzzier than water.
-    pub radii: [i32; 4],
+    /// Classes past the end use their registry `blend_radius`.
+    pub radii: Vec<i32>,
     /// Bias to keep the current label (0.0–1.0). ~0.25 keeps interiors stable.
     pub inertia: f32,
     /// If true, only blend pixels that sit on a class boundary.
@@ -19,17 +22,17 @@ impl Default for BlendSettings {
     fn default() -> Self {
         Self {
             iterations: 3,
-            radii: [2, 3, 2, 3], // grass, forest, water, mountain
+            radii: Vec::new(), // from the class registry
             inertia: 0.25,
             boundary_only: true,
         }
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct FractalSettings {
     pub iterations: usize,      // 1–3; more = stronger, slower
-    pub radii: [i32; 4],        // blur radii per class (grass, forest, water, mountain)
+    pub radii: Vec<i32>,        // blur radii per class id (missing = registry blend_radius)
     pub inertia: f32,           // 0..1 bias to keep current label
     pub boundary_only: bool,    // only modify boundary pixels
     pub warp_amp: f32,          // max displacement in pixels (e.g., 3.5)
@@ -44,7 +47,7 @@ impl Default for FractalSettings {
     fn default() -> Self {
         Self {
             iterations: 2,
-            radii: [2, 3, 2, 3],
+            radii: Vec::new(),
             inertia: 0.25,
             boundary_only: true,
             warp_amp: 4.0,
@@ -64,7 +67,7 @@ impl Default for FractalSettings {
 
 fn circle_mask(size: IVec2, c: IVec2, r: i32) -> Vec<u8> {
     let (w,h) = (size.x, size.y);
-    let r2 = (r.max(0) * r.max(0)) as i32;
+    let r2 = r.max(0) * r.max(0);
     let mut m = vec![0u8; (w*h) as usize];
     let xmin = (c.x - r).max(0);
     let xmax = (c.x + r).min(w-1);
@@ -79,7 +82,7 @@ fn circle_mask(size: IVec2, c: IVec2, r: i32) -> Vec<u8> {
     m
 }
 
-fn build_locked_mask(
+pub(crate) fn build_locked_mask(
     tpl: &MapTemplate,
     base_centers: &[IVec2],
     shrines: &[IVec2],
@@ -151,51 +154,6 @@ fn box_blur(w: i32, h: i32, src: &[f32], radius: i32, dst: &mut [f32]) {
     }
 }
 
-#[inline] fn fade(t: f32) -> f32 { t * t * (3.0 - 2.0 * t) }
-#[inline] fn lerp(a: f32, b: f32, t: f32) -> f32 { a + (b - a) * t }
-
-// Hash a lattice point (xi, yi) + seed into [0,1)
-fn hash01_lattice(xi: i32, yi: i32, seed: u32) -> f32 {
-    let mut v = (xi as u32).wrapping_mul(0x9E3779B1)
-        ^ (yi as u32).wrapping_mul(0x85EBCA77)
-        ^ seed.wrapping_mul(0xC2B2AE3D);
-    v ^= v >> 16; v = v.wrapping_mul(0x7feb352d);
-    v ^= v >> 15; v = v.wrapping_mul(0x846ca68b);
-    v ^= v >> 16;
-    (v as f32) / (u32::MAX as f32) // [0,1)
-}
-
-// Value noise with smooth bilinear interpolation, output in [-1,1]
-fn value_noise_2d(x: f32, y: f32, seed: u32) -> f32 {
-    let x0 = x.floor() as i32; let y0 = y.floor() as i32;
-    let tx = x - x0 as f32;    let ty = y - y0 as f32;
-    let n00 = hash01_lattice(x0,     y0,     seed);
-    let n10 = hash01_lattice(x0 + 1, y0,     seed);
-    let n01 = hash01_lattice(x0,     y0 + 1, seed);
-    let n11 = hash01_lattice(x0 + 1, y0 + 1, seed);
-    let ux = fade(tx); let uy = fade(ty);
-    let a = lerp(n00, n10, ux);
-    let b = lerp(n01, n11, ux);
-    let v = lerp(a, b, uy);
-    v * 2.0 - 1.0 // [-1,1]
-}
-
-// Fractal Brownian Motion
-fn fbm_2d(x: f32, y: f32, seed: u32, octaves: u32, gain: f32, lacunarity: f32) -> f32 {
-    let mut amp = 1.0;
-    let mut freq = 1.0;
-    let mut sum = 0.0;
-    let mut norm = 0.0;
-    for o in 0..octaves {
-        let v = value_noise_2d(x * freq, y * freq, seed.wrapping_add(o));
-        sum += v * amp;
-        norm += amp;
-        amp *= gain;
-        freq *= lacunarity;
-    }
-    if norm > 0.0 { sum / norm } else { 0.0 }
-}
-
 // Bilinear sample from a scalar buffer
 fn sample_scalar(buf: &[f32], w: i32, h: i32, x: f32, y: f32) -> f32 {
     let x = x.clamp(0.0, (w - 1) as f32);
@@ -218,6 +176,7 @@ pub fn blend_terrain(
     tpl: &MapTemplate,
     base_centers: &[IVec2],
     shrines: &[IVec2],
+    corridor: Option<&LeyCorridor>,
     classes_in: &Grid<u8>,
     settings: BlendSettings,
 ) -> Grid<u8> {
@@ -226,29 +185,37 @@ pub fn blend_terrain(
 
     // Hard pins we must preserve as grass.
     let locked = build_locked_mask(tpl, base_centers, shrines);
+    let reg = TerrainClasses::from_template(tpl);
+    let ground = reg.ground();
+    let radii = reg.blend_radii(&settings.radii);
+
+    // Tiles whose class is fixed: locked grass first, then ley corridors.
+    let pin = |i: usize| -> Option<u8> {
+        if locked[i] != 0 { Some(ground) } else { corridor.and_then(|c| c.class_at(i)) }
+    };
 
     // Work buffers
     let mut classes = classes_in.clone();
     let mut next = Grid::<u8>::new(w,h);
 
     // One-hot channels and blur buffers
-    let mut chan = [vec![0f32; total], vec![0f32; total], vec![0f32; total], vec![0f32; total]];
-    let mut blurred = [vec![0f32; total], vec![0f32; total], vec![0f32; total], vec![0f32; total]];
+    let mut chan = vec![vec![0f32; total]; reg.len()];
+    let mut blurred = vec![vec![0f32; total]; reg.len()];
 
     for _it in 0..settings.iterations {
-        // Build one-hot per class (skip locked—keep them pure grass contribution)
-        for k in 0..4 { chan[k].fill(0.0); }
+        // Build one-hot per class (pinned tiles contribute their pinned class)
+        for c in chan.iter_mut() { c.fill(0.0); }
         for y in 0..h {
             for x in 0..w {
                 let i = idx(w, x, y);
-                let k = if locked[i] != 0 { TERRAIN_GRASSLAND } else { *classes.get(x,y) };
+                let k = pin(i).unwrap_or(*classes.get(x,y));
                 chan[k as usize][i] = 1.0;
             }
         }
 
         // Blur each class channel with its own radius
-        for k in 0..4 {
-            box_blur(w, h, &chan[k], settings.radii[k], &mut blurred[k]);
+        for ((c, b), &r) in chan.iter().zip(blurred.iter_mut()).zip(radii.iter()) {
+            box_blur(w, h, c, r, b);
         }
 
         // Reassign labels (argmax), with inertia to keep the current class
@@ -257,8 +224,8 @@ pub fn blend_terrain(
                 let i = idx(w,x,y);
 
                 // Respect hard pins
-                if locked[i] != 0 {
-                    next.set(x,y, TERRAIN_GRASSLAND);
+                if let Some(k) = pin(i) {
+                    next.set(x,y, k);
                     continue;
                 }
 
@@ -273,8 +240,8 @@ pub fn blend_terrain(
                 // Score = blurred affinity + inertia if same as current class
                 let mut best_k = 0usize;
                 let mut best_s = f32::NEG_INFINITY;
-                for k in 0..4 {
-                    let mut s = blurred[k][i];
+                for (k, b) in blurred.iter().enumerate() {
+                    let mut s = b[i];
                     if k == cur { s += settings.inertia; }
                     if s > best_s { best_s = s; best_k = k; }
                 }
@@ -286,9 +253,9 @@ pub fn blend_terrain(
         classes = next.clone();
     }
 
-    // Defensive: ensure locked stay grass
+    // Defensive: ensure pinned tiles keep their class
     for y in 0..h { for x in 0..w {
-        if locked[idx(w,x,y)] != 0 { classes.set(x,y, TERRAIN_GRASSLAND); }
+        if let Some(k) = pin(idx(w,x,y)) { classes.set(x,y, k); }
     }}
 
     classes
@@ -298,6 +265,7 @@ pub fn blend_fractal(
     tpl: &MapTemplate,
     base_centers: &[IVec2],
     shrines: &[IVec2],
+    corridor: Option<&LeyCorridor>,
     classes_in: &Grid<u8>,
     settings: FractalSettings,
 ) -> Grid<u8> {
@@ -306,32 +274,40 @@ pub fn blend_fractal(
 
     // Hard grass rings to preserve
     let locked = build_locked_mask(tpl, base_centers, shrines);
+    let reg = TerrainClasses::from_template(tpl);
+    let ground = reg.ground();
+    let radii = reg.blend_radii(&settings.radii);
+
+    // Tiles whose class is fixed: locked grass first, then ley corridors.
+    let pin = |i: usize| -> Option<u8> {
+        if locked[i] != 0 { Some(ground) } else { corridor.and_then(|c| c.class_at(i)) }
+    };
 
     // Work grids
     let mut classes = classes_in.clone();
     let mut next = Grid::<u8>::new(w,h);
 
     // One-hot and blurred channels
-    let mut chan = [vec![0f32; total], vec![0f32; total], vec![0f32; total], vec![0f32; total]];
-    let mut blurred = [vec![0f32; total], vec![0f32; total], vec![0f32; total], vec![0f32; total]];
+    let mut chan = vec![vec![0f32; total]; reg.len()];
+    let mut blurred = vec![vec![0f32; total]; reg.len()];
 
     let seed_x = settings.seed.wrapping_add(0xB5297A4D);
     let seed_y = settings.seed.wrapping_add(0x68E31DA4);
 
     for _ in 0..settings.iterations {
-        // Rebuild one-hot with locked areas forced to grass
-        for k in 0..4 { chan[k].fill(0.0); }
+        // Rebuild one-hot with pinned tiles forced to their class
+        for c in chan.iter_mut() { c.fill(0.0); }
         for y in 0..h {
             for x in 0..w {
                 let i = idx(w,x,y);
-                let k = if locked[i]!=0 { TERRAIN_GRASSLAND } else { *classes.get(x,y) };
+                let k = pin(i).unwrap_or(*classes.get(x,y));
                 chan[k as usize][i] = 1.0;
             }
         }
 
         // Blur each channel with its radius
-        for k in 0..4 {
-            box_blur(w, h, &chan[k], settings.radii[k], &mut blurred[k]);
+        for ((c, b), &r) in chan.iter().zip(blurred.iter_mut()).zip(radii.iter()) {
+            box_blur(w, h, c, r, b);
         }
 
         // Domain-warped relabel
@@ -339,8 +315,8 @@ pub fn blend_fractal(
             for x in 0..w {
                 let i = idx(w,x,y);
 
-                if locked[i] != 0 {
-                    next.set(x,y, TERRAIN_GRASSLAND);
+                if let Some(k) = pin(i) {
+                    next.set(x,y, k);
                     continue;
                 }
                 if settings.boundary_only && !is_boundary(&classes, x, y) {
@@ -358,8 +334,8 @@ pub fn blend_fractal(
 
                 let mut best_k = 0usize;
                 let mut best_s = f32::NEG_INFINITY;
-                for k in 0..4 {
-                    let s = sample_scalar(&blurred[k], w, h, x as f32 + dx, y as f32 + dy)
+                for (k, b) in blurred.iter().enumerate() {
+                    let s = sample_scalar(b, w, h, x as f32 + dx, y as f32 + dy)
                         + if k == cur { settings.inertia } else { 0.0 };
                     if s > best_s { best_s = s; best_k = k; }
                 }
@@ -370,9 +346,9 @@ pub fn blend_fractal(
         classes = next.clone();
     }
 
-    // Keep locked areas grass, defensively
+    // Keep pinned tiles, defensively
     for y in 0..h { for x in 0..w {
-        if locked[idx(w,x,y)]!=0 { classes.set(x,y, TERRAIN_GRASSLAND); }
+        if let Some(k) = pin(idx(w,x,y)) { classes.set(x,y, k); }
     }}
 
     classes
@@ -0,0 +1,82 @@
+use super::template::{ClassRole, MapTemplate, Region, TerrainClassDef, TerrainClumps, TerrainWeights};
+
+/// Terrain class registry resolved from `terrain.classes`. Class ids are
+/// indices into `defs` and are what every `Grid<u8>` of classes stores.
+#[derive(Clone)]
+pub struct TerrainClasses {
+    pub defs: Vec<TerrainClassDef>,
+    ground: u8,
+}
+
+impl TerrainClasses {
+    pub fn new(defs: Vec<TerrainClassDef>) -> Self {
+        assert!(!defs.is_empty() && defs.len() <= u8::MAX as usize, "need 1..=255 terrain classes");
+        let ground = defs.iter().position(|d| d.role == ClassRole::Ground).unwrap_or(0) as u8;
+        Self { defs, ground }
+    }
+
+    pub fn from_template(tpl: &MapTemplate) -> Self {
+        Self::new(tpl.terrain.classes.clone())
+    }
+
+    #[inline] pub fn len(&self) -> usize { self.defs.len() }
+    #[inline] pub fn is_empty(&self) -> bool { self.defs.is_empty() }
+
+    /// Fill class for locked pads, corridors and leftovers.
+    #[inline] pub fn ground(&self) -> u8 { self.ground }
+
+    /// First class with `role`, if any.
+    pub fn role(&self, role: ClassRole) -> Option<u8> {
+        if role == ClassRole::Ground { return Some(self.ground); }
+        self.defs.iter().position(|d| d.role == role).map(|i| i as u8)
+    }
+
+    /// Class id by name (case-insensitive).
+    pub fn id(&self, name: &str) -> Option<u8> {
+        self.defs.iter().position(|d| d.name.eq_ignore_ascii_case(name)).map(|i| i as u8)
+    }
+
+    pub fn region(&self, r: &Region) -> Option<u8> { self.id(&r.0) }
+
+    #[inline] pub fn passable(&self, id: u8) -> bool {
+        self.defs.get(id as usize).is_some_and(|d| d.passable)
+    }
+
+    #[inline] pub fn carve_cost(&self, id: u8) -> f32 {
+        self.defs.get(id as usize).map(|d| d.carve_cost).unwrap_or(1.0)
+    }
+
+    pub fn palette(&self) -> Vec<[u8; 4]> {
+        self.defs.iter().map(|d| [d.color.0, d.color.1, d.color.2, 255]).collect()
+    }
+
+    /// Blur radius per class: explicit overrides first, then each class's own.
+    pub fn blend_radii(&self, overrides: &[i32]) -> Vec<i32> {
+        self.defs.iter().enumerate()
+            .map(|(k, d)| overrides.get(k).copied().unwrap_or(d.blend_radius))
+            .collect()
+    }
+
+    /// Area weights as one non-negative value per class id. Unknown names are
+    /// rejected by validation; here they are skipped.
+    pub fn weights(&self, w: &TerrainWeights) -> Vec<f32> {
+        let mut out = vec![0.0; self.len()];
+        for (name, v) in &w.0 {
+            let k = self.id(name);
+            debug_assert!(k.is_some(), "terrain weights: unknown class `{name}` got past validation");
+            if let Some(k) = k { out[k as usize] += v.max(0.0); }
+        }
+        out
+    }
+
+    /// Clump radius range for `id`.
+    pub fn patch(&self, id: u8, clumps: &TerrainClumps) -> (i32, i32) {
+        let d = &self.defs[id as usize];
+        d.patch.unwrap_or(match d.role {
+            ClassRole::Forest   => clumps.forest_patch,
+            ClassRole::Water    => clumps.water_patch,
+            ClassRole::Mountain => clumps.mountain_patch,
+            _                   => (4, 10),
+        })
+    }
+}
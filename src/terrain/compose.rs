@@ -0,0 +1,468 @@
+//! Template composition: `extends: "base.ron"` at the top of a template and
+//! `include: "file.ron"` (or a list of files) inside any struct block.
+//!
+//! Files are parsed into a light RON tree that keeps enum names and raw text,
+//! so the merged result is re-emitted as RON and deserialized as usual.
+//! Merge rules: named-field structs merge field by field (the including file
+//! wins); everything else — lists, tuples, enum values, scalars — is replaced
+//! whole. Paths are relative to the file that names them.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum ComposeError {
+    /// A template or one of its bases/includes could not be read.
+    Missing { path: PathBuf, from: Option<PathBuf>, source: std::io::Error },
+    /// `extends`/`include` chain that comes back to a file already being loaded.
+    Cycle(Vec<PathBuf>),
+    /// The file isn't shaped like RON this loader can compose.
+    Syntax { path: PathBuf, line: usize, col: usize, msg: String },
+    /// `extends`/`include` isn't a string or a list of strings.
+    BadInclude { path: PathBuf, value: String },
+}
+
+impl fmt::Display for ComposeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ComposeError::Missing { path, from: Some(from), source } =>
+                write!(f, "{}: cannot read {} ({source})", from.display(), path.display()),
+            ComposeError::Missing { path, from: None, source } =>
+                write!(f, "cannot read {} ({source})", path.display()),
+            ComposeError::Cycle(chain) => {
+                let names: Vec<_> = chain.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "template include cycle: {}", names.join(" -> "))
+            }
+            ComposeError::Syntax { path, line, col, msg } =>
+                write!(f, "{}:{line}:{col}: {msg}", path.display()),
+            ComposeError::BadInclude { path, value } =>
+                write!(f, "{}: extends/include must be a path or list of paths, got `{value}`", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for ComposeError {}
+
+#[derive(Clone, Debug)]
+enum Node {
+    /// `(a: .., b: ..)` or `Name(a: ..)`; fields in file order.
+    Struct { name: Option<String>, fields: Vec<Field> },
+    /// Any other value, kept as its RON source text.
+    Raw(String),
+}
+
+#[derive(Clone, Debug)]
+struct Field {
+    key: String,
+    value: Node,
+    at: Origin,
+}
+
+/// Where a field was written: file and 1-based line/column of its name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Origin {
+    pub path: PathBuf,
+    pub line: usize,
+    pub col: usize,
+}
+
+const COMPOSE_KEYS: [&str; 2] = ["extends", "include"];
+
+/// A template after composition.
+pub struct Composed {
+    pub text: String,
+    /// False when nothing was extended or included: `text` is the file as
+    /// written, so parse positions line up with it.
+    pub merged: bool,
+    /// For merged text, each field in text order: its line in `text`, its
+    /// dotted path (`terrain.blend.radius`) and where it was written.
+    pub fields: Vec<(usize, String, Origin)>,
+}
+
+impl Composed {
+    /// The innermost field covering a 1-based line of `text`.
+    pub fn field_at(&self, line: usize) -> Option<(&str, &Origin)> {
+        self.fields.iter().rev().find(|(l, ..)| *l <= line).map(|(_, f, at)| (f.as_str(), at))
+    }
+}
+
+/// File reader used during composition; paths arrive already normalized.
+pub type ReadFn<'a> = dyn FnMut(&Path) -> std::io::Result<String> + 'a;
+
+/// Resolve `path` and everything it extends/includes into one RON document.
+pub fn compose_file(path: &Path) -> Result<Composed, ComposeError> {
+    compose_with(path, &mut |p| std::fs::read_to_string(p))
+}
+
+/// `compose_file` over any file source (e.g. a Bevy asset reader).
+pub fn compose_with(path: &Path, read: &mut ReadFn) -> Result<Composed, ComposeError> {
+    let path = normalize(path);
+    let mut ctx = Ctx { read, stack: Vec::new(), merged: false };
+    let node = ctx.load(&path, None)?;
+    if !ctx.merged {
+        let text = ctx.read_file(&path, None)?;
+        return Ok(Composed { text, merged: false, fields: Vec::new() });
+    }
+    let mut out = Emitter { text: String::new(), line: 1, fields: Vec::new() };
+    out.node(&node, 0, "");
+    Ok(Composed { text: out.text, merged: true, fields: out.fields })
+}
+
+/// Fold `.` and `..` without touching the filesystem, so the same file always
+/// gets the same key (asset paths don't exist relative to the working dir).
+pub fn normalize(path: &Path) -> PathBuf {
+    use std::path::Component;
+    let mut out = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(out.components().next_back(), Some(Component::Normal(_))) => { out.pop(); }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+struct Ctx<'r, 'a> {
+    read: &'r mut ReadFn<'a>,
+    stack: Vec<PathBuf>,
+    merged: bool,
+}
+
+impl Ctx<'_, '_> {
+    fn read_file(&mut self, path: &Path, from: Option<&Path>) -> Result<String, ComposeError> {
+        (self.read)(path).map_err(|source| ComposeError::Missing {
+            path: path.to_path_buf(),
+            from: from.map(Path::to_path_buf),
+            source,
+        })
+    }
+
+    fn load(&mut self, path: &Path, from: Option<&Path>) -> Result<Node, ComposeError> {
+        if let Some(at) = self.stack.iter().position(|p| p == path) {
+            let mut chain = self.stack[at..].to_vec();
+            chain.push(path.to_path_buf());
+            return Err(ComposeError::Cycle(chain));
+        }
+        let text = self.read_file(path, from)?;
+        self.stack.push(path.to_path_buf());
+
+        let mut p = Parser { src: &text, pos: 0, path };
+        let node = p.value()?;
+        p.skip_ws();
+        if p.pos < text.len() {
+            return Err(p.error("trailing characters after template"));
+        }
+        let node = self.resolve(node, path)?;
+        self.stack.pop();
+        Ok(node)
+    }
+
+    // Expand extends/include in `node` (depth-first) relative to `path`.
+    fn resolve(&mut self, node: Node, path: &Path) -> Result<Node, ComposeError> {
+        let Node::Struct { name, fields } = node else { return Ok(node); };
+        let mut bases = Vec::new();
+        let mut own = Vec::new();
+        for f in fields {
+            if COMPOSE_KEYS.contains(&f.key.as_str()) {
+                bases.extend(include_paths(&f.value, path)?);
+            } else {
+                own.push(Field { value: self.resolve(f.value, path)?, ..f });
+            }
+        }
+        let mut out = Node::Struct { name, fields: own };
+        if bases.is_empty() { return Ok(out); }
+        self.merged = true;
+
+        // Earlier bases are overridden by later ones, and all of them by this block.
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut acc: Option<Node> = None;
+        for rel in bases {
+            let base = self.load(&normalize(&dir.join(rel)), Some(path))?;
+            acc = Some(match acc { Some(m) => merge(m, base), None => base });
+        }
+        if let Some(m) = acc { out = merge(m, out); }
+        Ok(out)
+    }
+}
+
+fn include_paths(v: &Node, path: &Path) -> Result<Vec<String>, ComposeError> {
+    let bad = |value: String| ComposeError::BadInclude { path: path.to_path_buf(), value };
+    let Node::Raw(text) = v else { return Err(bad("a struct".into())); };
+    ron::from_str::<String>(text).map(|s| vec![s])
+        .or_else(|_| ron::from_str::<Vec<String>>(text))
+        .map_err(|_| bad(text.clone()))
+}
+
+fn merge(base: Node, over: Node) -> Node {
+    match (base, over) {
+        (Node::Struct { name: bn, fields: mut bf }, Node::Struct { name: on, fields: of })
+            if bn.is_none() || on.is_none() || bn == on =>
+        {
+            for f in of {
+                match bf.iter_mut().find(|b| b.key == f.key) {
+                    Some(slot) => {
+                        let old = std::mem::replace(&mut slot.value, Node::Raw(String::new()));
+                        slot.value = merge(old, f.value);
+                        slot.at = f.at;
+                    }
+                    None => bf.push(f),
+                }
+            }
+            Node::Struct { name: on.or(bn), fields: bf }
+        }
+        (_, over) => over,
+    }
+}
+
+// Writes merged RON, one field per line, noting where each field landed.
+struct Emitter {
+    text: String,
+    line: usize,
+    fields: Vec<(usize, String, Origin)>,
+}
+
+impl Emitter {
+    fn push(&mut self, s: &str) {
+        self.line += s.matches('\n').count();
+        self.text.push_str(s);
+    }
+
+    fn node(&mut self, node: &Node, indent: usize, prefix: &str) {
+        match node {
+            Node::Raw(t) => self.push(t),
+            Node::Struct { name, fields } => {
+                if let Some(n) = name { self.push(n); }
+                self.push("(\n");
+                for f in fields {
+                    let field = if prefix.is_empty() { f.key.clone() } else { format!("{prefix}.{}", f.key) };
+                    self.fields.push((self.line, field.clone(), f.at.clone()));
+                    self.push(&"  ".repeat(indent + 1));
+                    self.push(&f.key);
+                    self.push(": ");
+                    self.node(&f.value, indent + 1, &field);
+                    self.push(",\n");
+                }
+                self.push(&"  ".repeat(indent));
+                self.push(")");
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    path: &'a Path,
+}
+
+impl Parser<'_> {
+    // 1-based line and column of the current position.
+    fn line_col(&self) -> (usize, usize) {
+        let before = &self.src[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let col = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+        (line, col)
+    }
+
+    fn error(&self, msg: &str) -> ComposeError {
+        let (line, col) = self.line_col();
+        ComposeError::Syntax { path: self.path.to_path_buf(), line, col, msg: msg.to_string() }
+    }
+
+    fn peek(&self) -> Option<char> { self.src[self.pos..].chars().next() }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_ws(&mut self) {
+        loop {
+            let rest = &self.src[self.pos..];
+            if rest.starts_with("//") {
+                self.pos += rest.find('\n').unwrap_or(rest.len());
+            } else if rest.starts_with("/*") {
+                self.pos += rest.find("*/").map_or(rest.len(), |e| e + 2);
+            } else if rest.starts_with(|c: char| c.is_whitespace()) {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn ident(&mut self) -> Option<&str> {
+        let rest = &self.src[self.pos..];
+        let len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        if len == 0 || rest.starts_with(|c: char| c.is_ascii_digit()) { return None; }
+        self.pos += len;
+        Some(&rest[..len])
+    }
+
+    // At `(`: does a named field (`ident :`) follow?
+    fn struct_follows(&self) -> bool {
+        let mut p = Parser { src: self.src, pos: self.pos + 1, path: self.path };
+        p.skip_ws();
+        if p.ident().is_none() { return false; }
+        p.skip_ws();
+        p.peek() == Some(':')
+    }
+
+    fn value(&mut self) -> Result<Node, ComposeError> {
+        self.skip_ws();
+        let start = self.pos;
+        let name = self.ident().map(str::to_string);
+        if self.peek() == Some('(') && self.struct_follows() {
+            return self.struct_body(name);
+        }
+        self.pos = start;
+        self.raw()
+    }
+
+    fn struct_body(&mut self, name: Option<String>) -> Result<Node, ComposeError> {
+        self.bump(); // (
+        let mut fields: Vec<Field> = Vec::new();
+        loop {
+            self.skip_ws();
+            if self.peek() == Some(')') { self.bump(); break; }
+            let (line, col) = self.line_col();
+            let Some(key) = self.ident().map(str::to_string) else {
+                return Err(self.error("expected a field name"));
+            };
+            self.skip_ws();
+            if self.bump() != Some(':') { return Err(self.error("expected `:` after field name")); }
+            let value = self.value()?;
+            if fields.iter().any(|f| f.key == key) {
+                return Err(self.error(&format!("duplicate field `{key}`")));
+            }
+            fields.push(Field { key, value, at: Origin { path: self.path.to_path_buf(), line, col } });
+            self.skip_ws();
+            match self.bump() {
+                Some(',') => {}
+                Some(')') => break,
+                _ => return Err(self.error("expected `,` or `)`")),
+            }
+        }
+        Ok(Node::Struct { name, fields })
+    }
+
+    // Scan one value verbatim up to the `,` or closing bracket that ends it.
+    fn raw(&mut self) -> Result<Node, ComposeError> {
+        let start = self.pos;
+        let mut end = self.pos;
+        let mut depth = 0usize;
+        loop {
+            self.skip_ws();
+            let Some(c) = self.peek() else { break; };
+            match c {
+                '(' | '[' | '{' => { depth += 1; self.bump(); }
+                ')' | ']' | '}' => {
+                    if depth == 0 { break; }
+                    depth -= 1;
+                    self.bump();
+                }
+                ',' if depth == 0 => break,
+                '"' => self.string('"')?,
+                '\'' => self.string('\'')?,
+                _ => { self.bump(); }
+            }
+            end = self.pos;
+        }
+        if end == start { return Err(self.error("expected a value")); }
+        Ok(Node::Raw(self.src[start..end].to_string()))
+    }
+
+    fn string(&mut self, quote: char) -> Result<(), ComposeError> {
+        self.bump();
+        loop {
+            match self.bump() {
+                Some('\\') => { self.bump(); }
+                Some(c) if c == quote => return Ok(()),
+                Some(_) => {}
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn compose(files: &[(&str, &str)], root: &str) -> Result<Composed, ComposeError> {
+        let files: HashMap<PathBuf, String> = files.iter()
+            .map(|(p, t)| (PathBuf::from(p), t.to_string()))
+            .collect();
+        compose_with(Path::new(root), &mut |p| {
+            files.get(p).cloned().ok_or_else(|| std::io::ErrorKind::NotFound.into())
+        })
+    }
+
+    // Composed text with whitespace dropped, so layout doesn't matter.
+    fn flat(c: &Composed) -> String { c.text.split_whitespace().collect() }
+
+    #[test]
+    fn plain_file_is_passed_through() {
+        let c = compose(&[("maps/a.ron", "(size: (64, 64))")], "maps/a.ron").unwrap();
+        assert!(!c.merged);
+        assert_eq!(c.text, "(size: (64, 64))");
+    }
+
+    #[test]
+    fn nested_structs_merge_and_the_extender_wins() {
+        let c = compose(&[
+            ("maps/base.ron", "(size: (64, 64), terrain: (blend: (radius: 3, passes: 2), seed: 1))"),
+            ("maps/a.ron", "(extends: \"base.ron\", terrain: (blend: (radius: 5)))"),
+        ], "maps/a.ron").unwrap();
+        assert!(c.merged);
+        assert_eq!(flat(&c), "(size:(64,64),terrain:(blend:(radius:5,passes:2,),seed:1,),)");
+    }
+
+    #[test]
+    fn lists_and_other_variants_are_replaced_whole() {
+        let c = compose(&[
+            ("maps/base.ron", "(pipeline: [Bases, Ley, Terrain], shape: Annulus(inner: 2, radius: 9))"),
+            ("maps/a.ron", "(extends: \"base.ron\", pipeline: [Bases], shape: Disc(radius: 4))"),
+        ], "maps/a.ron").unwrap();
+        assert_eq!(flat(&c), "(pipeline:[Bases],shape:Disc(radius:4,),)");
+    }
+
+    #[test]
+    fn include_chain_resolves_relative_to_each_file() {
+        let c = compose(&[
+            ("maps/a.ron", "(terrain: (include: \"parts/t.ron\", seed: 3))"),
+            ("maps/parts/t.ron", "(include: [\"../common/blend.ron\"], seed: 2, scale: 1.5)"),
+            ("maps/common/blend.ron", "(blend: (radius: 4), seed: 1)"),
+        ], "maps/a.ron").unwrap();
+        assert_eq!(flat(&c), "(terrain:(blend:(radius:4,),seed:3,scale:1.5,),)");
+    }
+
+    #[test]
+    fn extends_cycle_is_an_error() {
+        let err = compose(&[
+            ("maps/a.ron", "(extends: \"b.ron\")"),
+            ("maps/b.ron", "(extends: \"./a.ron\")"),
+        ], "maps/a.ron").err().unwrap();
+        let ComposeError::Cycle(chain) = &err else { panic!("expected a cycle, got {err}") };
+        assert_eq!(chain, &[PathBuf::from("maps/a.ron"), "maps/b.ron".into(), "maps/a.ron".into()]);
+        assert_eq!(err.to_string(), "template include cycle: maps/a.ron -> maps/b.ron -> maps/a.ron");
+    }
+
+    #[test]
+    fn missing_base_names_the_file_that_asked_for_it() {
+        let err = compose(&[("maps/a.ron", "(extends: \"gone.ron\")")], "maps/a.ron").err().unwrap();
+        let ComposeError::Missing { path, from, .. } = &err else { panic!("expected missing, got {err}") };
+        assert_eq!(path, Path::new("maps/gone.ron"));
+        assert_eq!(from.as_deref(), Some(Path::new("maps/a.ron")));
+        assert!(err.to_string().starts_with("maps/a.ron: cannot read maps/gone.ron"), "{err}");
+    }
+
+    #[test]
+    fn bad_include_value_is_rejected() {
+        let err = compose(&[("maps/a.ron", "(include: 3)")], "maps/a.ron").err().unwrap();
+        assert!(matches!(err, ComposeError::BadInclude { ref value, .. } if value == "3"), "{err}");
+    }
+}
@@ -0,0 +1,245 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use glam::IVec2;
+use super::grid::Grid;
+use super::classes::TerrainClasses;
+
+#[derive(Clone, Copy)]
+pub struct ConnectivitySettings {
+    /// Carve corridors when something is cut off (otherwise only report).
+    pub carve: bool,
+    /// Corridor brush radius in tiles (0 = 1-tile path).
+    pub corridor_width: i32,
+}
+
+impl Default for ConnectivitySettings {
+    fn default() -> Self {
+        Self { carve: true, corridor_width: 1 }
+    }
+}
+
+/// Result of the post-Phase-4 connectivity pass.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectivityReport {
+    /// Base indices that could not reach base 0 overland before carving.
+    pub disconnected_bases: Vec<usize>,
+    /// Shrine indices that could not reach base 0 overland before carving.
+    pub disconnected_shrines: Vec<usize>,
+    /// Carved corridors as tile paths (from the connected side outwards).
+    pub corridors: Vec<Vec<IVec2>>,
+    /// Every base and shrine shares one overland component after this pass.
+    pub connected: bool,
+}
+
+
+const NEIGHBORS4: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+#[inline] fn idx(w: i32, x: i32, y: i32) -> usize { (y * w + x) as usize }
+
+/// Label 4-connected passable regions; impassable tiles get `u32::MAX`.
+pub fn passable_components(classes: &Grid<u8>, reg: &TerrainClasses) -> Vec<u32> {
+    let (w, h) = (classes.w, classes.h);
+    let mut labels = vec![u32::MAX; (w * h) as usize];
+    let mut next = 0u32;
+    let mut queue = VecDeque::new();
+    for y in 0..h {
+        for x in 0..w {
+            let i = idx(w, x, y);
+            if labels[i] != u32::MAX || !reg.passable(*classes.get(x, y)) { continue; }
+            labels[i] = next;
+            queue.push_back(IVec2::new(x, y));
+            while let Some(p) = queue.pop_front() {
+                for (dx, dy) in NEIGHBORS4 {
+                    let n = p + IVec2::new(dx, dy);
+                    if !classes.in_bounds(n) { continue; }
+                    let j = idx(w, n.x, n.y);
+                    if labels[j] == u32::MAX && reg.passable(*classes.get(n.x, n.y)) {
+                        labels[j] = next;
+                        queue.push_back(n);
+                    }
+                }
+            }
+            next += 1;
+        }
+    }
+    labels
+}
+
+fn label_at(labels: &[u32], w: i32, h: i32, p: IVec2) -> u32 {
+    if p.x < 0 || p.y < 0 || p.x >= w || p.y >= h { return u32::MAX; }
+    labels[idx(w, p.x, p.y)]
+}
+
+// Integer step cost (x10) so the heap can order exactly. Passable tiles cost 1.
+fn step_cost(class: u8, reg: &TerrainClasses) -> u32 {
+    let c = if reg.passable(class) { 1.0 } else { reg.carve_cost(class) };
+    (c.max(1.0) * 10.0).round() as u32
+}
+
+/// Multi-source Dijkstra from the reference component to the nearest tile of any
+/// `goal` component. Returns the tile path (reference side first).
+fn cheapest_link(
+    classes: &Grid<u8>,
+    labels: &[u32],
+    reference: u32,
+    goals: &[u32],
+    reg: &TerrainClasses,
+) -> Option<Vec<IVec2>> {
+    let (w, h) = (classes.w, classes.h);
+    let total = (w * h) as usize;
+    let mut dist = vec![u32::MAX; total];
+    let mut prev = vec![usize::MAX; total];
+    let mut heap = BinaryHeap::new();
+    for (i, &l) in labels.iter().enumerate() {
+        if l == reference {
+            dist[i] = 0;
+            heap.push(Reverse((0u32, i)));
+        }
+    }
+    while let Some(Reverse((d, i))) = heap.pop() {
+        if d > dist[i] { continue; }
+        if goals.contains(&labels[i]) {
+            let mut path = vec![i];
+            let mut cur = i;
+            while prev[cur] != usize::MAX {
+                cur = prev[cur];
+                path.push(cur);
+            }
+            path.reverse();
+            return Some(path.into_iter()
+                .map(|i| IVec2::new(i as i32 % w, i as i32 / w))
+                .collect());
+        }
+        let p = IVec2::new(i as i32 % w, i as i32 / w);
+        for (dx, dy) in NEIGHBORS4 {
+            let n = p + IVec2::new(dx, dy);
+            if n.x < 0 || n.y < 0 || n.x >= w || n.y >= h { continue; }
+            let j = idx(w, n.x, n.y);
+            let nd = d + step_cost(*classes.get(n.x, n.y), reg);
+            if nd < dist[j] {
+                dist[j] = nd;
+                prev[j] = i;
+                heap.push(Reverse((nd, j)));
+            }
+        }
+    }
+    None
+}
+
+/// Turn impassable tiles within `r` of `path` into ground.
+pub fn carve_corridor(classes: &mut Grid<u8>, reg: &TerrainClasses, path: &[IVec2], r: i32) {
+    let r = r.max(0);
+    for &c in path {
+        for y in (c.y - r).max(0)..=(c.y + r).min(classes.h - 1) {
+            for x in (c.x - r).max(0)..=(c.x + r).min(classes.w - 1) {
+                let (dx, dy) = (x - c.x, y - c.y);
+                if dx*dx + dy*dy <= r*r && !reg.passable(*classes.get(x, y)) {
+                    classes.set(x, y, reg.ground());
+                }
+            }
+        }
+    }
+}
+
+/// Post-Phase-4 pass: flood-fill passable terrain, report bases/shrines that
+/// can't reach base 0 overland, and (optionally) carve the cheapest ground
+/// corridors until everything is joined. Passability and carve costs come
+/// from the class registry.
+pub fn ensure_connectivity(
+    classes_in: &Grid<u8>,
+    reg: &TerrainClasses,
+    base_centers: &[IVec2],
+    shrines: &[IVec2],
+    settings: ConnectivitySettings,
+) -> (Grid<u8>, ConnectivityReport) {
+    let mut classes = classes_in.clone();
+    let mut report = ConnectivityReport::default();
+    let (w, h) = (classes.w, classes.h);
+    let Some(&home) = base_centers.first() else {
+        report.connected = true;
+        return (classes, report);
+    };
+
+    let targets: Vec<IVec2> = base_centers.iter().chain(shrines.iter()).copied().collect();
+    let mut labels = passable_components(&classes, reg);
+    let reference = label_at(&labels, w, h, home);
+
+    for (i, &c) in base_centers.iter().enumerate() {
+        if label_at(&labels, w, h, c) != reference { report.disconnected_bases.push(i); }
+    }
+    for (i, &s) in shrines.iter().enumerate() {
+        if label_at(&labels, w, h, s) != reference { report.disconnected_shrines.push(i); }
+    }
+
+    if settings.carve && reference != u32::MAX {
+        // Each corridor merges at least one component, so this terminates.
+        for _ in 0..targets.len() {
+            let reference = label_at(&labels, w, h, home);
+            let mut goals: Vec<u32> = targets.iter()
+                .map(|&t| label_at(&labels, w, h, t))
+                .filter(|&l| l != reference && l != u32::MAX)
+                .collect();
+            goals.dedup();
+            if goals.is_empty() { break; }
+            let Some(path) = cheapest_link(&classes, &labels, reference, &goals, reg) else { break; };
+            carve_corridor(&mut classes, reg, &path, settings.corridor_width);
+            report.corridors.push(path);
+            labels = passable_components(&classes, reg);
+        }
+    }
+
+    let reference = label_at(&labels, w, h, home);
+    report.connected = reference != u32::MAX
+        && targets.iter().all(|&t| label_at(&labels, w, h, t) == reference);
+
+    (classes, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::template::default_terrain_classes;
+
+    const GROUND: u8 = 0;
+    const WATER: u8 = 2;
+
+    // 20x20 ground split by a water wall down x = 10, one base either side.
+    fn walled() -> (Grid<u8>, TerrainClasses, Vec<IVec2>) {
+        let mut classes = Grid::new(20, 20);
+        for y in 0..20 { classes.set(10, y, WATER); }
+        (classes, TerrainClasses::new(default_terrain_classes()), vec![IVec2::new(3, 10), IVec2::new(16, 10)])
+    }
+
+    #[test]
+    fn walled_off_base_gets_a_corridor() {
+        let (classes, reg, bases) = walled();
+        let (out, report) = ensure_connectivity(&classes, &reg, &bases, &[], ConnectivitySettings { carve: true, corridor_width: 0 });
+        assert_eq!(report.disconnected_bases, vec![1]);
+        assert_eq!(report.corridors.len(), 1);
+        assert!(report.connected);
+        let labels = passable_components(&out, &reg);
+        assert_eq!(label_at(&labels, 20, 20, bases[0]), label_at(&labels, 20, 20, bases[1]));
+        // A 1-tile path only needs to break the wall once.
+        assert_eq!((0..20).filter(|&y| *out.get(10, y) == GROUND).count(), 1);
+    }
+
+    #[test]
+    fn report_only_leaves_the_map_alone() {
+        let (classes, reg, bases) = walled();
+        let (out, report) = ensure_connectivity(&classes, &reg, &bases, &[], ConnectivitySettings { carve: false, corridor_width: 1 });
+        assert_eq!(report.disconnected_bases, vec![1]);
+        assert!(report.corridors.is_empty());
+        assert!(!report.connected);
+        assert_eq!(out.as_slice(), classes.as_slice());
+    }
+
+    #[test]
+    fn connected_map_needs_no_corridor() {
+        let (_, reg, bases) = walled();
+        let classes = Grid::new(20, 20);
+        let (_, report) = ensure_connectivity(&classes, &reg, &bases, &[IVec2::new(10, 2)], ConnectivitySettings::default());
+        assert!(report.disconnected_bases.is_empty() && report.disconnected_shrines.is_empty());
+        assert!(report.corridors.is_empty());
+        assert!(report.connected);
+    }
+}
@@ -133,20 +133,52 @@ pub fn write_height_with_overlays(
     img.save(path).expect("save png");
 }
 
+// Ids past the palette show up magenta so they're easy to spot.
+#[inline] fn palette_color(palette: &[[u8; 4]], id: u8) -> [u8; 4] {
+    palette.get(id as usize).copied().unwrap_or([255, 0, 255, 255])
+}
+
 pub fn write_terrain_classes(
     path: &str,
-    classes: &Grid<u8>,                 // 0..=3 terrain IDs
-    palette: &[[u8; 4]; 4],             // RGBA for each class
+    classes: &Grid<u8>,                 // terrain class ids
+    palette: &[[u8; 4]],                // RGBA for each class id
 ) {
+    terrain_image(classes, palette).save(path).expect("save png");
+}
+
+/// Terrain classes as an in-memory image (one pixel per tile).
+pub fn terrain_image(classes: &Grid<u8>, palette: &[[u8; 4]]) -> image::RgbaImage {
     let (w, h) = (classes.w as u32, classes.h as u32);
     let mut img = ImageBuffer::<Rgba<u8>, Vec<u8>>::new(w, h);
     for y in 0..classes.h {
         for x in 0..classes.w {
-            let id = *classes.get(x, y).min(&3u8);
-            let c = palette[id as usize];
+            let c = palette_color(palette, *classes.get(x, y));
             img.put_pixel(x as u32, y as u32, Rgba(c));
         }
     }
+    img
+}
+
+/// Terrain classes with polylines on top (e.g., rivers), each drawn as
+/// connected Bresenham segments in its own colour.
+pub fn write_terrain_with_polylines(
+    path: &str,
+    classes: &Grid<u8>,
+    palette: &[[u8; 4]],
+    polylines: &[(&[IVec2], [u8; 4])],
+) {
+    let (w, h) = (classes.w as u32, classes.h as u32);
+    let mut img = ImageBuffer::<Rgba<u8>, Vec<u8>>::new(w, h);
+    for y in 0..classes.h {
+        for x in 0..classes.w {
+            img.put_pixel(x as u32, y as u32, Rgba(palette_color(palette, *classes.get(x, y))));
+        }
+    }
+    for &(pts, color) in polylines {
+        for seg in pts.windows(2) {
+            draw_line(&mut img, seg[0], seg[1], color);
+        }
+    }
     img.save(path).expect("save png");
 }
 
@@ -201,39 +233,74 @@ fn draw_filled_disk(img: &mut image::RgbaImage, c: IVec2, r: i32, rgba: [u8;4])
 pub fn write_terrain_with_objects(
     path: &str,
     classes: &Grid<u8>,
-    palette: &[[u8;4];4],
+    palette: &[[u8;4]],
     objects: &[PlacedObject],
     tpl: &MapTemplate,
 ) {
+    let _ = terrain_with_objects_image(classes, palette, objects, tpl).save(path);
+}
+
+/// `write_terrain_with_objects` without the file.
+pub fn terrain_with_objects_image(
+    classes: &Grid<u8>,
+    palette: &[[u8;4]],
+    objects: &[PlacedObject],
+    tpl: &MapTemplate,
+) -> image::RgbaImage {
     // 1) Base terrain
-    write_terrain_classes(path, classes, palette);
-
-    // 2) Load and overlay
-    if let Ok(dyn_img) = image::open(path) {
-        let mut img = dyn_img.to_rgba8();
-
-        // Precompute colors and draw radii for each type
-        let mut type_colors: Vec<[u8;4]> = Vec::with_capacity(tpl.objects.types.len());
-        let mut type_draw_r: Vec<i32> = Vec::with_capacity(tpl.objects.types.len());
-        for (i, t) in tpl.objects.types.iter().enumerate() {
-            type_colors.push(color_for_type(i, t));
-            // smaller than the hard min-distance radius so dots don’t look huge
-            let r = (t.radius as f32 * 0.6).round() as i32;
-            type_draw_r.push(r.max(1));
-        }
+    let mut img = terrain_image(classes, palette);
 
-        for o in objects {
-            let k = o.kind as usize;
-            if k >= type_colors.len() { continue; }
-            let color = type_colors[k];
-            let rdraw = type_draw_r[k];
-            draw_filled_disk(&mut img, o.pos, rdraw, color);
+    // 2) Overlay
+    // Precompute colors and draw radii for each type
+    let mut type_colors: Vec<[u8;4]> = Vec::with_capacity(tpl.objects.types.len());
+    let mut type_draw_r: Vec<i32> = Vec::with_capacity(tpl.objects.types.len());
+    for (i, t) in tpl.objects.types.iter().enumerate() {
+        type_colors.push(color_for_type(i, t));
+        // smaller than the hard min-distance radius so dots don’t look huge
+        let r = (t.radius as f32 * 0.6).round() as i32;
+        type_draw_r.push(r.max(1));
+    }
 
-            // Optional: add a thin outline for visibility on light tiles
-            // Uncomment if you want:
-            // draw_filled_disk(&mut img, o.pos, (rdraw+1).min(rdraw+1), [0,0,0,80]);
-        }
+    for o in objects {
+        let k = o.kind as usize;
+        if k >= type_colors.len() { continue; }
+        let color = type_colors[k];
+        let rdraw = type_draw_r[k];
+        draw_filled_disk(&mut img, o.pos, rdraw, color);
 
-        let _ = img.save(path);
+        // Optional: add a thin outline for visibility on light tiles
+        // Uncomment if you want:
+        // draw_filled_disk(&mut img, o.pos, (rdraw+1).min(rdraw+1), [0,0,0,80]);
+    }
+    img
+}
+
+/// `img` scaled to `thumb` pixels on its longer side.
+pub fn thumbnail(img: &image::RgbaImage, thumb: u32) -> image::RgbaImage {
+    let scale = thumb as f32 / img.width().max(img.height()).max(1) as f32;
+    let (tw, th) = ((img.width() as f32 * scale) as u32, (img.height() as f32 * scale) as u32);
+    image::imageops::resize(img, tw.max(1), th.max(1), image::imageops::FilterType::Triangle)
+}
+
+/// Thumbnails laid out row-major, `cols` per row, each inside a frame of its
+/// own colour (e.g. red for a map that failed a check). Thumbnails should
+/// already be `thumb` pixels on their longer side (see `thumbnail`).
+pub fn write_contact_sheet(path: &str, tiles: &[(image::RgbaImage, [u8;4])], cols: usize, thumb: u32) {
+    const FRAME: u32 = 3;
+    let cols = cols.max(1);
+    let rows = tiles.len().div_ceil(cols);
+    let cell = thumb + 2 * FRAME;
+    let mut sheet = ImageBuffer::from_pixel(cols as u32 * cell, rows as u32 * cell, Rgba([24, 24, 24, 255]));
+    for (i, (img, frame)) in tiles.iter().enumerate() {
+        let (x0, y0) = ((i % cols) as u32 * cell, (i / cols) as u32 * cell);
+        for y in 0..cell {
+            for x in 0..cell {
+                if x < FRAME || y < FRAME || x >= cell - FRAME || y >= cell - FRAME {
+                    sheet.put_pixel(x0 + x, y0 + y, Rgba(*frame));
+                }
+            }
+        }
+        image::imageops::overlay(&mut sheet, img, (x0 + FRAME) as i64, (y0 + FRAME) as i64);
     }
+    sheet.save(path).expect("save contact sheet");
 }
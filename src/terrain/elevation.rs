@@ -0,0 +1,114 @@
+use glam::IVec2;
+use super::grid::Grid;
+use super::template::MapTemplate;
+use super::noise::{fbm_2d, ridged_2d, lerp};
+
+#[derive(Clone, Copy)]
+pub struct ElevationSettings {
+    pub amplitude: f32,         // +/- height around the base elevation
+    pub frequency: f32,         // fBm base frequency in cycles per tile (e.g., 1/64)
+    pub octaves: u32,           // 4–6 gives hills with some detail
+    pub gain: f32,              // amplitude falloff per octave
+    pub lacunarity: f32,        // frequency growth per octave
+    pub ridge_weight: f32,      // 0..1 mix of ridged noise into the fBm
+    pub ridge_frequency: f32,   // ridges read better a bit denser than the hills
+    pub feather: i32,           // tiles of smooth falloff from a pad to the terrain
+    pub seed: u32,
+}
+
+impl Default for ElevationSettings {
+    fn default() -> Self {
+        Self {
+            amplitude: 12.0,
+            frequency: 1.0 / 64.0,
+            octaves: 5,
+            gain: 0.5,
+            lacunarity: 2.0,
+            ridge_weight: 0.35,
+            ridge_frequency: 1.0 / 48.0,
+            feather: 10,
+            seed: 7,
+        }
+    }
+}
+
+/// A disk that must end up flat at `elevation` (base build area, shrine pad).
+#[derive(Clone, Copy)]
+struct FlatPad {
+    center: IVec2,
+    radius: i32,
+    elevation: f32,
+}
+
+#[inline] fn smoothstep(t: f32) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn raw_height(x: i32, y: i32, base_elev: f32, s: &ElevationSettings) -> f32 {
+    let (xf, yf) = (x as f32, y as f32);
+    let hills = fbm_2d(xf * s.frequency, yf * s.frequency, s.seed, s.octaves, s.gain, s.lacunarity);
+    let ridges = ridged_2d(
+        xf * s.ridge_frequency, yf * s.ridge_frequency,
+        s.seed.wrapping_add(0x51ED_270B), s.octaves, s.gain, s.lacunarity,
+    );
+    let n = lerp(hills, ridges, s.ridge_weight.clamp(0.0, 1.0));
+    base_elev + s.amplitude * n
+}
+
+/// Phase 2B: multi-octave heightmap centred on `player_spawns.elevation`.
+/// Base disks are flattened at the spawn elevation and shrine pads at the
+/// terrain height under the shrine; both blend out over `feather` tiles.
+pub fn generate_elevation(
+    tpl: &MapTemplate,
+    base_centers: &[IVec2],
+    shrines: &[IVec2],
+    settings: ElevationSettings,
+) -> Grid<f32> {
+    let (w, h) = tpl.size;
+    let base_elev = tpl.player_spawns.elevation;
+    let mut height = Grid::<f32>::new(w, h);
+
+    for y in 0..h {
+        for x in 0..w {
+            height.set(x, y, raw_height(x, y, base_elev, &settings));
+        }
+    }
+
+    // Pads: bases first, shrines sample the unflattened terrain so they sit naturally.
+    let mut pads = Vec::with_capacity(base_centers.len() + shrines.len());
+    let br = tpl.player_spawns.base_radius.max(1);
+    for &c in base_centers {
+        pads.push(FlatPad { center: c, radius: br, elevation: base_elev });
+    }
+    let sr = tpl.terrain.shrine_grass_radius.max(0);
+    for &s in shrines {
+        let z = if height.in_bounds(s) { *height.get(s.x, s.y) } else { base_elev };
+        pads.push(FlatPad { center: s, radius: sr, elevation: z });
+    }
+
+    let feather = settings.feather.max(0);
+    for pad in &pads {
+        let reach = pad.radius + feather;
+        let xmin = (pad.center.x - reach).max(0);
+        let xmax = (pad.center.x + reach).min(w - 1);
+        let ymin = (pad.center.y - reach).max(0);
+        let ymax = (pad.center.y + reach).min(h - 1);
+        for y in ymin..=ymax {
+            for x in xmin..=xmax {
+                let d = (IVec2::new(x, y) - pad.center).as_vec2().length();
+                if d > reach as f32 { continue; }
+                // 1 inside the disk, smooth falloff to 0 across the feather band
+                let t = if feather == 0 || d <= pad.radius as f32 {
+                    1.0
+                } else {
+                    1.0 - smoothstep((d - pad.radius as f32) / feather as f32)
+                };
+                let cur = *height.get(x, y);
+                height.set(x, y, lerp(cur, pad.elevation, t));
+            }
+        }
+    }
+
+    height
+}
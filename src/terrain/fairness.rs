@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+use std::io;
+use glam::{IVec2, Vec2};
+use serde::Serialize;
+use super::grid::Grid;
+use super::template::{MapTemplate, ReportFormat};
+use super::objects::PlacedObject;
+use super::classes::TerrainClasses;
+use super::seed::MapSeed;
+
+/// Terrain fractions (0..1) inside one radius around a base.
+#[derive(Serialize, Clone, Debug)]
+pub struct TerrainComposition {
+    pub radius: i32,
+    /// (class name, fraction) in class-id order.
+    pub fractions: Vec<(String, f32)>,
+}
+
+/// One object type as seen from a base.
+#[derive(Serialize, Clone, Debug)]
+pub struct ObjectStats {
+    pub name: String,
+    /// Count inside each of the report radii (same order as `FairnessReport::radii`).
+    pub counts: Vec<usize>,
+    /// Straight-line distance to the closest one, if any exist.
+    pub nearest: Option<f32>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct BaseFairness {
+    pub base: usize,
+    pub center: (i32, i32),
+    pub terrain: Vec<TerrainComposition>,
+    pub objects: Vec<ObjectStats>,
+    pub nearest_shrine: Option<f32>,
+    pub nearest_ley_line: Option<f32>,
+    /// Overland steps (4-neighbour, passable classes only) to the passable tile
+    /// closest to the map centre; None when the base can't get there.
+    pub walk_to_center: Option<u32>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct FairnessReport {
+    /// Map seed code; with the template it reproduces this exact map.
+    pub seed: MapSeed,
+    pub radii: Vec<i32>,
+    pub bases: Vec<BaseFairness>,
+    /// Mean coefficient of variation across every per-base metric
+    /// (0 = identical bases; ~0.1 is already noticeable).
+    pub imbalance: f32,
+}
+
+const NEIGHBORS4: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+fn point_segment_distance(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let len2 = ab.length_squared();
+    let t = if len2 > 0.0 { ((p - a).dot(ab) / len2).clamp(0.0, 1.0) } else { 0.0 };
+    p.distance(a + ab * t)
+}
+
+// BFS step counts over passable terrain from `from` (u32::MAX = unreachable).
+fn walk_distances(classes: &Grid<u8>, reg: &TerrainClasses, from: IVec2) -> Vec<u32> {
+    let (w, h) = (classes.w, classes.h);
+    let mut dist = vec![u32::MAX; (w * h) as usize];
+    if !classes.in_bounds(from) || !reg.passable(*classes.get(from.x, from.y)) { return dist; }
+    dist[(from.y * w + from.x) as usize] = 0;
+    let mut queue = VecDeque::from([from]);
+    while let Some(p) = queue.pop_front() {
+        let d = dist[(p.y * w + p.x) as usize];
+        for (dx, dy) in NEIGHBORS4 {
+            let n = p + IVec2::new(dx, dy);
+            if !classes.in_bounds(n) || !reg.passable(*classes.get(n.x, n.y)) { continue; }
+            let j = (n.y * w + n.x) as usize;
+            if dist[j] == u32::MAX {
+                dist[j] = d + 1;
+                queue.push_back(n);
+            }
+        }
+    }
+    dist
+}
+
+// The centre itself may be a lake or a peak; walk to the nearest dry tile instead.
+fn passable_near_center(classes: &Grid<u8>, reg: &TerrainClasses) -> Option<IVec2> {
+    let c = Vec2::new(classes.w as f32, classes.h as f32) / 2.0;
+    let mut best: Option<(IVec2, f32)> = None;
+    for y in 0..classes.h {
+        for x in 0..classes.w {
+            if !reg.passable(*classes.get(x, y)) { continue; }
+            let d = (Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - c).length_squared();
+            if best.is_none_or(|(_, bd)| d < bd) { best = Some((IVec2::new(x, y), d)); }
+        }
+    }
+    best.map(|(p, _)| p)
+}
+
+// Coefficient of variation of one metric across bases (0 when the mean is ~0).
+fn variation(values: &[f32]) -> f32 {
+    if values.len() < 2 { return 0.0; }
+    let n = values.len() as f32;
+    let mean = values.iter().sum::<f32>() / n;
+    if mean.abs() < 1e-6 { return 0.0; }
+    let var = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / n;
+    var.sqrt() / mean.abs()
+}
+
+/// Measure what each base gets within `radii`: terrain mix, object counts and
+/// distances, shrine/ley access and overland distance to the centre.
+pub fn fairness_report(
+    tpl: &MapTemplate,
+    classes: &Grid<u8>,
+    base_centers: &[IVec2],
+    shrines: &[IVec2],
+    ley_lines: &[(IVec2, IVec2)],
+    objects: &[PlacedObject],
+    radii: &[i32],
+) -> FairnessReport {
+    let reg = TerrainClasses::from_template(tpl);
+    let center_target = passable_near_center(classes, &reg);
+    let mut bases = Vec::with_capacity(base_centers.len());
+
+    for (bi, &c) in base_centers.iter().enumerate() {
+        let cf = c.as_vec2();
+
+        let terrain = radii.iter().map(|&r| {
+            let mut counts = vec![0usize; reg.len()];
+            let mut total = 0usize;
+            for y in (c.y - r).max(0)..=(c.y + r).min(classes.h - 1) {
+                for x in (c.x - r).max(0)..=(c.x + r).min(classes.w - 1) {
+                    let (dx, dy) = (x - c.x, y - c.y);
+                    if dx*dx + dy*dy > r*r { continue; }
+                    if let Some(n) = counts.get_mut(*classes.get(x, y) as usize) { *n += 1; }
+                    total += 1;
+                }
+            }
+            let fractions = reg.defs.iter().zip(&counts)
+                .map(|(d, &n)| (d.name.clone(), if total > 0 { n as f32 / total as f32 } else { 0.0 }))
+                .collect();
+            TerrainComposition { radius: r, fractions }
+        }).collect();
+
+        let objects = tpl.objects.types.iter().enumerate().map(|(ti, t)| {
+            let dists: Vec<f32> = objects.iter()
+                .filter(|o| o.kind as usize == ti)
+                .map(|o| o.pos.as_vec2().distance(cf))
+                .collect();
+            ObjectStats {
+                name: t.name.clone(),
+                counts: radii.iter().map(|&r| dists.iter().filter(|&&d| d <= r as f32).count()).collect(),
+                nearest: dists.iter().copied().reduce(f32::min),
+            }
+        }).collect();
+
+        let nearest_shrine = shrines.iter().map(|s| s.as_vec2().distance(cf)).reduce(f32::min);
+        let nearest_ley_line = ley_lines.iter()
+            .map(|&(a, b)| point_segment_distance(cf, a.as_vec2(), b.as_vec2()))
+            .reduce(f32::min);
+
+        let walk_to_center = center_target.and_then(|t| {
+            let d = walk_distances(classes, &reg, c)[(t.y * classes.w + t.x) as usize];
+            (d != u32::MAX).then_some(d)
+        });
+
+        bases.push(BaseFairness {
+            base: bi,
+            center: (c.x, c.y),
+            terrain,
+            objects,
+            nearest_shrine,
+            nearest_ley_line,
+            walk_to_center,
+        });
+    }
+
+    // Flatten every comparable metric into one column per metric, then average
+    // their spread. Missing/unreachable distances count as "very far" so rows
+    // stay aligned and the gap shows up in the score.
+    let mut columns: Vec<Vec<f32>> = Vec::new();
+    let far = (classes.w + classes.h) as f32 * 4.0;
+    for b in &bases {
+        let mut row: Vec<f32> = Vec::new();
+        for t in &b.terrain { row.extend(t.fractions.iter().map(|&(_, f)| f)); }
+        for o in &b.objects {
+            row.extend(o.counts.iter().map(|&n| n as f32));
+            row.push(o.nearest.unwrap_or(far));
+        }
+        row.push(b.nearest_shrine.unwrap_or(far));
+        row.push(b.nearest_ley_line.unwrap_or(far));
+        row.push(b.walk_to_center.map(|d| d as f32).unwrap_or(far));
+        if columns.is_empty() { columns = vec![Vec::new(); row.len()]; }
+        for (col, v) in columns.iter_mut().zip(row) { col.push(v); }
+    }
+    let imbalance = if columns.is_empty() {
+        0.0
+    } else {
+        columns.iter().map(|c| variation(c)).sum::<f32>() / columns.len() as f32
+    };
+
+    // The pipeline stamps the seed; the report itself only sees the finished map.
+    FairnessReport { seed: MapSeed::default(), radii: radii.to_vec(), bases, imbalance }
+}
+
+/// Serialize the report as pretty RON or JSON.
+pub fn write_report(path: &str, report: &FairnessReport, format: ReportFormat) -> io::Result<()> {
+    let text = match format {
+        ReportFormat::Ron => ron::ser::to_string_pretty(report, ron::ser::PrettyConfig::default()).map_err(io::Error::other)?,
+        ReportFormat::Json => serde_json::to_string_pretty(report)?,
+    };
+    std::fs::write(path, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASES: [IVec2; 4] = [IVec2::new(8, 20), IVec2::new(32, 20), IVec2::new(20, 8), IVec2::new(20, 32)];
+
+    fn preset() -> MapTemplate {
+        MapTemplate::load("assets/maps/haunted_woods.ron").expect("preset loads").template
+    }
+
+    /// 41x41 grassland with a tree two tiles outward of each base.
+    fn open_map(tpl: &MapTemplate) -> (Grid<u8>, Vec<PlacedObject>) {
+        let ground = TerrainClasses::from_template(tpl).ground();
+        let mut classes = Grid::new(41, 41);
+        for y in 0..41 {
+            for x in 0..41 { classes.set(x, y, ground); }
+        }
+        let centre = IVec2::splat(20);
+        let objects = BASES.iter().map(|&b| PlacedObject { pos: b + (b - centre) / 6, kind: 0 }).collect();
+        (classes, objects)
+    }
+
+    /// Water square `r` tiles out from `c`, open at `gap` if given.
+    fn moat(classes: &mut Grid<u8>, water: u8, c: IVec2, r: i32, gap: Option<IVec2>) {
+        for y in -r..=r {
+            for x in -r..=r {
+                let p = c + IVec2::new(x, y);
+                if (x.abs() == r || y.abs() == r) && Some(p) != gap { classes.set(p.x, p.y, water); }
+            }
+        }
+    }
+
+    #[test]
+    fn symmetric_bases_are_balanced() {
+        let tpl = preset();
+        let (classes, objects) = open_map(&tpl);
+        let report = fairness_report(&tpl, &classes, &BASES, &[], &[], &objects, &[3, 6]);
+        assert!(report.imbalance < 1e-4, "imbalance {}", report.imbalance);
+        assert!(report.bases.iter().all(|b| b.walk_to_center == Some(12)));
+    }
+
+    #[test]
+    fn an_unreachable_base_raises_the_score() {
+        let tpl = preset();
+        let water = TerrainClasses::from_template(&tpl).id("Water").expect("preset has water");
+        let (mut open, objects) = open_map(&tpl);
+        let mut shut = open.clone();
+        moat(&mut open, water, BASES[0], 5, Some(BASES[0] + IVec2::new(5, 0)));
+        moat(&mut shut, water, BASES[0], 5, None);
+
+        let open = fairness_report(&tpl, &open, &BASES, &[], &[], &objects, &[3]);
+        let shut = fairness_report(&tpl, &shut, &BASES, &[], &[], &objects, &[3]);
+        assert_eq!(open.bases[0].walk_to_center, Some(12));
+        assert_eq!(shut.bases[0].walk_to_center, None);
+        assert!(shut.imbalance > open.imbalance + 0.05, "{} vs {}", shut.imbalance, open.imbalance);
+    }
+}
@@ -1,29 +1,35 @@
-use std::path::Path;
+//! The standard generation phases and the converters from template configs to
+//! their runtime settings. `template_pipeline` strings them together in the
+//! order the template asks for.
+
 use glam::IVec2;
-use super::grid::Grid;
-use super::template::{MapTemplate, LeyConfig, BlendConfig, FractalConfig};
-use super::debug_png::{write_height_with_disks, write_height_with_overlays, write_terrain_classes, write_terrain_with_objects};
-use super::spawns::{BaseLocations, generate_bases};
-use super::ley::{LeySettings, LeyNetwork, generate_ley};
-use super::landscape::generate_terrain_clumps;
+use super::template::{MapTemplate, LeyConfig, BlendConfig, FractalConfig, ElevationConfig, RiverConfig, ConnectivityConfig, TerrainMode, ReportFormat, PhaseStep};
+use super::debug_png::{write_height_with_disks, write_height_with_overlays, write_terrain_classes, write_terrain_with_objects, write_terrain_with_polylines};
+use super::spawns::generate_bases;
+use super::ley::{LeySettings, LeyGraph, LeyNodeKind, LeyCorridor, generate_ley, shape_ley, ley_mask};
+use super::landscape::{generate_terrain_clumps, generate_terrain_biomes};
+use super::classes::TerrainClasses;
 use super::blend::{blend_terrain, BlendSettings, blend_fractal, FractalSettings};
-use super::objects::{generate_objects, PlacedObject};
+use super::objects::generate_objects;
+use super::elevation::{generate_elevation, ElevationSettings};
+use super::rivers::{carve_rivers, River, RiverSettings};
+use super::connectivity::{carve_corridor, ensure_connectivity, ConnectivitySettings};
+use super::fairness::{fairness_report, write_report};
+use super::pipeline::{DebugOut, GenContext, GenerationPhase, PhaseError, Pipeline};
 
 // Converters from template configs -> runtime settings
 fn to_blend_settings(c: &BlendConfig) -> BlendSettings {
-    let (g, f, w, m) = c.radii;
     BlendSettings {
         iterations: c.iterations,
-        radii: [g, f, w, m],
+        radii: c.radii.clone(),
         inertia: c.inertia,
         boundary_only: c.boundary_only,
     }
 }
-fn to_fractal_settings(c: &FractalConfig) -> FractalSettings {
-    let (g, f, w, m) = c.radii;
+fn to_fractal_settings(c: &FractalConfig, seed: u32) -> FractalSettings {
     FractalSettings {
         iterations: c.iterations,
-        radii: [g, f, w, m],
+        radii: c.radii.clone(),
         inertia: c.inertia,
         boundary_only: c.boundary_only,
         warp_amp: c.warp_amp,
@@ -31,13 +37,48 @@ fn to_fractal_settings(c: &FractalConfig) -> FractalSettings {
         warp_octaves: c.warp_octaves,
         warp_gain: c.warp_gain,
         warp_lacunarity: c.warp_lacunarity,
-        seed: c.seed,
+        seed,
+    }
+}
+fn to_elevation_settings(c: &ElevationConfig, seed: u32) -> ElevationSettings {
+    ElevationSettings {
+        amplitude: c.amplitude,
+        frequency: c.frequency,
+        octaves: c.octaves,
+        gain: c.gain,
+        lacunarity: c.lacunarity,
+        ridge_weight: c.ridge_weight,
+        ridge_frequency: c.ridge_frequency,
+        feather: c.feather,
+        seed,
+    }
+}
+fn to_river_settings(c: &RiverConfig, seed: u32) -> RiverSettings {
+    RiverSettings {
+        count: c.count,
+        width: c.width,
+        meander: c.meander,
+        meander_freq: c.meander_freq,
+        min_length: c.min_length,
+        source_spacing: c.source_spacing,
+        seed,
+    }
+}
+fn to_connectivity_settings(c: &ConnectivityConfig) -> ConnectivitySettings {
+    ConnectivitySettings {
+        carve: c.carve,
+        corridor_width: c.corridor_width,
     }
 }
-fn to_ley_settings(tpl: &MapTemplate, num_bases: usize) -> super::ley::LeySettings {
+fn to_ley_settings(tpl: &MapTemplate, num_bases: usize, seed: u32) -> super::ley::LeySettings {
     let r: &LeyConfig = &tpl.ley;
     let spb = r.shrines_per_base;
     let total_shrines = spb.saturating_mul(num_bases);
+    let reg = TerrainClasses::from_template(tpl);
+    // Validation rejects unknown classes; an unvalidated template falls back to ground.
+    let corridor_class = reg.region(&r.corridor_class);
+    debug_assert!(corridor_class.is_some(), "ley: unknown corridor_class `{}` got past validation", r.corridor_class.0);
+    let corridor_class = corridor_class.unwrap_or_else(|| reg.ground());
 
     LeySettings {
         m_shrines: total_shrines,
@@ -45,102 +86,367 @@ fn to_ley_settings(tpl: &MapTemplate, num_bases: usize) -> super::ley::LeySettin
         offset_deg: r.offset_deg,
         connect_cycle: r.connect_cycle,
         connect_spokes: r.connect_spokes,
+        connect_mst: r.connect_mst,
+        connect_delaunay: r.connect_delaunay,
+        connect_knn: r.connect_knn,
+        connect_bases: r.connect_bases,
+        style: r.style,
+        meander: r.meander,
+        meander_freq: r.meander_freq,
+        terrain_weight: r.terrain_weight,
+        sample_spacing: r.sample_spacing,
+        seed,
+        corridor_width: r.corridor_width,
+        corridor_class,
+        object_reach: r.object_reach,
     }
 }
 
-// Optional PNGs: pass Some("out") to save, or None to skip.
-// Also allow passing settings; if None, we’ll read from template or fall back to defaults.
-pub fn generate_all_phases(
-    tpl: &MapTemplate,
-    num_bases: usize,
-    start_angle_deg: f32,
-    ley_override: Option<LeySettings>,
-    blend_override: Option<BlendSettings>,
-    fractal_override: Option<FractalSettings>,
-    terrain_seed: u32,
-    out_dir: Option<&str>,
-) -> (BaseLocations, LeyNetwork, Grid<u8>, Vec<PlacedObject>) {
-    // resolve configs (override > template > defaults)
-    let ley_cfg = ley_override
-        .unwrap_or_else(|| to_ley_settings(tpl, num_bases));
-    let blend_cfg = blend_override
-        .unwrap_or_else(|| to_blend_settings(&tpl.blend));
-    let fractal_cfg = fractal_override
-        .unwrap_or_else(|| to_fractal_settings(&tpl.fractal));
-
-    let save = |name: &str, f: &dyn Fn(&Path)| {
-        if let Some(dir) = out_dir {
-            let dirp = Path::new(dir);
-            let _ = std::fs::create_dir_all(dirp);
-            f(&dirp.join(name));
-        }
-    };
-
-    const PALETTE: [[u8;4];4] = [
-        [110,180,110,255], // grassland
-        [ 34,139, 34,255], // forest
-        [ 64,120,255,255], // water
-        [150,150,150,255], // mountain
-    ];
-
-    // Phase 1
-    let p1 = generate_bases(tpl, num_bases, Some(start_angle_deg));
-    save("phase1_bases.png", &|p| {
-        let base_disks: Vec<_> = p1.base_centers.iter().map(|&c| (c, p1.base_radius, [255,64,64])).collect();
-        write_height_with_disks(&p.to_string_lossy(), &p1.height, &base_disks);
+// Ley settings for the running phase (shared by Ley and LeyPaths).
+fn ley_settings(ctx: &GenContext) -> LeySettings {
+    to_ley_settings(ctx.tpl, ctx.params.num_bases, ctx.phase_seed("ley", ctx.tpl.ley.seed))
+}
+
+// Corridor and object-reach masks around the current ley graph.
+fn ley_masks(ctx: &mut GenContext, graph: &LeyGraph, cfg: &LeySettings) {
+    let size = IVec2::new(ctx.tpl.size.0, ctx.tpl.size.1);
+    ctx.ley_lines = graph.segments();
+    ctx.corridor = (cfg.corridor_width > 0).then(|| LeyCorridor {
+        mask: ley_mask(graph, size, cfg.corridor_width),
+        class: cfg.corridor_class,
     });
+    ctx.ley_near = Some(ley_mask(graph, size, cfg.object_reach));
+}
+
+fn shrine_points(ctx: &GenContext) -> Vec<(IVec2, [u8; 4])> {
+    ctx.shrines().iter().map(|&q| (q, [64,255,255,255])).collect()
+}
+
+/// Phase 1: base disks on a ring, flattened at one height.
+#[derive(Clone)]
+pub struct BasesPhase;
+
+impl GenerationPhase for BasesPhase {
+    fn name(&self) -> &str { "bases" }
+
+    fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError> {
+        let p1 = generate_bases(ctx.tpl, ctx.params.num_bases, Some(ctx.params.start_angle_deg));
+        ctx.height = Some(p1.height.clone());
+        ctx.bases = Some(p1);
+        Ok(())
+    }
+
+    fn debug(&self, ctx: &GenContext, out: &DebugOut) {
+        let Ok(p1) = ctx.bases() else { return; };
+        let base_disks: Vec<_> = p1.base_centers.iter().map(|&c| (c, p1.base_radius, [255,64,64])).collect();
+        write_height_with_disks(&out.path("phase1_bases.png"), &p1.height, &base_disks);
+    }
+}
+
+/// Phase 2: shrines and the ley graph (straight lines until LeyPaths runs).
+#[derive(Clone)]
+pub struct LeyPhase;
+
+impl GenerationPhase for LeyPhase {
+    fn name(&self) -> &str { "ley" }
+
+    fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError> {
+        let cfg = ley_settings(ctx);
+        let mut ley = generate_ley(ctx.tpl.size, ctx.base_centers()?, ctx.params.start_angle_deg, &cfg);
+        if let Some(s) = &ctx.sym {
+            s.fold_ley(&mut ley.graph);
+            for (shrine, node) in ley.shrines.iter_mut().zip(&ley.graph.nodes) { *shrine = node.pos; }
+        }
+        ley_masks(ctx, &ley.graph, &cfg);
+        ctx.ley = Some(ley);
+        Ok(())
+    }
 
-    // Phase 2
-    let ley = generate_ley(
-        tpl.size, num_bases, start_angle_deg,
-        ley_cfg.m_shrines, ley_cfg.shrine_ring, ley_cfg.offset_deg,
-        ley_cfg.connect_cycle, ley_cfg.connect_spokes
-    );
-    save("phase2_ley.png", &|p| {
+    fn debug(&self, ctx: &GenContext, out: &DebugOut) {
+        let (Ok(p1), Some(ley)) = (ctx.bases(), &ctx.ley) else { return; };
         let base_disks_rgba: Vec<_> = p1.base_centers.iter().map(|&c| (c, p1.base_radius, [255,64,64,200])).collect();
-        let shrine_points: Vec<_> = ley.shrines.iter().copied().map(|q| (q, [64,255,255,255])).collect();
-        let center_px = IVec2::new(tpl.size.0/2, tpl.size.1/2);
-        let ley_lines: Vec<_> = ley.lines.iter().map(|&(a,b)| {
-            let is_spoke = a==center_px || b==center_px;
-            let color = if is_spoke { [64,96,255,255] } else { [64,255,96,255] };
+        let ley_lines: Vec<_> = ley.graph.edges.iter().map(|e| {
+            let kinds = [ley.graph.nodes[e.a].kind, ley.graph.nodes[e.b].kind];
+            let color = if kinds.contains(&LeyNodeKind::Center) { [64,96,255,255] }
+                else if kinds.contains(&LeyNodeKind::Base) { [255,160,64,255] }
+                else { [64,255,96,255] };
+            let (a, b) = ley.graph.segment(e);
             (a,b,color)
         }).collect();
-        write_height_with_overlays(&p.to_string_lossy(), &p1.height, &base_disks_rgba, &shrine_points, &ley_lines);
-    });
+        write_height_with_overlays(&out.path("phase2_ley.png"), &p1.height, &base_disks_rgba, &shrine_points(ctx), &ley_lines);
+    }
+}
 
-    // Phase 3
-    let classes = generate_terrain_clumps(tpl, &p1.base_centers, &ley.shrines, terrain_seed);
-    save("phase3_terrain.png", &|p| {
-        write_terrain_classes(&p.to_string_lossy(), &classes, &PALETTE);
-    });
+/// Phase 2B: heightmap with base disks and shrine pads flattened.
+#[derive(Clone)]
+pub struct ElevationPhase;
 
-    // Phase 4A (blend)
-    let blended = blend_terrain(
-        tpl, &p1.base_centers, &ley.shrines, &classes, blend_cfg,
-    );
-    save("phase4a_blend.png", &|p| {
-        write_terrain_classes(&p.to_string_lossy(), &blended, &PALETTE);
-    });
+impl GenerationPhase for ElevationPhase {
+    fn name(&self) -> &str { "elevation" }
 
-    // Phase 4B (fractal)
-    let final_classes = blend_fractal(
-        tpl, &p1.base_centers, &ley.shrines, &blended, fractal_cfg,
-    );
-    save("phase4b_fractal.png", &|p| {
-        write_terrain_classes(&p.to_string_lossy(), &final_classes, &PALETTE);
-    });
+    fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError> {
+        let cfg = to_elevation_settings(&ctx.tpl.elevation, ctx.phase_seed("elevation", ctx.tpl.elevation.seed));
+        let height = generate_elevation(ctx.tpl, ctx.base_centers()?, ctx.shrines(), cfg);
+        ctx.height = Some(ctx.fold(height));
+        Ok(())
+    }
 
-    // Phase 5 (Populate with objects)
-    let objs = generate_objects(
-        &tpl,
-        &final_classes,
-        &p1.base_centers,   // from Phase 1
-        &ley.shrines,       // from Phase 2
-        0,                  // extra_seed or your own objects_seed
-    );
-    save("phase5_objects.png", &|p| {
-        write_terrain_with_objects(&p.to_string_lossy(), &final_classes, &PALETTE, &objs, &tpl);
-    });
+    fn debug(&self, ctx: &GenContext, out: &DebugOut) {
+        let Ok(height) = ctx.height() else { return; };
+        let ley_lines: Vec<_> = ctx.ley_lines.iter().map(|&(a,b)| (a, b, [64,255,96,255])).collect();
+        write_height_with_overlays(&out.path("phase2b_elevation.png"), height, &[], &shrine_points(ctx), &ley_lines);
+    }
+}
+
+/// Phase 2C: ley polylines routed over the heightmap, corridor masks rebuilt.
+#[derive(Clone)]
+pub struct LeyPathsPhase;
+
+impl GenerationPhase for LeyPathsPhase {
+    fn name(&self) -> &str { "ley_paths" }
+
+    fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError> {
+        let cfg = ley_settings(ctx);
+        let mut graph = ctx.ley.as_ref().ok_or(PhaseError::Missing("a ley network"))?.graph.clone();
+        shape_ley(&mut graph, ctx.height()?, &cfg);
+        if let Some(s) = &ctx.sym { s.fold_ley(&mut graph); }
+        ley_masks(ctx, &graph, &cfg);
+        if let Some(ley) = &mut ctx.ley { ley.graph = graph; }
+        Ok(())
+    }
+
+    fn debug(&self, ctx: &GenContext, out: &DebugOut) {
+        let Ok(height) = ctx.height() else { return; };
+        let ley_lines: Vec<_> = ctx.ley_lines.iter().map(|&(a,b)| (a, b, [64,255,96,255])).collect();
+        write_height_with_overlays(&out.path("phase2c_ley_paths.png"), height, &[], &shrine_points(ctx), &ley_lines);
+    }
+}
+
+/// Phase 3: terrain classes (clumps or biomes).
+#[derive(Clone)]
+pub struct TerrainPhase;
+
+impl GenerationPhase for TerrainPhase {
+    fn name(&self) -> &str { "terrain" }
+
+    fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError> {
+        let seed = ctx.phase_seed("terrain", 0);
+        let (tpl, bases, shrines) = (ctx.tpl, ctx.base_centers()?, ctx.shrines());
+        let classes = match tpl.terrain.mode {
+            TerrainMode::Clumps => generate_terrain_clumps(
+                tpl, bases, shrines, &ctx.ley_lines, ctx.corridor.as_ref(), seed,
+            ),
+            TerrainMode::Biomes => {
+                let (classes, moisture) = generate_terrain_biomes(
+                    tpl, ctx.height()?, bases, shrines, ctx.corridor.as_ref(), seed,
+                );
+                ctx.moisture = Some(moisture);
+                classes
+            }
+        };
+        ctx.classes = Some(ctx.fold(classes));
+        Ok(())
+    }
+
+    fn debug(&self, ctx: &GenContext, out: &DebugOut) {
+        if let Some(moisture) = &ctx.moisture {
+            write_height_with_disks(&out.path("phase3_moisture.png"), moisture, &[]);
+        }
+        let Ok(classes) = ctx.classes() else { return; };
+        write_terrain_classes(&out.path("phase3_terrain.png"), classes, &ctx.reg.palette());
+    }
+}
+
+/// Phase 4A: blur→relabel smoothing of class boundaries.
+#[derive(Clone)]
+pub struct BlendPhase;
+
+impl GenerationPhase for BlendPhase {
+    fn name(&self) -> &str { "blend" }
+
+    fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError> {
+        let blended = blend_terrain(
+            ctx.tpl, ctx.base_centers()?, ctx.shrines(), ctx.corridor.as_ref(), ctx.classes()?,
+            to_blend_settings(&ctx.tpl.blend),
+        );
+        ctx.classes = Some(ctx.fold(blended));
+        Ok(())
+    }
+
+    fn debug(&self, ctx: &GenContext, out: &DebugOut) {
+        let Ok(classes) = ctx.classes() else { return; };
+        write_terrain_classes(&out.path("phase4a_blend.png"), classes, &ctx.reg.palette());
+    }
+}
+
+/// Phase 4B: domain-warped blend for ragged, natural edges.
+#[derive(Clone)]
+pub struct FractalPhase;
+
+impl GenerationPhase for FractalPhase {
+    fn name(&self) -> &str { "fractal" }
+
+    fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError> {
+        let cfg = to_fractal_settings(&ctx.tpl.fractal, ctx.phase_seed("fractal", ctx.tpl.fractal.seed));
+        let warped = blend_fractal(
+            ctx.tpl, ctx.base_centers()?, ctx.shrines(), ctx.corridor.as_ref(), ctx.classes()?, cfg,
+        );
+        ctx.classes = Some(ctx.fold(warped));
+        Ok(())
+    }
+
+    fn debug(&self, ctx: &GenContext, out: &DebugOut) {
+        let Ok(classes) = ctx.classes() else { return; };
+        write_terrain_classes(&out.path("phase4b_fractal.png"), classes, &ctx.reg.palette());
+    }
+}
+
+/// Phase 4C: rivers traced downhill and carved into the classes.
+#[derive(Clone)]
+pub struct RiversPhase;
+
+impl GenerationPhase for RiversPhase {
+    fn name(&self) -> &str { "rivers" }
+
+    fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError> {
+        let cfg = to_river_settings(&ctx.tpl.rivers, ctx.phase_seed("rivers", ctx.tpl.rivers.seed));
+        let (classes, mut rivers) = carve_rivers(
+            ctx.tpl, ctx.height()?, ctx.classes()?, ctx.base_centers()?, ctx.shrines(), cfg,
+        );
+        if let Some(s) = &ctx.sym {
+            let lines: Vec<_> = rivers.into_iter().map(|r| r.points).collect();
+            rivers = s.fold_polylines(&lines).into_iter().map(|points| River { points }).collect();
+        }
+        ctx.classes = Some(ctx.fold(classes));
+        ctx.rivers.extend(rivers);
+        Ok(())
+    }
+
+    fn debug(&self, ctx: &GenContext, out: &DebugOut) {
+        let Ok(classes) = ctx.classes() else { return; };
+        let lines: Vec<_> = ctx.rivers.iter().map(|r| (r.points.as_slice(), [255,255,255,255])).collect();
+        write_terrain_with_polylines(&out.path("phase4c_rivers.png"), classes, &ctx.reg.palette(), &lines);
+    }
+}
+
+/// Phase 4D: make every base and shrine reachable overland.
+#[derive(Clone)]
+pub struct ConnectivityPhase;
+
+// Symmetric re-carves to try before settling for a one-sided corridor.
+const SYMMETRIC_CARVE_PASSES: usize = 3;
+
+impl GenerationPhase for ConnectivityPhase {
+    fn name(&self) -> &str { "connectivity" }
+
+    fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError> {
+        let cfg = to_connectivity_settings(&ctx.tpl.connectivity);
+        let (bases, shrines) = (ctx.base_centers()?, ctx.shrines());
+        let (mut classes, mut report) = ensure_connectivity(ctx.classes()?, &ctx.reg, bases, shrines, cfg);
+        if let Some(s) = &ctx.sym {
+            // Mirror the reference wedge's corridors, then re-check: folding can drop a
+            // corridor that lived in another wedge. Whatever the re-check carves goes
+            // into every wedge and is folded again, so the map stays symmetric.
+            classes = s.fold_grid(&classes);
+            let mut corridors = s.fold_polylines(&report.corridors);
+            for pass in 0.. {
+                let (fixed, recheck) = ensure_connectivity(&classes, &ctx.reg, bases, shrines, cfg);
+                report.connected = recheck.connected;
+                if recheck.corridors.is_empty() { break; }
+                if pass == SYMMETRIC_CARVE_PASSES {
+                    // Reachability is non-negotiable: keep the one-sided fix.
+                    classes = fixed;
+                    corridors.extend(recheck.corridors);
+                    break;
+                }
+                let copies = s.replicate_polylines(&recheck.corridors);
+                for c in &copies { carve_corridor(&mut classes, &ctx.reg, c, cfg.corridor_width.max(1)); }
+                classes = s.fold_grid(&classes);
+                corridors.extend(copies);
+            }
+            report.corridors = corridors;
+        }
+        ctx.classes = Some(classes);
+        ctx.connectivity = Some(report);
+        Ok(())
+    }
+
+    fn debug(&self, ctx: &GenContext, out: &DebugOut) {
+        let (Ok(classes), Some(report)) = (ctx.classes(), &ctx.connectivity) else { return; };
+        let lines: Vec<_> = report.corridors.iter().map(|c| (c.as_slice(), [255,220,0,255])).collect();
+        write_terrain_with_polylines(&out.path("phase4d_connectivity.png"), classes, &ctx.reg.palette(), &lines);
+    }
+}
+
+/// Phase 5: trees, rocks and the like. Replaces any earlier placement.
+#[derive(Clone)]
+pub struct ObjectsPhase;
+
+impl GenerationPhase for ObjectsPhase {
+    fn name(&self) -> &str { "objects" }
+
+    fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError> {
+        let salt = (ctx.tpl.objects.base_seed as u64).wrapping_add(ctx.repeat as u64);
+        let classes = ctx.classes()?;
+        let placement = generate_objects(
+            ctx.tpl, classes, ctx.base_centers()?, ctx.shrines(), ctx.ley_near.as_deref(),
+            ctx.params.seed.salted("objects", salt),
+        );
+        let mut objs = placement.objects;
+        if let Some(s) = &ctx.sym {
+            objs = s.fold_objects(&objs, IVec2::new(classes.w, classes.h), &ctx.tpl.objects.spacing_matrix());
+        }
+        ctx.objects = objs;
+        ctx.object_shortfalls = placement.shortfalls;
+        Ok(())
+    }
+
+    fn debug(&self, ctx: &GenContext, out: &DebugOut) {
+        let Ok(classes) = ctx.classes() else { return; };
+        write_terrain_with_objects(&out.path("phase5_objects.png"), classes, &ctx.reg.palette(), &ctx.objects, ctx.tpl);
+    }
+}
+
+/// Phase 6: per-base fairness measurements over the map so far.
+#[derive(Clone)]
+pub struct FairnessPhase;
+
+impl GenerationPhase for FairnessPhase {
+    fn name(&self) -> &str { "fairness" }
+
+    fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError> {
+        let mut report = fairness_report(
+            ctx.tpl, ctx.classes()?, ctx.base_centers()?, ctx.shrines(), &ctx.ley_lines, &ctx.objects,
+            &ctx.tpl.fairness.radii,
+        );
+        report.seed = ctx.params.seed;
+        ctx.fairness = Some(report);
+        Ok(())
+    }
+
+    fn debug(&self, ctx: &GenContext, out: &DebugOut) {
+        let (Some(report), Some(format)) = (&ctx.fairness, ctx.tpl.fairness.write) else { return; };
+        let ext = match format { ReportFormat::Ron => "ron", ReportFormat::Json => "json" };
+        let path = out.path(&format!("phase6_fairness.{ext}"));
+        if let Err(e) = write_report(&path, report, format) {
+            eprintln!("fairness report {path}: {e}");
+        }
+    }
+}
 
-    (p1, ley, final_classes, objs)
+/// The standard phases in the template's order (`MapTemplate::pipeline_steps`).
+pub fn template_pipeline(tpl: &MapTemplate) -> Pipeline {
+    tpl.pipeline_steps().iter().fold(Pipeline::new(), |p, step| match step {
+        PhaseStep::Bases => p.then(BasesPhase),
+        PhaseStep::Ley => p.then(LeyPhase),
+        PhaseStep::Elevation => p.then(ElevationPhase),
+        PhaseStep::LeyPaths => p.then(LeyPathsPhase),
+        PhaseStep::Terrain => p.then(TerrainPhase),
+        PhaseStep::Blend => p.then(BlendPhase),
+        PhaseStep::Fractal => p.then(FractalPhase),
+        PhaseStep::Rivers => p.then(RiversPhase),
+        PhaseStep::Connectivity => p.then(ConnectivityPhase),
+        PhaseStep::Objects => p.then(ObjectsPhase),
+        PhaseStep::Fairness => p.then(FairnessPhase),
+    })
 }
@@ -28,6 +28,10 @@ impl<T> Grid<T> {
         &self.data[self.idx(x, y)]
     }
 
+    /// Cells in row-major order.
+    #[inline]
+    pub fn as_slice(&self) -> &[T] { &self.data }
+
     #[allow(dead_code)]
     pub fn in_bounds(&self, p: IVec2) -> bool {
         p.x >= 0 && p.y >= 0 && p.x < self.w && p.y < self.h
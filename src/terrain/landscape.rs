@@ -1,12 +1,9 @@
-use glam::IVec2;
+use glam::{IVec2, Vec2};
 use super::grid::Grid;
-use super::template::{MapTemplate, TerrainWeights, AreaSource};
-
-// Terrain class ids
-pub const TERRAIN_GRASSLAND: u8 = 0;
-pub const TERRAIN_FOREST:    u8 = 1;
-pub const TERRAIN_WATER:     u8 = 2;
-pub const TERRAIN_MOUNTAIN:  u8 = 3;
+use super::template::{MapTemplate, AreaSource, AreaShape, ClassRole, TerrainArea};
+use super::noise::fbm_2d;
+use super::ley::LeyCorridor;
+use super::classes::TerrainClasses;
 
 // ---------------- tiny RNG (deterministic, no extra deps) ----------------
 struct Rng64(u64);
@@ -38,25 +35,21 @@ fn clamp_radius_to_bounds(size: IVec2, c: IVec2, r: i32) -> i32 {
     r.min(rmax_x).min(rmax_y).max(0)
 }
 
-#[inline] fn weights_arr(w: &TerrainWeights) -> [f32;4] {
-    [w.grassland.max(0.0), w.forest.max(0.0), w.water.max(0.0), w.mountain.max(0.0)]
-}
-
-fn normalize(v: &mut [f32; 4]) {
+// All-zero weights fall back to pure ground.
+fn normalize(v: &mut [f32], ground: u8) {
     let s = v.iter().copied().sum::<f32>();
     if s > 0.0 {
         for x in v.iter_mut() {
             *x /= s;
         }
     } else {
-        v[0] = 1.0;
-        v[1] = 0.0;
-        v[2] = 0.0;
-        v[3] = 0.0;
+        v.fill(0.0);
+        v[ground as usize] = 1.0;
     }
 }
 
 // Fill a blobby disk (slight ellipse jitter) of class `id` into `classes` and mark painted.
+#[allow(clippy::too_many_arguments)]
 fn stamp_blob(
     classes: &mut Grid<u8>,
     painted: &mut [u8],
@@ -100,7 +93,7 @@ fn stamp_blob(
 // Build a circle mask as u8 array (1=inside)
 fn circle_mask(size: IVec2, c: IVec2, r: i32) -> Vec<u8> {
     let (w,h) = (size.x, size.y);
-    let r2 = (r.max(0) * r.max(0)) as i32;
+    let r2 = r.max(0) * r.max(0);
     let mut m = vec![0u8; (w*h) as usize];
     let xmin = (c.x - r).max(0);
     let xmax = (c.x + r).min(w-1);
@@ -117,23 +110,122 @@ fn circle_mask(size: IVec2, c: IVec2, r: i32) -> Vec<u8> {
     m
 }
 
-/// New Phase 3: clumpy terrain that honors hard grass buffers around bases & shrines.
-/// We process areas in ascending radius so small/strong rules occupy space first.
-pub fn generate_terrain_clumps(
+// Distance (tiles) to the nearest ley segment, only resolved out to `max_r`;
+// everything further stays at f32::MAX.
+fn ley_distance(size: IVec2, segments: &[(IVec2, IVec2)], max_r: i32) -> Vec<f32> {
+    let (w, h) = (size.x, size.y);
+    let r = max_r.max(0);
+    let mut dist = vec![f32::MAX; (w*h) as usize];
+    for &(a, b) in segments {
+        let (af, bf) = (a.as_vec2(), b.as_vec2());
+        let ab = bf - af;
+        let len2 = ab.length_squared();
+        for y in (a.y.min(b.y) - r).max(0)..=(a.y.max(b.y) + r).min(h-1) {
+            for x in (a.x.min(b.x) - r).max(0)..=(a.x.max(b.x) + r).min(w-1) {
+                let p = Vec2::new(x as f32, y as f32);
+                let t = if len2 > 0.0 { ((p - af).dot(ab) / len2).clamp(0.0, 1.0) } else { 0.0 };
+                let d = p.distance(af + ab * t);
+                let i = idx(w,x,y);
+                if d < dist[i] { dist[i] = d; }
+            }
+        }
+    }
+    dist
+}
+
+// Signed angle difference wrapped to [-PI, PI].
+fn wrap_angle(a: f32) -> f32 {
+    use std::f32::consts::{PI, TAU};
+    (a + PI).rem_euclid(TAU) - PI
+}
+
+// Does offset `d` from an anchor fall inside the area's shape? `outward` is the
+// anchor's direction away from the map centre (spawns/shrines) that sectors turn with.
+fn in_shape(a: &TerrainArea, d: IVec2, outward: Option<f32>) -> bool {
+    let d2 = d.x*d.x + d.y*d.y;
+    if d2 > a.radius * a.radius { return false; }
+    match a.shape {
+        AreaShape::Disk => true,
+        AreaShape::Annulus(inner) => d2 >= inner.max(0) * inner.max(0),
+        AreaShape::Sector { angle, width } => {
+            if d2 == 0 { return true; }
+            let dir = outward.unwrap_or(0.0) + angle.to_radians();
+            let ang = (d.y as f32).atan2(d.x as f32);
+            wrap_angle(ang - dir).abs() <= width.to_radians() * 0.5
+        }
+    }
+}
+
+// Area mask (1=inside) for one template area: the shape around every anchor,
+// or a distance band for `Ley`, then the optional noise cut.
+fn area_mask(
     tpl: &MapTemplate,
+    a: &TerrainArea,
     base_centers: &[IVec2],
     shrines: &[IVec2],
+    ley_dist: &[f32],
     seed: u32,
-) -> Grid<u8> {
+) -> Vec<u8> {
     let size = IVec2::new(tpl.size.0, tpl.size.1);
-    let w = size.x; let h = size.y;
+    let (w, h) = (size.x, size.y);
+    let center = IVec2::new(w/2, h/2);
+    let mut m = vec![0u8; (w*h) as usize];
 
-    // Start as grass everywhere
-    let mut classes = Grid::<u8>::new(w, h);
-    for y in 0..h { for x in 0..w { classes.set(x,y, TERRAIN_GRASSLAND); } }
+    let outward = |p: IVec2| {
+        let v = (p - center).as_vec2();
+        Some(if v == Vec2::ZERO { 0.0 } else { v.y.atan2(v.x) })
+    };
+    let anchors: Vec<(IVec2, Option<f32>)> = match a.source {
+        AreaSource::Center => vec![(center, None)],
+        AreaSource::Point(x, y) => vec![(IVec2::new(x, y), None)],
+        AreaSource::Spawn => base_centers.iter().map(|&c| (c, outward(c))).collect(),
+        AreaSource::Shrine => shrines.iter().map(|&s| (s, outward(s))).collect(),
+        AreaSource::Ley => {
+            let inner = match a.shape { AreaShape::Annulus(inner) => inner.max(0) as f32, _ => 0.0 };
+            for (i, &d) in ley_dist.iter().enumerate() {
+                if d >= inner && d <= a.radius as f32 { m[i] = 1; }
+            }
+            Vec::new()
+        }
+    };
+    for (c, dir) in anchors {
+        let r = a.radius.max(0);
+        for y in (c.y - r).max(0)..=(c.y + r).min(h-1) {
+            for x in (c.x - r).max(0)..=(c.x + r).min(w-1) {
+                if in_shape(a, IVec2::new(x, y) - c, dir) { m[idx(w,x,y)] = 1; }
+            }
+        }
+    }
+
+    if let Some(n) = a.noise {
+        let nseed = (seed ^ n.seed.wrapping_mul(0x85EB_CA6B)).wrapping_add(0x27D4_EB2F);
+        for y in 0..h {
+            for x in 0..w {
+                let i = idx(w,x,y);
+                if m[i] == 0 { continue; }
+                let v = fbm_2d(x as f32 * n.freq, y as f32 * n.freq, nseed, n.octaves.max(1), 0.5, 2.0);
+                if v <= n.threshold { m[i] = 0; }
+            }
+        }
+    }
+    m
+}
 
-    // Hard "locked grass" mask: base disks + shrine disks
-    let mut locked = vec![0u8; (w*h) as usize];
+// Tie-break order for equal radii: fixed anchors claim space before repeated ones.
+fn source_rank(s: &AreaSource) -> u8 {
+    match s {
+        AreaSource::Point(..) => 0,
+        AreaSource::Center => 1,
+        AreaSource::Shrine => 2,
+        AreaSource::Spawn => 3,
+        AreaSource::Ley => 4,
+    }
+}
+
+// Hard "locked grass" mask: base disks + shrine disks
+fn locked_grass_mask(tpl: &MapTemplate, base_centers: &[IVec2], shrines: &[IVec2]) -> Vec<u8> {
+    let size = IVec2::new(tpl.size.0, tpl.size.1);
+    let mut locked = vec![0u8; (size.x*size.y) as usize];
     // Base disks
     let br = tpl.player_spawns.base_radius.max(1);
     for &c in base_centers {
@@ -146,51 +238,79 @@ pub fn generate_terrain_clumps(
         let m = circle_mask(size, s, sr);
         for i in 0..m.len() { if m[i]!=0 { locked[i]=1; } }
     }
+    locked
+}
+
+// Replace each value by its rank in [0,1] so thresholds read as area fractions.
+fn rank_normalize(values: &[f32]) -> Vec<f32> {
+    let n = values.len();
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let denom = (n.max(2) - 1) as f32;
+    let mut out = vec![0f32; n];
+    for (rank, &i) in order.iter().enumerate() {
+        out[i] = rank as f32 / denom;
+    }
+    out
+}
+
+/// New Phase 3: clumpy terrain that honors hard grass buffers around bases & shrines.
+/// We process areas in ascending radius so small/strong rules occupy space first.
+/// Ley corridors (if any) are painted before the areas, under the locked grass.
+/// `ley_lines` are the shaped ley segments that `AreaSource::Ley` bands measure from.
+pub fn generate_terrain_clumps(
+    tpl: &MapTemplate,
+    base_centers: &[IVec2],
+    shrines: &[IVec2],
+    ley_lines: &[(IVec2, IVec2)],
+    corridor: Option<&LeyCorridor>,
+    seed: u32,
+) -> Grid<u8> {
+    let size = IVec2::new(tpl.size.0, tpl.size.1);
+    let w = size.x; let h = size.y;
+    let reg = TerrainClasses::from_template(tpl);
+    let ground = reg.ground();
+
+    // Start as grass everywhere
+    let mut classes = Grid::<u8>::new(w, h);
+    for y in 0..h { for x in 0..w { classes.set(x,y, ground); } }
+
+    let locked = locked_grass_mask(tpl, base_centers, shrines);
     // Apply locked grass now
     for y in 0..h { for x in 0..w {
-        if locked[idx(w,x,y)] != 0 { classes.set(x,y, TERRAIN_GRASSLAND); }
+        if locked[idx(w,x,y)] != 0 { classes.set(x,y, ground); }
     }}
 
     // Painted mask: tiles already assigned by an area (won't be repainted by larger areas)
     let mut painted = locked.clone();
 
-    // Sort areas by priority: smaller radius first; tie-breaker Center before Spawn.
+    if let Some(cor) = corridor {
+        for y in 0..h { for x in 0..w {
+            let i = idx(w,x,y);
+            if locked[i] != 0 { continue; }
+            if let Some(k) = cor.class_at(i) { classes.set(x,y, k); painted[i] = 1; }
+        }}
+    }
+
+    // Sort areas by priority: smaller radius first; ties go Point, Center, Shrine, Spawn, Ley.
     let mut areas = tpl.terrain.areas.clone();
-    areas.sort_by(|a,b| {
-        use std::cmp::Ordering::*;
-        match a.radius.cmp(&b.radius) {
-            Equal => match (&a.source, &b.source) {
-                (AreaSource::Center, AreaSource::Spawn) => Less,
-                (AreaSource::Spawn,  AreaSource::Center) => Greater,
-                _ => Equal,
-            }
-            other => other
-        }
-    });
+    areas.sort_by(|a,b| a.radius.cmp(&b.radius).then(source_rank(&a.source).cmp(&source_rank(&b.source))));
+
+    // Ley distances are only needed (and only resolved) as far as the widest Ley band.
+    let ley_reach = areas.iter().filter(|a| matches!(a.source, AreaSource::Ley)).map(|a| a.radius).max();
+    let ley_dist = match ley_reach {
+        Some(r) => ley_distance(size, ley_lines, r),
+        None => Vec::new(),
+    };
 
-    // Precompute some clump sizes
-    let (f_min,f_max) = tpl.terrain.clumps.forest_patch;
-    let (w_min,w_max) = tpl.terrain.clumps.water_patch;
-    let (m_min,m_max) = tpl.terrain.clumps.mountain_patch;
+    // Precompute clump sizes per class
+    let patches: Vec<(i32,i32)> = (0..reg.len()).map(|k| reg.patch(k as u8, &tpl.terrain.clumps)).collect();
 
     let mut rng = Rng64::new(seed as u64 ^ 0xA53C_9E37);
 
     for a in &areas {
-        // Build area mask: union over spawns (if source=Spawn), or single circle at center
-        let mut area_mask = vec![0u8; (w*h) as usize];
-        match a.source {
-            AreaSource::Center => {
-                let c = IVec2::new(tpl.size.0/2, tpl.size.1/2);
-                let m = circle_mask(size, c, a.radius);
-                for i in 0..m.len() { if m[i]!=0 { area_mask[i]=1; } }
-            }
-            AreaSource::Spawn => {
-                for &c in base_centers {
-                    let m = circle_mask(size, c, a.radius);
-                    for i in 0..m.len() { if m[i]!=0 { area_mask[i]=1; } }
-                }
-            }
-        }
+        // Build area mask: the area's shape around each anchor (or a ley band), noise-cut
+        let area_mask = area_mask(tpl, a, base_centers, shrines, &ley_dist, seed);
 
         // Available tiles to paint in this area now
         let mut avail: Vec<usize> = (0..(w*h) as usize)
@@ -200,15 +320,12 @@ pub fn generate_terrain_clumps(
         if avail.is_empty() { continue; }
 
         // Local target counts (approximate) for non-grass classes
-        let mut mix = weights_arr(&a.weights);
+        let mut mix = reg.weights(&a.weights);
         // emphasize this area's pull
-        for k in 0..4 { mix[k] *= a.scale.max(0.0); }
-        normalize(&mut mix);
+        for m in mix.iter_mut() { *m *= a.scale.max(0.0); }
+        normalize(&mut mix, ground);
 
         let target_total = avail.len() as f32;
-        let mut target_forest   = (mix[TERRAIN_FOREST as usize]   * target_total).round() as i32;
-        let mut target_water    = (mix[TERRAIN_WATER as usize]    * target_total).round() as i32;
-        let mut target_mountain = (mix[TERRAIN_MOUNTAIN as usize] * target_total).round() as i32;
 
         // Helper: pick a random center from available
         let pick_center = |rng: &mut Rng64, avail: &Vec<usize>| -> Option<IVec2> {
@@ -239,25 +356,167 @@ pub fn generate_terrain_clumps(
             }
         };
 
-        // Forest, then Water, then Mountain
-        place_class(TERRAIN_FOREST,   (f_min,f_max), &mut target_forest);
-        place_class(TERRAIN_WATER,    (w_min,w_max), &mut target_water);
-        place_class(TERRAIN_MOUNTAIN, (m_min,m_max), &mut target_mountain);
+        // Every non-ground class in registry order (classic: forest, water, mountain)
+        for k in (0..reg.len() as u8).filter(|&k| k != ground) {
+            let mut target = (mix[k as usize] * target_total).round() as i32;
+            place_class(k, patches[k as usize], &mut target);
+        }
 
-        // Fill leftover (still unpainted in this area) with grass and mark as painted
+        // Fill leftover (still unpainted in this area) with ground and mark as painted
         for i in avail {
             if painted[i]==0 && area_mask[i]!=0 {
                 painted[i]=1;
                 let x = (i as i32) % w; let y = (i as i32) / w;
-                classes.set(x,y, TERRAIN_GRASSLAND);
+                classes.set(x,y, ground);
             }
         }
     }
 
     // Ensure locked grass stays grass (defensive)
     for y in 0..h { for x in 0..w {
-        if locked[idx(w,x,y)]!=0 { classes.set(x,y, TERRAIN_GRASSLAND); }
+        if locked[idx(w,x,y)]!=0 { classes.set(x,y, ground); }
     }}
 
     classes
 }
+
+/// Phase 3 (biome mode): Whittaker-style classes from elevation x moisture.
+/// Moisture is fBm plus a bonus on low ground; both fields are rank-normalised
+/// so `BiomeConfig` levels behave like area fractions. Locked grass and ley corridors are honoured.
+/// Returns the class grid and the normalised moisture field (for debug output).
+pub fn generate_terrain_biomes(
+    tpl: &MapTemplate,
+    height: &Grid<f32>,
+    base_centers: &[IVec2],
+    shrines: &[IVec2],
+    corridor: Option<&LeyCorridor>,
+    seed: u32,
+) -> (Grid<u8>, Grid<f32>) {
+    let (w, h) = (height.w, height.h);
+    let cfg = &tpl.terrain.biomes;
+    let reg = TerrainClasses::from_template(tpl);
+    let ground = reg.ground();
+    // Missing roles fall back to ground so partial registries still work.
+    let water = reg.role(ClassRole::Water).unwrap_or(ground);
+    let mountain = reg.role(ClassRole::Mountain).unwrap_or(ground);
+    let forest = reg.role(ClassRole::Forest).unwrap_or(ground);
+    let locked = locked_grass_mask(tpl, base_centers, shrines);
+
+    let mut raw_elev = Vec::with_capacity((w*h) as usize);
+    for y in 0..h { for x in 0..w { raw_elev.push(*height.get(x, y)); } }
+    let elev = rank_normalize(&raw_elev);
+
+    // Moisture: noise in [0,1] pulled up where the ground is low.
+    let moist_seed = (seed ^ 0x3C6E_F372).wrapping_mul(0x9E37_79B1);
+    let mut raw_moist = Vec::with_capacity((w*h) as usize);
+    for y in 0..h {
+        for x in 0..w {
+            let n = fbm_2d(
+                x as f32 * cfg.moisture_freq, y as f32 * cfg.moisture_freq,
+                moist_seed, cfg.moisture_octaves, 0.5, 2.0,
+            ) * 0.5 + 0.5;
+            let e = elev[idx(w, x, y)];
+            raw_moist.push(n + cfg.lowland_wetness * (1.0 - e));
+        }
+    }
+    let moist = rank_normalize(&raw_moist);
+
+    let mut classes = Grid::<u8>::new(w, h);
+    let mut moisture = Grid::<f32>::new(w, h);
+    for y in 0..h {
+        for x in 0..w {
+            let i = idx(w, x, y);
+            let (e, m) = (elev[i], moist[i]);
+            moisture.set(x, y, m);
+            let class = if locked[i] != 0 {
+                ground
+            } else if let Some(k) = corridor.and_then(|c| c.class_at(i)) {
+                k
+            } else if e < cfg.sea_level || (e < cfg.lowland && m > cfg.marsh_moisture) {
+                water
+            } else if e > cfg.mountain_level {
+                mountain
+            } else if m > cfg.forest_moisture {
+                forest
+            } else {
+                ground
+            };
+            classes.set(x, y, class);
+        }
+    }
+
+    (classes, moisture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::template::{AreaNoise, TerrainWeights};
+
+    const SIZE: i32 = 64;
+
+    fn tpl() -> MapTemplate {
+        let mut tpl = MapTemplate::load("assets/maps/haunted_woods.ron").expect("preset loads").template;
+        tpl.size = (SIZE, SIZE);
+        tpl
+    }
+
+    fn area(source: AreaSource, radius: i32, shape: AreaShape) -> TerrainArea {
+        TerrainArea { source, radius, shape, noise: None, weights: TerrainWeights(Vec::new()), scale: 1.0 }
+    }
+
+    fn inside(m: &[u8], p: IVec2) -> bool { m[idx(SIZE, p.x, p.y)] == 1 }
+
+    #[test]
+    fn annulus_skips_the_middle() {
+        let c = IVec2::splat(SIZE / 2);
+        let m = area_mask(&tpl(), &area(AreaSource::Center, 10, AreaShape::Annulus(4)), &[], &[], &[], 0);
+        for (dx, want) in [(0, false), (3, false), (4, true), (7, true), (10, true), (11, false)] {
+            assert_eq!(inside(&m, c + IVec2::new(dx, 0)), want, "{dx} tiles out");
+        }
+    }
+
+    #[test]
+    fn sectors_turn_with_each_base() {
+        let (east, south) = (IVec2::new(52, 32), IVec2::new(32, 52));
+        let shape = AreaShape::Sector { angle: 0.0, width: 90.0 };
+        let m = area_mask(&tpl(), &area(AreaSource::Spawn, 8, shape), &[east, south], &[], &[], 0);
+        // Outwards is +x for the east base and +y for the south one.
+        assert!(inside(&m, east + IVec2::new(6, 0)) && inside(&m, east + IVec2::new(5, 3)));
+        assert!(!inside(&m, east + IVec2::new(-6, 0)) && !inside(&m, east + IVec2::new(0, 6)));
+        assert!(inside(&m, south + IVec2::new(0, 6)) && inside(&m, south + IVec2::new(-3, 5)));
+        assert!(!inside(&m, south + IVec2::new(6, 0)) && !inside(&m, south + IVec2::new(0, -6)));
+    }
+
+    #[test]
+    fn noise_only_cuts_inside_the_shape() {
+        let tpl = tpl();
+        let disk = area(AreaSource::Center, 20, AreaShape::Disk);
+        let full = area_mask(&tpl, &disk, &[], &[], &[], 5);
+        let cut = |threshold, seed| {
+            let a = TerrainArea { noise: Some(AreaNoise { freq: 0.1, threshold, octaves: 3, seed }), ..disk.clone() };
+            area_mask(&tpl, &a, &[], &[], &[], 5)
+        };
+        let count = |m: &[u8]| m.iter().filter(|&&v| v == 1).count();
+
+        let half = cut(0.0, 1);
+        assert!(half.iter().zip(&full).all(|(&h, &f)| h <= f), "noise never adds tiles");
+        assert!(count(&half) > 0 && count(&half) < count(&full));
+        assert_ne!(half, cut(0.0, 2), "the noise seed changes the cut");
+        assert_eq!(cut(-1.5, 1), full, "a threshold below the noise keeps everything");
+    }
+
+    #[test]
+    fn ley_bands_measure_from_the_lines() {
+        let size = IVec2::splat(SIZE);
+        let lines = [(IVec2::new(10, 32), IVec2::new(54, 32))];
+        let dist = ley_distance(size, &lines, 5);
+        let band = area_mask(&tpl(), &area(AreaSource::Ley, 5, AreaShape::Annulus(2)), &[], &[], &dist, 0);
+        let strip = area_mask(&tpl(), &area(AreaSource::Ley, 5, AreaShape::Disk), &[], &[], &dist, 0);
+        for (dy, in_band, in_strip) in [(0, false, true), (1, false, true), (3, true, true), (5, true, true), (6, false, false)] {
+            let p = IVec2::new(30, 32 + dy);
+            assert_eq!((inside(&band, p), inside(&strip, p)), (in_band, in_strip), "{dy} tiles off the line");
+        }
+        assert!(!inside(&strip, IVec2::new(2, 32)), "bands end with the line");
+    }
+}
@@ -1,66 +1,540 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use glam::{IVec2, Vec2};
+use super::grid::Grid;
+use super::template::LeyStyle;
+use super::noise::fbm_2d;
 
 /// Simple configuration for Phase 2 ley network.
+/// Topologies are additive: every enabled one contributes edges to the same graph.
+#[derive(Clone, Copy)]
 pub struct LeySettings {
     pub m_shrines: usize,   // how many shrines
     pub shrine_ring: i32,   // ring radius for shrines
     pub offset_deg: f32,    // angular offset vs. base ring start (e.g., 180/n for half-step)
     pub connect_cycle: bool,
     pub connect_spokes: bool,
+    pub connect_mst: bool,      // minimum spanning tree over shrines
+    pub connect_delaunay: bool, // Delaunay triangulation over shrines
+    pub connect_knn: usize,     // link each shrine to its k nearest shrines (0 = off)
+    pub connect_bases: usize,   // link each base to its k nearest shrines (0 = off)
+    pub style: LeyStyle,
+    pub meander: f32,           // max sideways offset in tiles (Meander)
+    pub meander_freq: f32,      // wiggles per tile of line length (Meander)
+    pub terrain_weight: f32,    // extra cost of low ground (Terrain)
+    pub sample_spacing: i32,    // tiles between polyline samples
+    pub seed: u32,
+    pub corridor_width: i32,    // force `corridor_class` within this many tiles (0 = off)
+    pub corridor_class: u8,
+    pub object_reach: i32,      // tiles counted as "on the ley" for object rules
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LeyNodeKind {
+    Shrine,
+    Center,
+    Base,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct LeyNode {
+    pub pos: IVec2,
+    pub kind: LeyNodeKind,
+}
+
+/// Undirected edge between two node indices.
+#[derive(Clone, Debug)]
+pub struct LeyEdge {
+    pub a: usize,
+    pub b: usize,
+    /// Length along `points`.
+    pub length: f32,
+    /// Polyline from node `a` to node `b` (both included), one sample every
+    /// `sample_spacing` tiles. Straight until `shape_ley` runs.
+    pub points: Vec<IVec2>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LeyGraph {
+    pub nodes: Vec<LeyNode>,
+    pub edges: Vec<LeyEdge>,
+}
+
+impl LeyGraph {
+    pub fn add_node(&mut self, pos: IVec2, kind: LeyNodeKind) -> usize {
+        self.nodes.push(LeyNode { pos, kind });
+        self.nodes.len() - 1
+    }
+
+    /// Add `a`–`b` unless it is a loop or already present (in either direction).
+    pub fn add_edge(&mut self, a: usize, b: usize) {
+        if a == b { return; }
+        if self.edges.iter().any(|e| (e.a == a && e.b == b) || (e.a == b && e.b == a)) { return; }
+        let (pa, pb) = (self.nodes[a].pos, self.nodes[b].pos);
+        let length = pa.as_vec2().distance(pb.as_vec2());
+        self.edges.push(LeyEdge { a, b, length, points: vec![pa, pb] });
+    }
+
+    /// Edge endpoints as positions.
+    pub fn segment(&self, e: &LeyEdge) -> (IVec2, IVec2) {
+        (self.nodes[e.a].pos, self.nodes[e.b].pos)
+    }
+
+    /// Every polyline piece as a position pair (for drawing and distance queries).
+    pub fn segments(&self) -> Vec<(IVec2, IVec2)> {
+        self.edges.iter()
+            .flat_map(|e| e.points.windows(2).map(|w| (w[0], w[1])))
+            .collect()
+    }
+}
+
+/// Tiles along ley lines that Phase 3/4 force to one terrain class.
+#[derive(Clone)]
+pub struct LeyCorridor {
+    pub mask: Vec<u8>,
+    pub class: u8,
+}
+
+impl LeyCorridor {
+    #[inline]
+    pub fn class_at(&self, i: usize) -> Option<u8> {
+        (self.mask[i] != 0).then_some(self.class)
+    }
 }
 
 /// Raw ley output for Phase 2
 pub struct LeyNetwork {
     pub shrines: Vec<IVec2>,
-    /// Line segments as pairs of points (pixel/ tile space)
-    pub lines: Vec<(IVec2, IVec2)>,
+    /// Shrines are nodes `0..shrines.len()`; centre and base nodes follow when used.
+    pub graph: LeyGraph,
+}
+
+// Prim's algorithm on the complete graph over `pts`.
+fn mst_edges(pts: &[Vec2]) -> Vec<(usize, usize)> {
+    let n = pts.len();
+    let mut out = Vec::new();
+    if n < 2 { return out; }
+    let mut in_tree = vec![false; n];
+    let mut best = vec![(f32::MAX, 0usize); n];
+    in_tree[0] = true;
+    for j in 1..n { best[j] = (pts[0].distance_squared(pts[j]), 0); }
+    for _ in 1..n {
+        let Some(next) = (0..n).filter(|&j| !in_tree[j]).min_by(|&i, &j| best[i].0.total_cmp(&best[j].0)) else { break; };
+        in_tree[next] = true;
+        out.push((best[next].1, next));
+        for j in 0..n {
+            if in_tree[j] { continue; }
+            let d = pts[next].distance_squared(pts[j]);
+            if d < best[j].0 { best[j] = (d, next); }
+        }
+    }
+    out
+}
+
+// Each point to its `k` nearest others (ties broken by index).
+fn knn_edges(pts: &[Vec2], k: usize) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    for i in 0..pts.len() {
+        let mut others: Vec<usize> = (0..pts.len()).filter(|&j| j != i).collect();
+        others.sort_by(|&a, &b| pts[i].distance_squared(pts[a]).total_cmp(&pts[i].distance_squared(pts[b])));
+        out.extend(others.into_iter().take(k).map(|j| (i, j)));
+    }
+    out
+}
+
+// >0 when a, b, c turn counter-clockwise.
+fn orient(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+// Strictly inside the circumcircle of triangle abc (any winding).
+fn in_circumcircle(a: [f64; 2], b: [f64; 2], c: [f64; 2], d: [f64; 2]) -> bool {
+    let (adx, ady) = (a[0] - d[0], a[1] - d[1]);
+    let (bdx, bdy) = (b[0] - d[0], b[1] - d[1]);
+    let (cdx, cdy) = (c[0] - d[0], c[1] - d[1]);
+    let det = (adx * adx + ady * ady) * (bdx * cdy - cdx * bdy)
+        - (bdx * bdx + bdy * bdy) * (adx * cdy - cdx * ady)
+        + (cdx * cdx + cdy * cdy) * (adx * bdy - bdx * ady);
+    det * orient(a, b, c).signum() > 1e-9
+}
+
+// Bowyer–Watson. Shrine counts are small, so the O(n²) version is plenty.
+fn delaunay_edges(pts: &[Vec2]) -> Vec<(usize, usize)> {
+    let n = pts.len();
+    if n < 3 {
+        return if n == 2 { vec![(0, 1)] } else { Vec::new() };
+    }
+    let mut v: Vec<[f64; 2]> = pts.iter().map(|p| [p.x as f64, p.y as f64]).collect();
+
+    // Super triangle well outside the bounding box
+    let (mut minx, mut miny, mut maxx, mut maxy) = (f64::MAX, f64::MAX, f64::MIN, f64::MIN);
+    for p in &v {
+        minx = minx.min(p[0]); miny = miny.min(p[1]);
+        maxx = maxx.max(p[0]); maxy = maxy.max(p[1]);
+    }
+    let span = (maxx - minx).max(maxy - miny).max(1.0) * 20.0;
+    let (mx, my) = ((minx + maxx) / 2.0, (miny + maxy) / 2.0);
+    v.push([mx - span, my - span]);
+    v.push([mx + span, my - span]);
+    v.push([mx, my + span]);
+
+    let mut tris: Vec<[usize; 3]> = vec![[n, n + 1, n + 2]];
+    for i in 0..n {
+        let p = v[i];
+        let (bad, keep): (Vec<[usize; 3]>, Vec<[usize; 3]>) = tris.into_iter()
+            .partition(|t| in_circumcircle(v[t[0]], v[t[1]], v[t[2]], p));
+
+        // Boundary of the cavity = edges used by exactly one bad triangle
+        let mut boundary: Vec<(usize, usize)> = Vec::new();
+        for t in &bad {
+            for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+                if let Some(k) = boundary.iter().position(|&(x, y)| (x == b && y == a) || (x == a && y == b)) {
+                    boundary.swap_remove(k);
+                } else {
+                    boundary.push((a, b));
+                }
+            }
+        }
+        tris = keep;
+        tris.extend(boundary.into_iter().map(|(a, b)| [a, b, i]));
+    }
+
+    let mut out = Vec::new();
+    for t in tris.iter().filter(|t| t.iter().all(|&k| k < n)) {
+        out.extend([(t[0], t[1]), (t[1], t[2]), (t[2], t[0])]);
+    }
+    out
 }
 
 /// Place `m_shrines` evenly on a ring of radius `shrine_ring`,
-/// offset by `offset_deg` relative to Phase 1 base ring start angle.
-/// Optionally connect shrines in a cycle and/or draw center spokes.
+/// offset by `offset_deg` relative to Phase 1 base ring start angle,
+/// then connect them with every topology enabled in `settings`.
 pub fn generate_ley(
     map_size: (i32, i32),
-    num_bases: usize,
+    base_centers: &[IVec2],
     start_angle_deg: f32,
-    m_shrines: usize,
-    shrine_ring: i32,
-    offset_deg: f32,
-    connect_cycle: bool,
-    connect_spokes: bool,
+    settings: &LeySettings,
 ) -> LeyNetwork {
-    assert!(num_bases >= 1 && m_shrines >= 1);
+    let m_shrines = settings.m_shrines;
+    assert!(!base_centers.is_empty() && m_shrines >= 1);
     let size = glam::ivec2(map_size.0, map_size.1);
     let center = size.as_vec2() / 2.0;
 
     let start_angle = start_angle_deg.to_radians();
-    let offset = offset_deg.to_radians();
+    let offset = settings.offset_deg.to_radians();
 
     // Shrine positions
     let mut shrines = Vec::with_capacity(m_shrines);
     for j in 0..m_shrines {
         let ang = start_angle + offset + (j as f32) * std::f32::consts::TAU / (m_shrines as f32);
-        let p = center + Vec2::new(ang.cos(), ang.sin()) * (shrine_ring as f32);
+        let p = center + Vec2::new(ang.cos(), ang.sin()) * (settings.shrine_ring as f32);
         shrines.push(IVec2::new(p.x.round() as i32, p.y.round() as i32));
     }
 
-    // Build ley lines
-    let mut lines = Vec::new();
+    let mut graph = LeyGraph::default();
+    for &s in &shrines { graph.add_node(s, LeyNodeKind::Shrine); }
+    let shrine_pts: Vec<Vec2> = shrines.iter().map(|s| s.as_vec2()).collect();
 
-    if connect_cycle {
+    // Build ley lines
+    if settings.connect_cycle {
         for j in 0..m_shrines {
-            let a = shrines[j];
-            let b = shrines[(j + 1) % m_shrines];
-            lines.push((a, b));
+            graph.add_edge(j, (j + 1) % m_shrines);
         }
     }
 
-    if connect_spokes {
+    if settings.connect_spokes {
         let c = IVec2::new(center.x.round() as i32, center.y.round() as i32);
-        for &s in &shrines {
-            lines.push((c, s));
+        let ci = graph.add_node(c, LeyNodeKind::Center);
+        for j in 0..m_shrines {
+            graph.add_edge(ci, j);
+        }
+    }
+
+    if settings.connect_mst {
+        for (a, b) in mst_edges(&shrine_pts) { graph.add_edge(a, b); }
+    }
+
+    if settings.connect_delaunay {
+        for (a, b) in delaunay_edges(&shrine_pts) { graph.add_edge(a, b); }
+    }
+
+    if settings.connect_knn > 0 {
+        for (a, b) in knn_edges(&shrine_pts, settings.connect_knn) { graph.add_edge(a, b); }
+    }
+
+    if settings.connect_bases > 0 {
+        for &bc in base_centers {
+            let bi = graph.add_node(bc, LeyNodeKind::Base);
+            let mut order: Vec<usize> = (0..m_shrines).collect();
+            order.sort_by(|&a, &b| bc.as_vec2().distance_squared(shrine_pts[a])
+                .total_cmp(&bc.as_vec2().distance_squared(shrine_pts[b])));
+            for j in order.into_iter().take(settings.connect_bases) {
+                graph.add_edge(bi, j);
+            }
+        }
+    }
+
+    LeyNetwork { shrines, graph }
+}
+
+fn polyline_length(pts: &[IVec2]) -> f32 {
+    pts.windows(2).map(|w| w[0].as_vec2().distance(w[1].as_vec2())).sum()
+}
+
+// Evenly resample a dense polyline every `spacing` tiles, keeping both ends.
+fn resample(pts: &[Vec2], spacing: f32) -> Vec<IVec2> {
+    let round = |p: Vec2| IVec2::new(p.x.round() as i32, p.y.round() as i32);
+    let mut out = vec![round(pts[0])];
+    let mut carry = 0.0; // distance walked since the last sample
+    for w in pts.windows(2) {
+        let (a, b) = (w[0], w[1]);
+        let seg = a.distance(b);
+        let mut t = spacing - carry;
+        while t <= seg {
+            out.push(round(a + (b - a) * (t / seg)));
+            t += spacing;
+        }
+        carry = seg - (t - spacing);
+    }
+    let end = round(pts[pts.len() - 1]);
+    if out.last() != Some(&end) { out.push(end); }
+    out.dedup();
+    out
+}
+
+// Sideways fBm offset, tapered to zero at both ends so the line still meets its nodes.
+fn meander_path(a: Vec2, b: Vec2, s: &LeySettings, seed: u32) -> Vec<Vec2> {
+    let d = b - a;
+    let len = d.length();
+    if len < 1.0 { return vec![a, b]; }
+    let perp = Vec2::new(-d.y, d.x) / len;
+    let n = len.ceil() as usize;
+    (0..=n).map(|i| {
+        let t = i as f32 / n as f32;
+        let wobble = fbm_2d(t * len * s.meander_freq, 0.0, seed, 3, 0.5, 2.0);
+        a + d * t + perp * (s.meander * wobble * (std::f32::consts::PI * t).sin())
+    }).collect()
+}
+
+const NEIGHBORS8: [(i32, i32); 8] = [
+    (-1,-1), (0,-1), (1,-1),
+    (-1, 0),         (1, 0),
+    (-1, 1), (0, 1), (1, 1),
+];
+
+// A* over the heightmap. Steps cost their length, scaled up on low ground so
+// lines ride ridges and skirt the basins where water ends up.
+fn terrain_path(height: &Grid<f32>, a: IVec2, b: IVec2, weight: f32) -> Vec<Vec2> {
+    let (w, h) = (height.w, height.h);
+    let clamp = |p: IVec2| p.clamp(IVec2::ZERO, IVec2::new(w - 1, h - 1));
+    let (start, goal) = (clamp(a), clamp(b));
+
+    let mut minh = f32::MAX; let mut maxh = f32::MIN;
+    for y in 0..h { for x in 0..w {
+        let v = *height.get(x, y);
+        minh = minh.min(v); maxh = maxh.max(v);
+    }}
+    let span = (maxh - minh).max(1e-6);
+    let weight = weight.max(0.0);
+
+    // Integer costs (x100) so the heap orders exactly; the heuristic is the
+    // straight-line distance at the cheapest possible rate, so it stays admissible.
+    let idx = |p: IVec2| (p.y * w + p.x) as usize;
+    let heuristic = |p: IVec2| (p.as_vec2().distance(goal.as_vec2()) * 100.0) as u32;
+    let mut dist = vec![u32::MAX; (w * h) as usize];
+    let mut prev = vec![usize::MAX; (w * h) as usize];
+    let mut heap = BinaryHeap::new();
+    dist[idx(start)] = 0;
+    heap.push(Reverse((heuristic(start), idx(start))));
+
+    while let Some(Reverse((_, i))) = heap.pop() {
+        let p = IVec2::new(i as i32 % w, i as i32 / w);
+        if p == goal { break; }
+        let d = dist[i];
+        for (dx, dy) in NEIGHBORS8 {
+            let n = p + IVec2::new(dx, dy);
+            if !height.in_bounds(n) { continue; }
+            let low = 1.0 - (*height.get(n.x, n.y) - minh) / span;
+            let step = if dx != 0 && dy != 0 { std::f32::consts::SQRT_2 } else { 1.0 };
+            let nd = d + (step * (1.0 + weight * low * low) * 100.0).round() as u32;
+            let j = idx(n);
+            if nd < dist[j] {
+                dist[j] = nd;
+                prev[j] = i;
+                heap.push(Reverse((nd + heuristic(n), j)));
+            }
         }
     }
 
-    LeyNetwork { shrines, lines }
+    let mut path = vec![goal.as_vec2()];
+    let mut cur = idx(goal);
+    while prev[cur] != usize::MAX {
+        cur = prev[cur];
+        path.push(IVec2::new(cur as i32 % w, cur as i32 / w).as_vec2());
+    }
+    path.reverse();
+    // Keep the true endpoints even if they were clamped onto the map.
+    path[0] = a.as_vec2();
+    let last = path.len() - 1;
+    path[last] = b.as_vec2();
+    path
+}
+
+/// Phase 2C: turn every edge into a sampled polyline in the configured style.
+/// Runs after the heightmap exists so Terrain style can follow it.
+pub fn shape_ley(graph: &mut LeyGraph, height: &Grid<f32>, settings: &LeySettings) {
+    let spacing = settings.sample_spacing.max(1) as f32;
+    for (i, e) in graph.edges.iter_mut().enumerate() {
+        let (a, b) = (graph.nodes[e.a].pos, graph.nodes[e.b].pos);
+        let dense = match settings.style {
+            LeyStyle::Straight => vec![a.as_vec2(), b.as_vec2()],
+            LeyStyle::Meander => {
+                let seed = settings.seed.wrapping_add((i as u32).wrapping_mul(0x9E37_79B9));
+                meander_path(a.as_vec2(), b.as_vec2(), settings, seed)
+            }
+            LeyStyle::Terrain => terrain_path(height, a, b, settings.terrain_weight),
+        };
+        e.points = resample(&dense, spacing);
+        e.length = polyline_length(&e.points);
+    }
+}
+
+/// 1 for every tile within `radius` of any ley polyline (map size `size`).
+pub fn ley_mask(graph: &LeyGraph, size: IVec2, radius: i32) -> Vec<u8> {
+    let (w, h) = (size.x, size.y);
+    let r = radius.max(0);
+    let mut mask = vec![0u8; (w * h) as usize];
+    let mut stamp = |c: IVec2| {
+        for y in (c.y - r).max(0)..=(c.y + r).min(h - 1) {
+            for x in (c.x - r).max(0)..=(c.x + r).min(w - 1) {
+                let (dx, dy) = (x - c.x, y - c.y);
+                if dx*dx + dy*dy <= r*r { mask[(y * w + x) as usize] = 1; }
+            }
+        }
+    };
+    for (a, b) in graph.segments() {
+        // Walk the segment in sub-tile steps so no tile along it is skipped.
+        let (af, bf) = (a.as_vec2(), b.as_vec2());
+        let steps = (bf - af).abs().max_element().ceil().max(1.0) as i32;
+        for k in 0..=steps {
+            let p = af.lerp(bf, k as f32 / steps as f32);
+            stamp(IVec2::new(p.x.round() as i32, p.y.round() as i32));
+        }
+    }
+    mask
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `n` points evenly spaced on a circle, optionally snapped to tiles like
+    // `generate_ley`'s shrines.
+    fn ring(n: usize, r: f32, snap: bool) -> Vec<Vec2> {
+        (0..n).map(|k| {
+            let a = k as f32 / n as f32 * std::f32::consts::TAU;
+            let p = Vec2::new(128.0, 128.0) + Vec2::new(a.cos(), a.sin()) * r;
+            if snap { p.round() } else { p }
+        }).collect()
+    }
+
+    fn undirected(edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut e: Vec<_> = edges.iter().map(|&(a, b)| (a.min(b), a.max(b))).collect();
+        e.sort();
+        e.dedup();
+        e
+    }
+
+    fn as_f64(p: Vec2) -> [f64; 2] { [p.x as f64, p.y as f64] }
+
+    // Segments ab and cd cross at a point inside both.
+    fn cross(pts: &[Vec2], (a, b): (usize, usize), (c, d): (usize, usize)) -> bool {
+        if a == c || a == d || b == c || b == d { return false; }
+        let [pa, pb, pc, pd] = [pts[a], pts[b], pts[c], pts[d]].map(as_f64);
+        orient(pa, pb, pc) * orient(pa, pb, pd) < 0.0 && orient(pc, pd, pa) * orient(pc, pd, pb) < 0.0
+    }
+
+    // Vertices on the convex hull, collinear ones excluded (monotone chain).
+    fn hull_len(pts: &[Vec2]) -> usize {
+        let mut p: Vec<[f64; 2]> = pts.iter().map(|&q| as_f64(q)).collect();
+        p.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mut hull: Vec<[f64; 2]> = Vec::new();
+        for pass in 0..2 {
+            let start = hull.len();
+            for &q in &p {
+                while hull.len() >= start + 2 && orient(hull[hull.len() - 2], hull[hull.len() - 1], q) <= 0.0 { hull.pop(); }
+                hull.push(q);
+            }
+            hull.pop();
+            if pass == 0 { p.reverse(); }
+        }
+        hull.len()
+    }
+
+    // A triangulation of n points with h on the hull has 3n - 3 - h edges,
+    // none of them crossing.
+    fn assert_triangulation(pts: &[Vec2]) {
+        let e = undirected(&delaunay_edges(pts));
+        for (i, &a) in e.iter().enumerate() {
+            for &b in &e[i + 1..] {
+                assert!(!cross(pts, a, b), "{a:?} crosses {b:?} for {} points", pts.len());
+            }
+        }
+        assert_eq!(e.len(), 3 * pts.len() - 3 - hull_len(pts), "edge count for {} points", pts.len());
+    }
+
+    #[test]
+    fn delaunay_on_cocircular_ring() {
+        for n in 3..=16 {
+            assert_triangulation(&ring(n, 60.0, false));
+            assert_triangulation(&ring(n, 60.0, true));
+        }
+    }
+
+    #[test]
+    fn delaunay_on_ring_with_centre() {
+        for n in 3..=16 {
+            let mut pts = ring(n, 60.0, true);
+            pts.push(Vec2::new(128.0, 128.0));
+            assert_triangulation(&pts);
+            // Every ring point sees the centre.
+            let e = undirected(&delaunay_edges(&pts));
+            assert!((0..n).all(|k| e.contains(&(k, n))), "{n} points: centre not linked to every shrine");
+        }
+    }
+
+    #[test]
+    fn delaunay_square_picks_one_diagonal() {
+        let pts = [Vec2::new(0.0, 0.0), Vec2::new(10.0, 0.0), Vec2::new(10.0, 10.0), Vec2::new(0.0, 10.0)];
+        assert_eq!(undirected(&delaunay_edges(&pts)).len(), 5);
+    }
+
+    #[test]
+    fn mst_spans_ring_with_its_sides() {
+        for n in 3..=12 {
+            let pts = ring(n, 60.0, false);
+            let e = mst_edges(&pts);
+            assert_eq!(e.len(), n - 1);
+            // Connected: union-find over the edges.
+            let mut parent: Vec<usize> = (0..n).collect();
+            fn find(p: &mut [usize], i: usize) -> usize { if p[i] == i { i } else { let r = find(p, p[i]); p[i] = r; r } }
+            for &(a, b) in &e {
+                let (ra, rb) = (find(&mut parent, a), find(&mut parent, b));
+                parent[ra] = rb;
+            }
+            let root = find(&mut parent, 0);
+            assert!((0..n).all(|i| find(&mut parent, i) == root));
+            // On a regular ring the cheapest tree is n - 1 sides.
+            let side = pts[0].distance(pts[1]);
+            let total: f32 = e.iter().map(|&(a, b)| pts[a].distance(pts[b])).sum();
+            assert!((total - side * (n - 1) as f32).abs() < 1e-2, "{n} points: {total} vs {}", side * (n - 1) as f32);
+        }
+    }
+
+    #[test]
+    fn mst_and_delaunay_small_inputs() {
+        assert!(mst_edges(&[]).is_empty());
+        assert!(delaunay_edges(&[Vec2::ZERO]).is_empty());
+        assert_eq!(delaunay_edges(&[Vec2::ZERO, Vec2::X]), vec![(0, 1)]);
+    }
 }
@@ -6,4 +6,17 @@ pub mod ley;
 pub mod landscape;
 pub mod spawns;
 pub mod blend;
-pub mod objects;
\ No newline at end of file
+pub mod objects;
+pub mod noise;
+pub mod elevation;
+pub mod rivers;
+pub mod connectivity;
+pub mod symmetry;
+pub mod fairness;
+pub mod classes;
+pub mod compose;
+pub mod validate;
+pub mod seed;
+pub mod pipeline;
+pub mod tmx;
+pub mod autotile;
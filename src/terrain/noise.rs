@@ -0,0 +1,62 @@
+// Shared lattice value noise + fBm used by the blend, elevation and later phases.
+
+#[inline] fn fade(t: f32) -> f32 { t * t * (3.0 - 2.0 * t) }
+#[inline] pub fn lerp(a: f32, b: f32, t: f32) -> f32 { a + (b - a) * t }
+
+// Hash a lattice point (xi, yi) + seed into [0,1)
+pub fn hash01_lattice(xi: i32, yi: i32, seed: u32) -> f32 {
+    let mut v = (xi as u32).wrapping_mul(0x9E3779B1)
+        ^ (yi as u32).wrapping_mul(0x85EBCA77)
+        ^ seed.wrapping_mul(0xC2B2AE3D);
+    v ^= v >> 16; v = v.wrapping_mul(0x7feb352d);
+    v ^= v >> 15; v = v.wrapping_mul(0x846ca68b);
+    v ^= v >> 16;
+    (v as f32) / (u32::MAX as f32) // [0,1)
+}
+
+// Value noise with smooth bilinear interpolation, output in [-1,1]
+pub fn value_noise_2d(x: f32, y: f32, seed: u32) -> f32 {
+    let x0 = x.floor() as i32; let y0 = y.floor() as i32;
+    let tx = x - x0 as f32;    let ty = y - y0 as f32;
+    let n00 = hash01_lattice(x0,     y0,     seed);
+    let n10 = hash01_lattice(x0 + 1, y0,     seed);
+    let n01 = hash01_lattice(x0,     y0 + 1, seed);
+    let n11 = hash01_lattice(x0 + 1, y0 + 1, seed);
+    let ux = fade(tx); let uy = fade(ty);
+    let a = lerp(n00, n10, ux);
+    let b = lerp(n01, n11, ux);
+    let v = lerp(a, b, uy);
+    v * 2.0 - 1.0 // [-1,1]
+}
+
+// Fractal Brownian Motion, output in [-1,1]
+pub fn fbm_2d(x: f32, y: f32, seed: u32, octaves: u32, gain: f32, lacunarity: f32) -> f32 {
+    let mut amp = 1.0;
+    let mut freq = 1.0;
+    let mut sum = 0.0;
+    let mut norm = 0.0;
+    for o in 0..octaves {
+        let v = value_noise_2d(x * freq, y * freq, seed.wrapping_add(o));
+        sum += v * amp;
+        norm += amp;
+        amp *= gain;
+        freq *= lacunarity;
+    }
+    if norm > 0.0 { sum / norm } else { 0.0 }
+}
+
+// Ridged multifractal: sharp crests where the fBm crosses zero, output in [-1,1]
+pub fn ridged_2d(x: f32, y: f32, seed: u32, octaves: u32, gain: f32, lacunarity: f32) -> f32 {
+    let mut amp = 1.0;
+    let mut freq = 1.0;
+    let mut sum = 0.0;
+    let mut norm = 0.0;
+    for o in 0..octaves {
+        let v = 1.0 - value_noise_2d(x * freq, y * freq, seed.wrapping_add(o)).abs();
+        sum += v * v * amp;
+        norm += amp;
+        amp *= gain;
+        freq *= lacunarity;
+    }
+    if norm > 0.0 { (sum / norm) * 2.0 - 1.0 } else { 0.0 }
+}
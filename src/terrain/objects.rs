@@ -1,10 +1,9 @@
-use glam::IVec2;
+use std::fmt;
+use glam::{IVec2, Vec2};
 
 use super::grid::Grid;
-use super::template::{MapTemplate, Region};
-use super::landscape::{
-    TERRAIN_GRASSLAND, TERRAIN_FOREST, TERRAIN_WATER, TERRAIN_MOUNTAIN,
-};
+use super::template::{MapTemplate, LeyAffinity};
+use super::classes::TerrainClasses;
 
 // ---------------- tiny deterministic RNG ----------------
 struct Rng64(u64);
@@ -18,9 +17,15 @@ impl Rng64 {
         z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
         (z ^ (z >> 31)) as u32
     }
+    fn f01(&mut self) -> f32 { (self.next_u32() as f32) / (u32::MAX as f32) }
     fn range_usize(&mut self, hi_excl: usize) -> usize {
         if hi_excl == 0 { 0 } else { (self.next_u32() as usize) % hi_excl }
     }
+    fn shuffle<T>(&mut self, v: &mut [T]) {
+        for i in (1..v.len()).rev() {
+            v.swap(i, self.range_usize(i + 1));
+        }
+    }
 }
 
 #[inline] fn idx(w: i32, x: i32, y: i32) -> usize { (y * w + x) as usize }
@@ -50,17 +55,114 @@ pub struct PlacedObject {
     pub kind: u16,
 }
 
+/// A type/region rule that ran out of room before reaching its target.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shortfall {
+    /// Object type name.
+    pub object: String,
+    pub region: String,
+    pub placed: usize,
+    pub target: usize,
+}
+
+impl fmt::Display for Shortfall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}` in `{}`: room for {} of {} at this spacing", self.object, self.region, self.placed, self.target)
+    }
+}
+
+/// What `generate_objects` placed, and which rules came up short.
+pub struct ObjectPlacement {
+    pub objects: Vec<PlacedObject>,
+    pub shortfalls: Vec<Shortfall>,
+}
+
+// Candidates tried around each active point before it retires (Bridson's k).
+const BRIDSON_TRIES: usize = 30;
+// A maximal Poisson-disk set with spacing r holds about 0.7 points per r² of
+// area; aim a little denser than the target and trim the surplus.
+const SATURATION: f32 = 0.6;
+
+// ---------------- placed objects, bucketed for neighbour checks ----------------
+pub(crate) struct Placed {
+    pub(crate) objs: Vec<PlacedObject>,
+    cell: i32,
+    bw: i32,
+    bh: i32,
+    buckets: Vec<Vec<usize>>,
+}
+
+impl Placed {
+    /// Empty `w`×`h` map, bucketed by the widest distance in `spacing`.
+    pub(crate) fn new(w: i32, h: i32, spacing: &[Vec<f32>]) -> Self {
+        let cell = spacing.iter().flatten().copied().fold(1.0, f32::max).ceil() as i32;
+        let (bw, bh) = ((w + cell - 1) / cell, (h + cell - 1) / cell);
+        Self { objs: Vec::new(), cell, bw, bh, buckets: vec![Vec::new(); (bw * bh) as usize] }
+    }
+
+    fn bucket(&self, p: IVec2) -> usize { ((p.y / self.cell) * self.bw + p.x / self.cell) as usize }
+
+    pub(crate) fn push(&mut self, o: PlacedObject) {
+        let b = self.bucket(o.pos);
+        self.buckets[b].push(self.objs.len());
+        self.objs.push(o);
+    }
+
+    /// Keep only `keep[k]` of the objects from `start` on.
+    fn retain_from(&mut self, start: usize, keep: &[bool]) {
+        let tail = self.objs.split_off(start);
+        for b in &mut self.buckets { b.retain(|&i| i < start); }
+        for (o, _) in tail.into_iter().zip(keep).filter(|(_, &k)| k) {
+            self.push(o);
+        }
+    }
+
+    /// Whether a `kind` object fits on `p`: the tile is free, nothing is
+    /// closer than `spacing` allows, and nothing placed from `since` on is
+    /// closer than `self_r`.
+    fn fits(&self, p: IVec2, kind: usize, spacing: &[Vec<f32>], since: usize, self_r: f32) -> bool {
+        let reach = spacing[kind].iter().copied().fold(self_r, f32::max);
+        let rings = (reach / self.cell as f32).ceil() as i32;
+        let (bx, by) = (p.x / self.cell, p.y / self.cell);
+        for yy in (by - rings).max(0)..=(by + rings).min(self.bh - 1) {
+            for xx in (bx - rings).max(0)..=(bx + rings).min(self.bw - 1) {
+                for &i in &self.buckets[(yy * self.bw + xx) as usize] {
+                    let o = &self.objs[i];
+                    if o.pos == p { return false; }
+                    let need = if i >= since { self_r } else { spacing[kind][o.kind as usize] };
+                    if ((o.pos - p).length_squared() as f32) < need * need { return false; }
+                }
+            }
+        }
+        true
+    }
+
+    /// Whether a `kind` object on `p` keeps the pairwise spacing to everything placed.
+    pub(crate) fn clear_of(&self, p: IVec2, kind: usize, spacing: &[Vec<f32>]) -> bool {
+        self.fits(p, kind, spacing, usize::MAX, 0.0)
+    }
+}
+
 /// Multi-type placement with:
 /// - Per-region densities
-/// - Global cross-type spacing: min distance = max(radius_a, radius_b)
+/// - Min distance per pair of types (`ObjectPlacementRules::spacing_matrix`)
 /// - Exclusion rings around bases/shrines (no placement inside)
+/// - Per-type ley affinity (`ley_near` marks tiles close to a ley line)
+///
+/// Each type/region rule is a Bridson Poisson-disk sample over the region's
+/// tiles, restarted from every tile it hasn't reached so separate patches
+/// fill too, then trimmed at random to the density's target. Types go in
+/// order of decreasing self-spacing. Same seed, same objects. A rule whose
+/// region can't hold its target at the required spacing places what fits and
+/// is listed in `shortfalls`.
 pub fn generate_objects(
     tpl: &MapTemplate,
     classes: &Grid<u8>,          // from Phase 3/4
     base_centers: &[IVec2],
     shrines: &[IVec2],
-    extra_seed: u32,
-) -> Vec<PlacedObject> {
+    ley_near: Option<&[u8]>,
+    seed: u64,
+) -> ObjectPlacement {
     let w = classes.w;
     let h = classes.h;
     let total = (w * h) as usize;
@@ -81,122 +183,178 @@ pub fn generate_objects(
         paint_exclusion_disk(&mut allowed, w, h, s, sr);
     }
 
-    // --------------- precompute region tile lists (only ALLOWED tiles) ----------
-    let mut tiles_grass = Vec::<usize>::new();
-    let mut tiles_forest = Vec::<usize>::new();
-    let mut tiles_water = Vec::<usize>::new();
-    let mut tiles_mountain = Vec::<usize>::new();
+    // --------------- precompute per-class tile lists (only ALLOWED tiles) -------
+    let reg = TerrainClasses::from_template(tpl);
+    let mut class_tiles: Vec<Vec<usize>> = vec![Vec::new(); reg.len()];
 
     for y in 0..h {
         for x in 0..w {
             let i = idx(w,x,y);
             if allowed[i] == 0 { continue; }
-            match *classes.get(x, y) {
-                TERRAIN_GRASSLAND => tiles_grass.push(i),
-                TERRAIN_FOREST    => tiles_forest.push(i),
-                TERRAIN_WATER     => tiles_water.push(i),
-                TERRAIN_MOUNTAIN  => tiles_mountain.push(i),
-                _ => {}
+            if let Some(list) = class_tiles.get_mut(*classes.get(x, y) as usize) {
+                list.push(i);
             }
         }
     }
 
-    let region_tiles = |r: Region| -> &Vec<usize> {
-        match r {
-            Region::Grassland => &tiles_grass,
-            Region::Forest    => &tiles_forest,
-            Region::Water     => &tiles_water,
-            Region::Mountain  => &tiles_mountain,
-        }
-    };
-
-    // --------------- spatial buckets for O(1) neighbor checks -------------------
-    let mut max_r = 1i32;
-    for t in &tpl.objects.types {
-        if t.radius > max_r { max_r = t.radius; }
-    }
-    let cell = max_r.max(1);
-    let bw = ((w + cell - 1) / cell) as i32;
-    let bh = ((h + cell - 1) / cell) as i32;
-    let nbuckets = (bw * bh) as usize;
-    let mut buckets: Vec<Vec<usize>> = vec![Vec::new(); nbuckets];
-
-    struct PO { pos: IVec2, kind: u16, r: i32 }
-    let mut placed: Vec<PO> = Vec::new();
-
-    let mut rng = Rng64::new(((tpl.objects.base_seed as u64) << 32) ^ (extra_seed as u64));
-
-    let bxy = |p: IVec2| -> (i32, i32) { (p.x / cell, p.y / cell) };
-    let bidx = |bx: i32, by: i32| -> usize { (by * bw + bx) as usize };
-
-    let can_place = |p: IVec2, r_new: i32,
-                     placed: &Vec<PO>, buckets: &Vec<Vec<usize>>| -> bool {
-        let (bx, by) = bxy(p);
-        let bx0 = (bx - 1).max(0);
-        let by0 = (by - 1).max(0);
-        let bx1 = (bx + 1).min(bw - 1);
-        let by1 = (by + 1).min(bh - 1);
-        for yy in by0..=by1 {
-            for xx in bx0..=bx1 {
-                let bi = bidx(xx, yy);
-                for &pi in &buckets[bi] {
-                    let q = placed[pi].pos;
-                    let r_other = placed[pi].r;
-                    let dmin = r_new.max(r_other);
-                    let dx = p.x - q.x;
-                    let dy = p.y - q.y;
-                    if dx*dx + dy*dy < dmin * dmin {
-                        return false;
+    let spacing = tpl.objects.spacing_matrix();
+    let mut placed = Placed::new(w, h, &spacing);
+    let mut rng = Rng64::new(seed);
+    let mut eligible = vec![false; total];
+    let mut shortfalls = Vec::new();
+
+    // --------------- place by descending self-spacing ---------------------------
+    let mut type_order: Vec<usize> = (0..tpl.objects.types.len()).collect();
+    type_order.sort_by(|&a, &b| spacing[b][b].total_cmp(&spacing[a][a]));
+
+    for ti in type_order {
+        let tr = &tpl.objects.types[ti];
+        for rr in &tr.per_region {
+            let k = reg.region(&rr.region);
+            debug_assert!(k.is_some(), "objects: `{}` uses unknown region `{}`", tr.name, rr.region.0);
+            let Some(k) = k else { continue; };
+            let tiles = &class_tiles[k as usize];
+            let target = (rr.density.per_unit2() * tiles.len() as f32).round() as usize;
+            if target == 0 { continue; }
+
+            // Ley affinity decides once per tile whether it may hold this type;
+            // an attracted type keeps each off-ley tile with probability 1/k.
+            let mut seeds: Vec<usize> = tiles.iter().copied().filter(|&i| {
+                let on_ley = ley_near.is_some_and(|m| m[i] != 0);
+                match tr.ley {
+                    LeyAffinity::Avoid => !on_ley,
+                    LeyAffinity::Attract(k) if ley_near.is_some() && !on_ley => rng.f01() * k.max(1.0) <= 1.0,
+                    _ => true,
+                }
+            }).collect();
+            if seeds.is_empty() { continue; }
+            for &i in &seeds { eligible[i] = true; }
+
+            // Spread out enough that a full sample lands near the target.
+            let r = spacing[ti][ti].max((SATURATION * seeds.len() as f32 / target as f32).sqrt());
+            let start = placed.objs.len();
+            rng.shuffle(&mut seeds);
+            let mut active: Vec<IVec2> = Vec::new();
+            for &s in &seeds {
+                let p = IVec2::new(s as i32 % w, s as i32 / w);
+                if !placed.fits(p, ti, &spacing, start, r) { continue; }
+                placed.push(PlacedObject { pos: p, kind: ti as u16 });
+                active.push(p);
+
+                // Grow from the seed: try points in the ring r..2r around a
+                // random active point; retire the point once every try fails.
+                let ring = r.max(1.0);
+                while !active.is_empty() {
+                    let j = rng.range_usize(active.len());
+                    let centre = active[j].as_vec2() + 0.5;
+                    let mut grown = false;
+                    for _ in 0..BRIDSON_TRIES {
+                        let d = Vec2::new(rng.f01() * 4.0 - 2.0, rng.f01() * 4.0 - 2.0) * ring;
+                        let len2 = d.length_squared();
+                        if len2 < ring * ring || len2 > 4.0 * ring * ring { continue; }
+                        let q = (centre + d).floor().as_ivec2();
+                        if q.x < 0 || q.y < 0 || q.x >= w || q.y >= h || !eligible[idx(w, q.x, q.y)] { continue; }
+                        if !placed.fits(q, ti, &spacing, start, r) { continue; }
+                        placed.push(PlacedObject { pos: q, kind: ti as u16 });
+                        active.push(q);
+                        grown = true;
+                        break;
                     }
+                    if !grown { active.swap_remove(j); }
                 }
             }
+            for &i in &seeds { eligible[i] = false; }
+
+            let n = placed.objs.len() - start;
+            if n > target {
+                let mut order: Vec<usize> = (0..n).collect();
+                rng.shuffle(&mut order);
+                let mut keep = vec![false; n];
+                for &k in &order[..target] { keep[k] = true; }
+                placed.retain_from(start, &keep);
+            } else if n < target {
+                shortfalls.push(Shortfall { object: tr.name.clone(), region: rr.region.0.clone(), placed: n, target });
+            }
         }
-        true
-    };
+    }
 
-    // --------------- place by descending radius ---------------------------------
-    let mut type_order: Vec<usize> = (0..tpl.objects.types.len()).collect();
-    type_order.sort_by_key(|&i| -(tpl.objects.types[i].radius));
+    ObjectPlacement { objects: placed.objs, shortfalls }
+}
 
-    for ti in type_order {
-        let tr = &tpl.objects.types[ti];
-        if tr.per_region.is_empty() { continue; }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::template::{Density, ObjectRegionRule, ObjectTypeRule, Region};
 
-        for rr in &tr.per_region {
-            let tiles = region_tiles(rr.region);
-            if tiles.is_empty() { continue; }
-
-            let area_tiles = tiles.len() as f32; // allowed tiles only
-            let per_unit2 = if rr.density.area > 0.0 { rr.density.per_unit2() } else { 0.0 };
-            if per_unit2 <= 0.0 { continue; }
-
-            let mut target = (per_unit2 * area_tiles).round() as i32;
-            if target <= 0 { continue; }
-
-            let mut attempts = 0i32;
-            let max_attempts = target * 50; // tune as needed
-
-            while target > 0 && attempts < max_attempts {
-                attempts += 1;
-                let pick = tiles[rng.range_usize(tiles.len())];
-                let y = (pick as i32) / w;
-                let x = (pick as i32) % w;
-                let p = IVec2::new(x, y);
-
-                // Still double-check allowed (cheap)
-                if allowed[pick] == 0 { continue; }
-
-                if can_place(p, tr.radius, &placed, &buckets) {
-                    let (bx, by) = bxy(p);
-                    let bi = bidx(bx, by);
-                    let idx_new = placed.len();
-                    placed.push(PO { pos: p, kind: ti as u16, r: tr.radius });
-                    buckets[bi].push(idx_new);
-                    target -= 1;
+    fn preset() -> MapTemplate {
+        MapTemplate::load("assets/maps/haunted_woods.ron").expect("preset loads").template
+    }
+
+    // Forest on the left half, grassland on the right, one small lake.
+    fn classes(tpl: &MapTemplate) -> Grid<u8> {
+        let reg = TerrainClasses::from_template(tpl);
+        let id = |n| reg.id(n).unwrap();
+        let mut g = Grid::new(96, 96);
+        for y in 0..96 {
+            for x in 0..96 {
+                let lake = (40..46).contains(&x) && (40..46).contains(&y);
+                g.set(x, y, if lake { id("Water") } else if x < 48 { id("Forest") } else { id("Grassland") });
+            }
+        }
+        g
+    }
+
+    fn place(tpl: &MapTemplate, seed: u64) -> ObjectPlacement {
+        let bases = [IVec2::new(20, 20), IVec2::new(75, 75)];
+        generate_objects(tpl, &classes(tpl), &bases, &[], None, seed)
+    }
+
+    #[test]
+    fn same_seed_same_objects() {
+        let tpl = preset();
+        let key = |p: &ObjectPlacement| p.objects.iter().map(|o| (o.pos, o.kind)).collect::<Vec<_>>();
+        let (a, b) = (place(&tpl, 7), place(&tpl, 7));
+        assert!(!a.objects.is_empty());
+        assert_eq!(key(&a), key(&b));
+        assert_ne!(key(&a), key(&place(&tpl, 8)));
+    }
+
+    #[test]
+    fn no_pair_closer_than_its_spacing() {
+        let tpl = preset();
+        let spacing = tpl.objects.spacing_matrix();
+        for seed in 0..4 {
+            let objs = place(&tpl, seed).objects;
+            for (i, a) in objs.iter().enumerate() {
+                for b in &objs[i + 1..] {
+                    let need = spacing[a.kind as usize][b.kind as usize];
+                    let d = (a.pos - b.pos).as_vec2().length();
+                    assert!(d > 0.0 && d >= need, "seed {seed}: {a:?} and {b:?} are {d} apart, need {need}");
                 }
             }
         }
     }
 
-    placed.into_iter().map(|po| PlacedObject { pos: po.pos, kind: po.kind }).collect()
+    #[test]
+    fn a_region_too_small_places_what_fits_and_says_so() {
+        let mut tpl = preset();
+        let rule = |region: &str, count| ObjectRegionRule {
+            region: Region(region.into()),
+            density: Density { count, area: 1.0 },
+        };
+        tpl.objects.spacing.clear();
+        tpl.objects.types = vec![ObjectTypeRule {
+            name: "Reed".into(),
+            radius: 3,
+            per_region: vec![rule("Water", 1.0), rule("Grassland", 0.01)],
+            ley: Default::default(),
+        }];
+        let p = place(&tpl, 3);
+        let in_lake = p.objects.iter().filter(|o| (40..46).contains(&o.pos.x) && (40..46).contains(&o.pos.y)).count();
+        assert_eq!(p.shortfalls.len(), 1, "{:?}", p.shortfalls);
+        let s = &p.shortfalls[0];
+        assert_eq!((s.object.as_str(), s.region.as_str(), s.target), ("Reed", "Water", 36));
+        assert!(s.placed >= 1 && s.placed < 36 && s.placed == in_lake, "{s}");
+        assert_eq!(s.to_string(), format!("`Reed` in `Water`: room for {in_lake} of 36 at this spacing"));
+    }
 }
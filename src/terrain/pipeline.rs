@@ -0,0 +1,415 @@
+//! Generation as a list of phases over one shared context. The template's
+//! `pipeline` picks and orders the standard phases (see `generate`); code can
+//! swap any of them for its own `GenerationPhase`.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use glam::IVec2;
+use super::grid::Grid;
+use super::template::MapTemplate;
+use super::classes::TerrainClasses;
+use super::spawns::BaseLocations;
+use super::ley::{LeyCorridor, LeyGraph, LeyNetwork};
+use super::rivers::River;
+use super::connectivity::ConnectivityReport;
+use super::objects::{PlacedObject, Shortfall};
+use super::fairness::FairnessReport;
+use super::symmetry::Symmetry;
+use super::seed::MapSeed;
+
+/// Inputs that don't live in the template.
+#[derive(Clone)]
+pub struct GenParams {
+    pub num_bases: usize,
+    pub start_angle_deg: f32,
+    pub seed: MapSeed,
+    /// Debug output directory (None = skip every `debug` hook).
+    pub out_dir: Option<PathBuf>,
+}
+
+/// Everything phases hand to each other. Outputs are `None` until the phase
+/// that makes them has run; read them through the accessors so a misordered
+/// pipeline reports what's missing instead of panicking.
+pub struct GenContext<'a> {
+    pub tpl: &'a MapTemplate,
+    pub reg: TerrainClasses,
+    pub params: GenParams,
+    pub sym: Option<Symmetry>,
+    /// Earlier runs of the current phase (0 the first time); mixed into its seed.
+    pub repeat: usize,
+
+    pub bases: Option<BaseLocations>,
+    /// Starts as the flat base-disk map from Bases; Elevation replaces it.
+    pub height: Option<Grid<f32>>,
+    pub ley: Option<LeyNetwork>,
+    /// Ley polylines as segments (empty without ley).
+    pub ley_lines: Vec<(IVec2, IVec2)>,
+    pub corridor: Option<LeyCorridor>,
+    /// Tiles within `object_reach` of a ley line.
+    pub ley_near: Option<Vec<u8>>,
+    pub classes: Option<Grid<u8>>,
+    /// Biomes-mode moisture field (debug output only).
+    pub moisture: Option<Grid<f32>>,
+    pub rivers: Vec<River>,
+    pub connectivity: Option<ConnectivityReport>,
+    pub objects: Vec<PlacedObject>,
+    pub object_shortfalls: Vec<Shortfall>,
+    pub fairness: Option<FairnessReport>,
+}
+
+impl<'a> GenContext<'a> {
+    pub fn new(tpl: &'a MapTemplate, params: GenParams) -> Self {
+        Self {
+            tpl,
+            reg: TerrainClasses::from_template(tpl),
+            sym: Symmetry::new(tpl, params.num_bases, params.start_angle_deg),
+            params,
+            repeat: 0,
+            bases: None,
+            height: None,
+            ley: None,
+            ley_lines: Vec::new(),
+            corridor: None,
+            ley_near: None,
+            classes: None,
+            moisture: None,
+            rivers: Vec::new(),
+            connectivity: None,
+            objects: Vec::new(),
+            object_shortfalls: Vec::new(),
+            fairness: None,
+        }
+    }
+
+    /// Seed for the running phase: the map seed's `label` stream, salted with
+    /// the template's per-phase seed and the repeat count.
+    pub fn phase_seed(&self, label: &str, salt: u32) -> u32 {
+        self.params.seed.phase(label, salt.wrapping_add(self.repeat as u32))
+    }
+
+    pub fn bases(&self) -> Result<&BaseLocations, PhaseError> {
+        self.bases.as_ref().ok_or(PhaseError::Missing("bases"))
+    }
+
+    pub fn base_centers(&self) -> Result<&[IVec2], PhaseError> {
+        Ok(&self.bases()?.base_centers)
+    }
+
+    /// Shrine positions; empty when the pipeline has no Ley phase.
+    pub fn shrines(&self) -> &[IVec2] {
+        self.ley.as_ref().map_or(&[], |l| &l.shrines)
+    }
+
+    pub fn height(&self) -> Result<&Grid<f32>, PhaseError> {
+        self.height.as_ref().ok_or(PhaseError::Missing("a heightmap"))
+    }
+
+    pub fn classes(&self) -> Result<&Grid<u8>, PhaseError> {
+        self.classes.as_ref().ok_or(PhaseError::Missing("terrain classes"))
+    }
+
+    /// Copy the reference wedge over the rest of the map when symmetry is on.
+    pub fn fold<T: Clone + Default>(&self, g: Grid<T>) -> Grid<T> {
+        match &self.sym {
+            Some(s) => s.fold_grid(&g),
+            None => g,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PhaseError {
+    /// An input no earlier phase produced (e.g. Blend before Terrain).
+    Missing(&'static str),
+}
+
+impl fmt::Display for PhaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PhaseError::Missing(what) => write!(f, "needs {what}, which no earlier phase produced"),
+        }
+    }
+}
+
+impl std::error::Error for PhaseError {}
+
+#[derive(Debug)]
+pub enum PipelineError {
+    /// Step `step` (0-based) failed.
+    Phase { step: usize, name: String, error: PhaseError },
+    /// Every phase ran but the map is missing a required output.
+    Incomplete(PhaseError),
+}
+
+impl fmt::Display for PipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipelineError::Phase { step, name, error } => write!(f, "pipeline step {step} ({name}): {error}"),
+            PipelineError::Incomplete(error) => write!(f, "pipeline output {error}"),
+        }
+    }
+}
+
+impl std::error::Error for PipelineError {}
+
+/// Where a phase writes its debug files.
+pub struct DebugOut<'a> {
+    dir: &'a Path,
+    repeat: usize,
+}
+
+impl DebugOut<'_> {
+    /// `name` in the output directory; repeated phases get `_2`, `_3`, ...
+    /// before the extension so they don't overwrite each other.
+    pub fn path(&self, name: &str) -> String {
+        let name = match (self.repeat, name.rsplit_once('.')) {
+            (0, _) => name.to_string(),
+            (n, Some((stem, ext))) => format!("{stem}_{}.{ext}", n + 1),
+            (n, None) => format!("{name}_{}", n + 1),
+        };
+        self.dir.join(name).to_string_lossy().into_owned()
+    }
+}
+
+/// One step of map generation.
+pub trait GenerationPhase {
+    /// Short name; used for repeat counting, errors and `Pipeline::replace`.
+    fn name(&self) -> &str;
+
+    fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError>;
+
+    /// Write debug files after `run`; only called when an output dir is set.
+    fn debug(&self, _ctx: &GenContext, _out: &DebugOut) {}
+}
+
+/// What a pipeline hook is told after each phase.
+pub struct PhaseInfo<'a> {
+    pub step: usize,
+    pub name: &'a str,
+    pub repeat: usize,
+    pub elapsed: Duration,
+}
+
+pub type PhaseHook = dyn FnMut(&PhaseInfo, &GenContext);
+
+/// Everything the pipeline produced.
+pub struct GeneratedMap {
+    pub bases: BaseLocations,
+    /// Empty network when the pipeline has no Ley phase.
+    pub ley: LeyNetwork,
+    /// Final heightmap (the flat base map if Elevation didn't run).
+    pub height: Grid<f32>,
+    pub classes: Grid<u8>,
+    /// River polylines (source → mouth), already carved into `classes`.
+    pub rivers: Vec<River>,
+    /// Overland reachability of bases/shrines (+ carved corridors), if checked.
+    pub connectivity: Option<ConnectivityReport>,
+    pub objects: Vec<PlacedObject>,
+    /// Object rules that placed fewer than their target (the region was too small).
+    pub object_shortfalls: Vec<Shortfall>,
+    /// Per-base fairness measurements over the final map, if measured.
+    pub fairness: Option<FairnessReport>,
+    /// The seed every phase above was derived from.
+    pub seed: MapSeed,
+}
+
+#[derive(Default)]
+pub struct Pipeline {
+    phases: Vec<Box<dyn GenerationPhase>>,
+    hooks: Vec<Box<PhaseHook>>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self { Self::default() }
+
+    /// Append a phase.
+    pub fn then(mut self, phase: impl GenerationPhase + 'static) -> Self {
+        self.phases.push(Box::new(phase));
+        self
+    }
+
+    /// Swap every phase called `name` for `phase`.
+    pub fn replace<P>(mut self, name: &str, phase: P) -> Self
+    where
+        P: GenerationPhase + Clone + 'static,
+    {
+        for slot in self.phases.iter_mut().filter(|p| p.name() == name) {
+            *slot = Box::new(phase.clone());
+        }
+        self
+    }
+
+    /// Call `hook` after every phase (timings, progress, custom debug output).
+    pub fn with_hook(mut self, hook: impl FnMut(&PhaseInfo, &GenContext) + 'static) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Run every phase in order over a fresh context.
+    pub fn generate(&mut self, tpl: &MapTemplate, params: GenParams) -> Result<GeneratedMap, PipelineError> {
+        let out_dir = params.out_dir.clone();
+        if let Some(dir) = &out_dir {
+            let _ = std::fs::create_dir_all(dir);
+            let _ = std::fs::write(dir.join("seed.txt"), format!("{}\n", params.seed));
+        }
+
+        let mut ctx = GenContext::new(tpl, params);
+        let mut runs: HashMap<String, usize> = HashMap::new();
+        for (step, phase) in self.phases.iter().enumerate() {
+            let name = phase.name().to_string();
+            let count = runs.entry(name.clone()).or_default();
+            ctx.repeat = *count;
+            *count += 1;
+
+            let start = Instant::now();
+            phase.run(&mut ctx).map_err(|error| PipelineError::Phase { step, name: name.clone(), error })?;
+            let elapsed = start.elapsed();
+
+            if let Some(dir) = &out_dir {
+                phase.debug(&ctx, &DebugOut { dir, repeat: ctx.repeat });
+            }
+            let info = PhaseInfo { step, name: &name, repeat: ctx.repeat, elapsed };
+            for hook in &mut self.hooks { hook(&info, &ctx); }
+        }
+
+        let missing = |what| PipelineError::Incomplete(PhaseError::Missing(what));
+        let bases = ctx.bases.ok_or(missing("bases"))?;
+        let classes = ctx.classes.ok_or(missing("terrain classes"))?;
+        let height = ctx.height.ok_or(missing("a heightmap"))?;
+        Ok(GeneratedMap {
+            bases,
+            ley: ctx.ley.unwrap_or(LeyNetwork { shrines: Vec::new(), graph: LeyGraph::default() }),
+            height,
+            classes,
+            rivers: ctx.rivers,
+            connectivity: ctx.connectivity,
+            objects: ctx.objects,
+            object_shortfalls: ctx.object_shortfalls,
+            fairness: ctx.fairness,
+            seed: ctx.params.seed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::terrain::generate::{BasesPhase, BlendPhase, ElevationPhase, LeyPhase, TerrainPhase};
+
+    fn preset() -> MapTemplate {
+        MapTemplate::load("assets/maps/haunted_woods.ron").expect("preset loads").template
+    }
+
+    fn params(out_dir: Option<PathBuf>) -> GenParams {
+        GenParams { num_bases: 4, start_angle_deg: 0.0, seed: MapSeed::from(7), out_dir }
+    }
+
+    /// `(tag, repeat, phase seed)` per probe run.
+    type Log = Rc<RefCell<Vec<(&'static str, usize, u32)>>>;
+
+    /// Records `(tag, repeat, phase seed)` for every run and writes a debug file.
+    #[derive(Clone)]
+    struct Probe {
+        name: &'static str,
+        tag: &'static str,
+        log: Log,
+    }
+
+    impl GenerationPhase for Probe {
+        fn name(&self) -> &str { self.name }
+
+        fn run(&self, ctx: &mut GenContext) -> Result<(), PhaseError> {
+            self.log.borrow_mut().push((self.tag, ctx.repeat, ctx.phase_seed(self.name, 0)));
+            Ok(())
+        }
+
+        fn debug(&self, _ctx: &GenContext, out: &DebugOut) {
+            std::fs::write(out.path("probe.txt"), self.tag).expect("debug file writes");
+        }
+    }
+
+    fn probe(name: &'static str, tag: &'static str, log: &Log) -> Probe {
+        Probe { name, tag, log: log.clone() }
+    }
+
+    #[test]
+    fn reordered_pipeline_runs_in_the_given_order() {
+        let tpl = preset();
+        let names = Rc::new(RefCell::new(Vec::new()));
+        let seen = names.clone();
+        let map = Pipeline::new()
+            .then(BasesPhase)
+            .then(ElevationPhase)
+            .then(LeyPhase)
+            .then(TerrainPhase)
+            .with_hook(move |info, _| seen.borrow_mut().push((info.step, info.name.to_string())))
+            .generate(&tpl, params(None))
+            .expect("reordered pipeline generates");
+        let expected = ["bases", "elevation", "ley", "terrain"];
+        assert_eq!(*names.borrow(), expected.iter().enumerate().map(|(i, n)| (i, n.to_string())).collect::<Vec<_>>());
+        assert!(!map.ley.shrines.is_empty());
+    }
+
+    #[test]
+    fn skipped_phase_leaves_the_map_incomplete() {
+        let tpl = preset();
+        match Pipeline::new().then(BasesPhase).generate(&tpl, params(None)) {
+            Err(PipelineError::Incomplete(PhaseError::Missing(what))) => assert_eq!(what, "terrain classes"),
+            other => panic!("expected Incomplete, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn misordered_phase_names_its_step() {
+        let tpl = preset();
+        match Pipeline::new().then(BasesPhase).then(BlendPhase).then(TerrainPhase).generate(&tpl, params(None)) {
+            Err(PipelineError::Phase { step, name, error: PhaseError::Missing(what) }) => {
+                assert_eq!((step, name.as_str(), what), (1, "blend", "terrain classes"));
+            }
+            other => panic!("expected a Phase error, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn repeated_phase_gets_a_new_seed_and_debug_name() {
+        let tpl = preset();
+        let dir = std::env::temp_dir().join(format!("pipeline_repeat_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let log = Log::default();
+        Pipeline::new()
+            .then(BasesPhase)
+            .then(probe("probe", "a", &log))
+            .then(TerrainPhase)
+            .then(probe("probe", "b", &log))
+            .generate(&tpl, params(Some(dir.clone())))
+            .expect("pipeline generates");
+
+        let log = log.borrow();
+        assert_eq!(log.iter().map(|&(t, r, _)| (t, r)).collect::<Vec<_>>(), [("a", 0), ("b", 1)]);
+        assert_ne!(log[0].2, log[1].2, "a repeat must not reuse the phase seed");
+        assert_eq!(std::fs::read_to_string(dir.join("probe.txt")).unwrap(), "a");
+        assert_eq!(std::fs::read_to_string(dir.join("probe_2.txt")).unwrap(), "b");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn replace_swaps_every_phase_with_that_name() {
+        let tpl = preset();
+        let log = Log::default();
+        Pipeline::new()
+            .then(BasesPhase)
+            .then(probe("probe", "old", &log))
+            .then(TerrainPhase)
+            .then(probe("probe", "old", &log))
+            .then(probe("other", "kept", &log))
+            .replace("probe", probe("probe", "new", &log))
+            .generate(&tpl, params(None))
+            .expect("pipeline generates");
+        let tags: Vec<_> = log.borrow().iter().map(|&(t, _, _)| t).collect();
+        assert_eq!(tags, ["new", "new", "kept"]);
+    }
+}
@@ -0,0 +1,208 @@
+use glam::IVec2;
+use super::grid::Grid;
+use super::template::MapTemplate;
+use super::blend::build_locked_mask;
+use super::template::ClassRole;
+use super::classes::TerrainClasses;
+use super::noise::{fbm_2d, hash01_lattice};
+
+#[derive(Clone, Copy)]
+pub struct RiverSettings {
+    pub count: usize,           // how many rivers to try to place
+    pub width: i32,             // carve brush radius (0 = 1-tile stream)
+    pub meander: f32,           // noise weight vs. pure downhill (0..1, relative to height span)
+    pub meander_freq: f32,      // meander noise frequency in cycles per tile
+    pub min_length: usize,      // drop traces shorter than this
+    pub source_spacing: i32,    // min distance between two river sources
+    pub seed: u32,
+}
+
+impl Default for RiverSettings {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            width: 1,
+            meander: 0.35,
+            meander_freq: 1.0 / 12.0,
+            min_length: 24,
+            source_spacing: 40,
+            seed: 11,
+        }
+    }
+}
+
+/// One carved river as a polyline of tile coordinates, source first.
+#[derive(Clone, Debug)]
+pub struct River {
+    pub points: Vec<IVec2>,
+}
+
+const NEIGHBORS8: [(i32, i32); 8] = [
+    (-1,-1), (0,-1), (1,-1),
+    (-1, 0),         (1, 0),
+    (-1, 1), (0, 1), (1, 1),
+];
+
+#[inline] fn idx(w: i32, x: i32, y: i32) -> usize { (y * w + x) as usize }
+
+fn carve_disk(classes: &mut Grid<u8>, locked: &[u8], c: IVec2, r: i32, water: u8) {
+    let (w, h) = (classes.w, classes.h);
+    let r = r.max(0);
+    for y in (c.y - r).max(0)..=(c.y + r).min(h - 1) {
+        for x in (c.x - r).max(0)..=(c.x + r).min(w - 1) {
+            let (dx, dy) = (x - c.x, y - c.y);
+            if dx*dx + dy*dy <= r*r && locked[idx(w, x, y)] == 0 {
+                classes.set(x, y, water);
+            }
+        }
+    }
+}
+
+/// Phase 4C: trace rivers downhill from mountain tiles until they reach water
+/// (including earlier rivers) or the map edge, then carve them as water.
+/// Traces that get boxed in end in a small pond. Locked base/shrine disks are
+/// never crossed or carved. Needs classes with the Water and Mountain roles.
+pub fn carve_rivers(
+    tpl: &MapTemplate,
+    height: &Grid<f32>,
+    classes_in: &Grid<u8>,
+    base_centers: &[IVec2],
+    shrines: &[IVec2],
+    settings: RiverSettings,
+) -> (Grid<u8>, Vec<River>) {
+    let mut classes = classes_in.clone();
+    let mut rivers = Vec::new();
+    if settings.count == 0 { return (classes, rivers); }
+    let reg = TerrainClasses::from_template(tpl);
+    let (Some(water), Some(mountain)) = (reg.role(ClassRole::Water), reg.role(ClassRole::Mountain)) else {
+        return (classes, rivers);
+    };
+
+    let (w, h) = (classes.w, classes.h);
+    let locked = build_locked_mask(tpl, base_centers, shrines);
+
+    let mut minh = f32::MAX; let mut maxh = f32::MIN;
+    for y in 0..h { for x in 0..w {
+        let v = *height.get(x, y);
+        minh = minh.min(v); maxh = maxh.max(v);
+    }}
+    let span = (maxh - minh).max(1e-6);
+
+    // Candidate sources: highest unlocked mountain tiles first, lightly jittered
+    // so equal plateaus don't always resolve to the same corner.
+    let mut sources: Vec<(IVec2, f32)> = Vec::new();
+    for y in 0..h {
+        for x in 0..w {
+            if locked[idx(w, x, y)] != 0 || *classes.get(x, y) != mountain { continue; }
+            let jitter = hash01_lattice(x, y, settings.seed) * 0.05 * span;
+            sources.push((IVec2::new(x, y), *height.get(x, y) + jitter));
+        }
+    }
+    sources.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let meander_seed = settings.seed.wrapping_add(0x2545_F491);
+    let score = |p: IVec2| -> f32 {
+        let n = fbm_2d(
+            p.x as f32 * settings.meander_freq, p.y as f32 * settings.meander_freq,
+            meander_seed, 3, 0.5, 2.0,
+        );
+        *height.get(p.x, p.y) + settings.meander * span * n
+    };
+
+    let max_steps = ((w * h) / 8).max(64) as usize;
+    let spacing2 = settings.source_spacing.max(0).pow(2);
+    let mut used_sources: Vec<IVec2> = Vec::new();
+    // Per-trace visit stamp avoids clearing a full-size buffer for every attempt.
+    let mut seen = vec![0u32; (w * h) as usize];
+    let mut stamp = 0u32;
+
+    for &(src, _) in &sources {
+        if rivers.len() >= settings.count { break; }
+        if used_sources.iter().any(|&s| (s - src).length_squared() < spacing2) { continue; }
+        // Earlier rivers may have flooded this tile already.
+        if *classes.get(src.x, src.y) == water { continue; }
+
+        stamp += 1;
+        seen[idx(w, src.x, src.y)] = stamp;
+        let mut points = vec![src];
+        let mut cur = src;
+        let mut pond = false;
+
+        loop {
+            let on_edge = cur.x == 0 || cur.y == 0 || cur.x == w - 1 || cur.y == h - 1;
+            if on_edge { break; }
+            if points.len() > 1 && *classes.get(cur.x, cur.y) == water { break; }
+            if points.len() >= max_steps { pond = true; break; }
+
+            let mut best: Option<(IVec2, f32)> = None;
+            for (dx, dy) in NEIGHBORS8 {
+                let n = cur + IVec2::new(dx, dy);
+                if !classes.in_bounds(n) { continue; }
+                let i = idx(w, n.x, n.y);
+                if locked[i] != 0 || seen[i] == stamp { continue; }
+                let s = score(n);
+                if best.is_none_or(|(_, bs)| s < bs) { best = Some((n, s)); }
+            }
+            let Some((next, _)) = best else { pond = true; break; };
+            seen[idx(w, next.x, next.y)] = stamp;
+            points.push(next);
+            cur = next;
+        }
+
+        if points.len() < settings.min_length.max(2) { continue; }
+
+        for &p in &points {
+            carve_disk(&mut classes, &locked, p, settings.width, water);
+        }
+        if pond {
+            carve_disk(&mut classes, &locked, cur, settings.width + 2, water);
+        }
+        used_sources.push(src);
+        rivers.push(River { points });
+    }
+
+    (classes, rivers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rivers_run_downhill_around_locked_disks() {
+        let mut tpl = MapTemplate::load("assets/maps/haunted_woods.ron").expect("preset loads").template;
+        tpl.size = (64, 64);
+        let reg = TerrainClasses::from_template(&tpl);
+        let (ground, water) = (reg.ground(), reg.id("Water").unwrap());
+        let mountain = reg.id("Mountain").unwrap();
+
+        // Falls towards x = 0; peaks near the east edge. Equal heights break
+        // towards -y, so the river runs north-west, into the base's disk.
+        let (mut height, mut classes) = (Grid::new(64, 64), Grid::new(64, 64));
+        for y in 0..64 {
+            for x in 0..64 {
+                height.set(x, y, x as f32);
+                classes.set(x, y, if x == 62 && (28..=36).contains(&y) { mountain } else { ground });
+            }
+        }
+        let (bases, shrines) = ([IVec2::new(50, 10)], [IVec2::new(30, 2)]);
+        let settings = RiverSettings { count: 1, meander: 0.0, min_length: 4, ..RiverSettings::default() };
+        let (carved, rivers) = carve_rivers(&tpl, &height, &classes, &bases, &shrines, settings);
+
+        assert_eq!(rivers.len(), 1);
+        let points = &rivers[0].points;
+        let (source, mouth) = (points[0], *points.last().unwrap());
+        assert!(mouth.x == 0 || mouth.y == 0, "the river ends on the map edge, not at {mouth}");
+        assert!(height.get(mouth.x, mouth.y) < height.get(source.x, source.y));
+        for pair in points.windows(2) {
+            assert!(height.get(pair[1].x, pair[1].y) <= height.get(pair[0].x, pair[0].y), "uphill at {}", pair[1]);
+        }
+        let locked = build_locked_mask(&tpl, &bases, &shrines);
+        assert!(points.iter().all(|p| locked[idx(64, p.x, p.y)] == 0), "the course crosses a locked disk");
+        for y in 0..64 {
+            for x in 0..64 {
+                if locked[idx(64, x, y)] != 0 { assert_ne!(*carved.get(x, y), water, "carved locked tile ({x}, {y})"); }
+            }
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! One seed per map. Every phase draws its own stream from it by label, so
+//! adding or reordering phases never shifts another phase's randomness, and
+//! the whole map (with its template) reproduces from a short code.
+
+use std::fmt;
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct MapSeed(pub u64);
+
+// Crockford base32: no I, L, O or U, so codes survive being read aloud or retyped.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+fn splitmix64(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xCBF2_9CE4_8422_2325, |h, b| (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01B3))
+}
+
+impl MapSeed {
+    /// Independent 64-bit stream for `label` ("ley", "objects", ...).
+    pub fn stream(self, label: &str) -> u64 {
+        splitmix64(self.0 ^ splitmix64(fnv1a(label)))
+    }
+
+    /// `stream` mixed with the template's own per-phase seed, so a preset can
+    /// still reroll one phase without touching the map seed.
+    pub fn salted(self, label: &str, salt: u64) -> u64 {
+        splitmix64(self.stream(label) ^ salt)
+    }
+
+    /// `salted` folded to 32 bits, for phase settings that take a u32 seed.
+    pub fn phase(self, label: &str, salt: u32) -> u32 {
+        let v = self.salted(label, salt as u64);
+        (v ^ (v >> 32)) as u32
+    }
+
+    /// Shareable code: Crockford base32, no padding (e.g. `3RJ0`).
+    pub fn code(self) -> String {
+        if self.0 == 0 { return "0".into(); }
+        let mut out = Vec::new();
+        let mut v = self.0;
+        while v > 0 {
+            out.push(ALPHABET[(v & 31) as usize]);
+            v >>= 5;
+        }
+        out.reverse();
+        String::from_utf8(out).expect("ascii")
+    }
+}
+
+impl From<u64> for MapSeed {
+    fn from(v: u64) -> Self { MapSeed(v) }
+}
+
+impl fmt::Display for MapSeed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str(&self.code()) }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeedParseError {
+    Empty,
+    BadChar(char),
+    TooLong,
+}
+
+impl fmt::Display for SeedParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeedParseError::Empty => write!(f, "empty seed code"),
+            SeedParseError::BadChar(c) => write!(f, "`{c}` is not a seed code character"),
+            SeedParseError::TooLong => write!(f, "seed code is longer than 64 bits"),
+        }
+    }
+}
+
+impl std::error::Error for SeedParseError {}
+
+impl FromStr for MapSeed {
+    type Err = SeedParseError;
+
+    /// Case-insensitive; dashes and spaces are ignored, and the usual
+    /// look-alikes are accepted (O → 0, I/L → 1).
+    fn from_str(s: &str) -> Result<Self, SeedParseError> {
+        let mut v: u64 = 0;
+        let mut any = false;
+        for c in s.chars() {
+            if c == '-' || c.is_whitespace() { continue; }
+            let c = match c.to_ascii_uppercase() {
+                'O' => '0',
+                'I' | 'L' => '1',
+                c => c,
+            };
+            let d = ALPHABET.iter().position(|&a| a as char == c).ok_or(SeedParseError::BadChar(c))?;
+            if v >> 59 != 0 { return Err(SeedParseError::TooLong); }
+            v = (v << 5) | d as u64;
+            any = true;
+        }
+        if !any { return Err(SeedParseError::Empty); }
+        Ok(MapSeed(v))
+    }
+}
+
+impl Serialize for MapSeed {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> { s.serialize_str(&self.code()) }
+}
+
+impl<'de> Deserialize<'de> for MapSeed {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(d)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_round_trip() {
+        for v in [0, 1, 31, 32, 123456, 1 << 59, u64::MAX - 1, u64::MAX] {
+            let seed = MapSeed(v);
+            assert_eq!(seed.code().parse(), Ok(seed), "{v} -> {}", seed.code());
+        }
+        assert_eq!(MapSeed(0).code(), "0");
+        assert_eq!(MapSeed(u64::MAX).code(), "FZZZZZZZZZZZZ");
+    }
+
+    #[test]
+    fn parsing_forgives_case_lookalikes_and_separators() {
+        let seed = MapSeed(32);
+        for code in ["10", "lo", "IO", "Lo", "1-0", " 1 0 ", "0010"] {
+            assert_eq!(code.parse(), Ok(seed), "{code}");
+        }
+        assert_eq!("3rj0".parse::<MapSeed>(), "3RJ0".parse());
+    }
+
+    #[test]
+    fn bad_codes_are_rejected() {
+        assert_eq!("".parse::<MapSeed>(), Err(SeedParseError::Empty));
+        assert_eq!(" - ".parse::<MapSeed>(), Err(SeedParseError::Empty));
+        assert_eq!("3RU0".parse::<MapSeed>(), Err(SeedParseError::BadChar('U')));
+        assert_eq!("3R!0".parse::<MapSeed>(), Err(SeedParseError::BadChar('!')));
+        assert_eq!("G000000000000".parse::<MapSeed>(), Err(SeedParseError::TooLong));
+        assert_eq!("ZZZZZZZZZZZZZZ".parse::<MapSeed>(), Err(SeedParseError::TooLong));
+    }
+}
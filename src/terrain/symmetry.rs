@@ -0,0 +1,317 @@
+use glam::{IVec2, Vec2};
+use super::grid::Grid;
+use super::template::{MapTemplate, SymmetryMode};
+use super::objects::{Placed, PlacedObject};
+use super::ley::{LeyEdge, LeyGraph, LeyNodeKind};
+
+/// Symmetry around the map centre. The reference wedge is the slice of the map
+/// around base 0; every fold copies that wedge onto the others so each base
+/// sees the same neighbourhood.
+#[derive(Clone, Copy)]
+pub struct Symmetry {
+    center: Vec2,
+    folds: usize,
+    start_angle: f32, // radians, direction of base 0
+    mirror: bool,
+}
+
+impl Symmetry {
+    /// None when the mode is off or there is nothing to be fair between.
+    /// Mirror only makes sense for two bases; other counts fall back to rotation.
+    pub fn new(tpl: &MapTemplate, num_bases: usize, start_angle_deg: f32) -> Option<Self> {
+        if num_bases < 2 { return None; }
+        let mirror = match tpl.symmetry {
+            SymmetryMode::None => return None,
+            SymmetryMode::Rotational => false,
+            SymmetryMode::Mirror => num_bases == 2,
+        };
+        Some(Self {
+            center: Vec2::new(tpl.size.0 as f32, tpl.size.1 as f32) / 2.0,
+            folds: num_bases,
+            start_angle: start_angle_deg.to_radians(),
+            mirror,
+        })
+    }
+
+    #[inline] fn step(&self) -> f32 { std::f32::consts::TAU / self.folds as f32 }
+
+    #[inline] fn axis(&self) -> Vec2 { Vec2::new(self.start_angle.cos(), self.start_angle.sin()) }
+
+    // Tile centre relative to the symmetry centre
+    #[inline] fn rel(&self, p: IVec2) -> Vec2 { p.as_vec2() + Vec2::splat(0.5) - self.center }
+
+    #[inline] fn tile_at(&self, v: Vec2) -> IVec2 {
+        let q = v + self.center;
+        IVec2::new(q.x.floor() as i32, q.y.floor() as i32)
+    }
+
+    /// Which copy of the reference wedge `p` lies in (0 = reference).
+    pub fn sector(&self, p: IVec2) -> usize {
+        let v = self.rel(p);
+        if self.mirror {
+            return if v.dot(self.axis()) >= 0.0 { 0 } else { 1 };
+        }
+        let ang = v.y.atan2(v.x) - self.start_angle;
+        // Tiles on a wedge edge sit on an exact half-step; nudge them all the same
+        // way so a tile and its rotated copies agree on which side they fall.
+        let k = (ang / self.step() + 0.5 + 1e-4).floor() as i32;
+        k.rem_euclid(self.folds as i32) as usize
+    }
+
+    /// Map a reference-wedge offset into copy `k`.
+    fn transform(&self, v: Vec2, k: usize) -> Vec2 {
+        if k == 0 { return v; }
+        if self.mirror {
+            let u = self.axis();
+            return v - 2.0 * v.dot(u) * u;
+        }
+        Vec2::from_angle(self.step() * k as f32).rotate(v)
+    }
+
+    /// Inverse of `transform`: bring an offset in copy `k` back to the reference wedge.
+    fn inverse(&self, v: Vec2, k: usize) -> Vec2 {
+        if self.mirror { return self.transform(v, k); }
+        if k == 0 { return v; }
+        Vec2::from_angle(-self.step() * k as f32).rotate(v)
+    }
+
+    pub fn in_reference(&self, p: IVec2) -> bool { self.sector(p) == 0 }
+
+    /// Tile in the reference wedge that `p` copies from.
+    pub fn source_of(&self, p: IVec2) -> IVec2 {
+        let k = self.sector(p);
+        self.tile_at(self.inverse(self.rel(p), k))
+    }
+
+    /// Follow `source_of` until it settles. Off 90° steps, rounding can land a
+    /// tile on a source that is itself a copy, or two edge tiles on each other;
+    /// a cycle resolves to its smallest member so every tile in it agrees.
+    fn root_of(&self, p: IVec2) -> IVec2 {
+        let mut chain = vec![p];
+        let mut src = self.source_of(p);
+        while chain.len() <= self.folds {
+            if let Some(i) = chain.iter().position(|&q| q == src) {
+                return chain[i..].iter().copied().min_by_key(|q| (q.y, q.x)).unwrap_or(src);
+            }
+            chain.push(src);
+            src = self.source_of(src);
+        }
+        src
+    }
+
+    /// Copy `k` of tile `p`.
+    pub fn image(&self, p: IVec2, k: usize) -> IVec2 { self.tile_at(self.transform(self.rel(p), k)) }
+
+    /// All symmetric copies of a reference-wedge tile (including itself).
+    pub fn images(&self, p: IVec2) -> Vec<IVec2> {
+        let v = self.rel(p);
+        (0..self.folds).map(|k| self.tile_at(self.transform(v, k))).collect()
+    }
+
+    /// Replace every tile by its reference-wedge source. Tiles whose source
+    /// falls off the map (square corners under rotation) keep their own value.
+    pub fn fold_grid<T: Clone + Default>(&self, g: &Grid<T>) -> Grid<T> {
+        let mut out = g.clone();
+        for y in 0..g.h {
+            for x in 0..g.w {
+                let src = self.root_of(IVec2::new(x, y));
+                if g.in_bounds(src) {
+                    out.set(x, y, g.get(src.x, src.y).clone());
+                }
+            }
+        }
+        out
+    }
+
+    /// Keep polylines that start in the reference wedge and replicate them.
+    pub fn fold_polylines(&self, lines: &[Vec<IVec2>]) -> Vec<Vec<IVec2>> {
+        let mut out = Vec::new();
+        for line in lines {
+            let Some(&first) = line.first() else { continue; };
+            if !self.in_reference(first) { continue; }
+            for k in 0..self.folds {
+                out.push(line.iter().map(|&p| self.tile_at(self.transform(self.rel(p), k))).collect());
+            }
+        }
+        out
+    }
+
+    /// Every copy of every polyline, wherever it lies (for paths added after
+    /// a fold, whose own wedge may not be the reference one).
+    pub fn replicate_polylines(&self, lines: &[Vec<IVec2>]) -> Vec<Vec<IVec2>> {
+        lines.iter()
+            .flat_map(|line| (0..self.folds).map(move |k| line.iter().map(|&p| self.image(p, k)).collect()))
+            .collect()
+    }
+
+    /// Make the ley network a copy of its reference wedge. Nodes elsewhere move
+    /// onto the nearest free copy of a reference node of the same kind (a
+    /// shrine ring offset from the bases isn't mirror-symmetric, and rounding
+    /// shifts rotated shrines by a tile). Then every edge whose midpoint lies
+    /// in the reference wedge is copied, shape and all, into each wedge and
+    /// the rest are dropped: MST and kNN break ties on a symmetric ring
+    /// arbitrarily, so without this each base could get a different network.
+    pub fn fold_ley(&self, graph: &mut LeyGraph) {
+        let mut anchors: Vec<(IVec2, LeyNodeKind, bool)> = graph.nodes.iter()
+            .filter(|n| self.in_reference(n.pos))
+            .flat_map(|n| (0..self.folds).map(move |k| (self.image(n.pos, k), n.kind, k == 0)))
+            .collect();
+        for n in graph.nodes.iter_mut().filter(|n| !self.in_reference(n.pos)) {
+            let nearest = anchors.iter_mut()
+                .filter(|a| a.1 == n.kind && !a.2)
+                .min_by_key(|a| (a.0 - n.pos).length_squared());
+            if let Some(a) = nearest {
+                n.pos = a.0;
+                a.2 = true;
+            }
+        }
+
+        let node_near = |p: IVec2, kind| {
+            graph.nodes.iter().position(|n| n.kind == kind && (n.pos - p).length_squared() <= 2)
+        };
+        let mut edges: Vec<LeyEdge> = Vec::new();
+        for e in &graph.edges {
+            let (na, nb) = (graph.nodes[e.a], graph.nodes[e.b]);
+            if self.sector((na.pos + nb.pos).div_euclid(IVec2::splat(2))) != 0 { continue; }
+            for k in 0..self.folds {
+                let (Some(a), Some(b)) = (node_near(self.image(na.pos, k), na.kind), node_near(self.image(nb.pos, k), nb.kind))
+                    else { continue };
+                if a == b || edges.iter().any(|f| (f.a, f.b) == (a, b) || (f.a, f.b) == (b, a)) { continue; }
+                let mut points: Vec<IVec2> = e.points.iter().map(|&p| self.image(p, k)).collect();
+                // Rounding can move a copied end by a tile; pin them to the nodes.
+                if let Some(first) = points.first_mut() { *first = graph.nodes[a].pos; }
+                if let Some(last) = points.last_mut() { *last = graph.nodes[b].pos; }
+                edges.push(LeyEdge { a, b, length: e.length, points });
+            }
+        }
+        graph.edges = edges;
+    }
+
+    /// Keep objects in the reference wedge and stamp copies into the others.
+    /// Copies near a seam can crowd each other or another object's copies; an
+    /// object goes in only if all its copies keep `spacing`, so the wedges
+    /// still match.
+    pub fn fold_objects(&self, objs: &[PlacedObject], size: IVec2, spacing: &[Vec<f32>]) -> Vec<PlacedObject> {
+        let mut placed = Placed::new(size.x, size.y, spacing);
+        for o in objs.iter().filter(|o| self.in_reference(o.pos)) {
+            let kind = o.kind as usize;
+            let mut copies: Vec<IVec2> = Vec::new();
+            for p in self.images(o.pos) {
+                if p.x < 0 || p.y < 0 || p.x >= size.x || p.y >= size.y || copies.contains(&p) { continue; }
+                copies.push(p);
+            }
+            let own = spacing[kind][kind];
+            let fits = copies.iter().enumerate().all(|(i, &p)| {
+                placed.clear_of(p, kind, spacing)
+                    && copies[..i].iter().all(|&q| (p - q).as_vec2().length() >= own)
+            });
+            if !fits { continue; }
+            for p in copies { placed.push(PlacedObject { pos: p, kind: o.kind }); }
+        }
+        placed.objs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sym(folds: usize, mirror: bool) -> Symmetry {
+        Symmetry { center: Vec2::splat(32.0), folds, start_angle: 0.0, mirror }
+    }
+
+    fn noise() -> Grid<u8> {
+        let mut g = Grid::new(64, 64);
+        let mut rng = fastrand::Rng::with_seed(7);
+        for y in 0..64 { for x in 0..64 { g.set(x, y, rng.u8(..)); } }
+        g
+    }
+
+    #[test]
+    fn folding_twice_changes_nothing() {
+        for s in [sym(2, true), sym(2, false), sym(3, false), sym(4, false), sym(5, false)] {
+            let once = s.fold_grid(&noise());
+            let twice = s.fold_grid(&once);
+            assert!(once.as_slice() == twice.as_slice(), "{} folds (mirror {}) left a seam", s.folds, s.mirror);
+        }
+    }
+
+    #[test]
+    fn quarter_turns_copy_exactly() {
+        let s = sym(4, false);
+        let g = s.fold_grid(&noise());
+        for y in 0..64 {
+            for x in 0..64 {
+                let p = IVec2::new(x, y);
+                for q in s.images(s.source_of(p)) {
+                    assert_eq!(g.get(q.x, q.y), g.get(x, y), "{p} vs {q}");
+                }
+            }
+        }
+    }
+
+    // Eight shrines on a ring around the centre, joined by an open chain (as an
+    // MST might draw it) whose first edge has a bend.
+    fn ring_graph() -> LeyGraph {
+        let mut g = LeyGraph::default();
+        for j in 0..8 {
+            let a = (j as f32 * 45.0).to_radians();
+            let p = Vec2::splat(32.0) + Vec2::new(a.cos(), a.sin()) * 20.0;
+            g.add_node(p.floor().as_ivec2(), LeyNodeKind::Shrine);
+        }
+        for j in 0..7 { g.add_edge(j, j + 1); }
+        let (a, b) = g.segment(&g.edges[0]);
+        g.edges[0].points = vec![a, (a + b) / 2 + IVec2::new(3, 0), b];
+        g
+    }
+
+    fn has_edge(g: &LeyGraph, a: IVec2, b: IVec2) -> Option<&LeyEdge> {
+        let near = |n: usize, p: IVec2| (g.nodes[n].pos - p).length_squared() <= 2;
+        g.edges.iter().find(|e| (near(e.a, a) && near(e.b, b)) || (near(e.a, b) && near(e.b, a)))
+    }
+
+    #[test]
+    fn ley_topology_comes_from_the_reference_wedge() {
+        for s in [sym(4, false), sym(2, true)] {
+            let mut g = ring_graph();
+            s.fold_ley(&mut g);
+            assert!(!g.edges.is_empty());
+            for e in &g.edges {
+                let (a, b) = g.segment(e);
+                for k in 0..s.folds {
+                    let copy = has_edge(&g, s.image(a, k), s.image(b, k));
+                    assert!(copy.is_some(), "{} folds: no copy {k} of {a}-{b}", s.folds);
+                    assert_eq!(copy.unwrap().points.len(), e.points.len());
+                }
+            }
+        }
+        // Only 0-1 lies in the reference wedge: 2-3, 4-5 and 6-7 copy it, 1-2 and the rest go.
+        let (s, mut g) = (sym(4, false), ring_graph());
+        s.fold_ley(&mut g);
+        let pairs: Vec<(usize, usize)> = g.edges.iter().map(|e| (e.a.min(e.b), e.a.max(e.b))).collect();
+        assert_eq!(pairs, [(0, 1), (2, 3), (4, 5), (6, 7)]);
+    }
+
+    #[test]
+    fn folded_objects_keep_their_spacing_and_symmetry() {
+        let spacing = vec![vec![3.0, 2.0], vec![2.0, 4.0]];
+        let mut rng = fastrand::Rng::with_seed(11);
+        for s in [sym(4, false), sym(2, true)] {
+            let objs: Vec<PlacedObject> = (0..400)
+                .map(|_| PlacedObject { pos: IVec2::new(rng.i32(0..64), rng.i32(0..64)), kind: rng.u16(0..2) })
+                .filter(|o| s.in_reference(o.pos))
+                .collect();
+            let out = s.fold_objects(&objs, IVec2::splat(64), &spacing);
+            assert!(out.len() > 20);
+            for (i, a) in out.iter().enumerate() {
+                for b in &out[i + 1..] {
+                    let need = spacing[a.kind as usize][b.kind as usize];
+                    assert!((a.pos - b.pos).as_vec2().length() >= need, "{a:?} and {b:?} closer than {need}");
+                }
+                for p in s.images(a.pos) {
+                    assert!(out.iter().any(|o| o.pos == p && o.kind == a.kind), "{a:?} has no copy at {p}");
+                }
+            }
+        }
+    }
+}
@@ -1,7 +1,13 @@
-use serde::Deserialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Deserializer};
+use serde::de::{EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
+use super::compose::{compose_file, ComposeError, Composed};
+use super::validate::{validate, Issue, Severity};
 
 /// Only what Phase 1 needs. Keep it tiny.
 #[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct PlayerSpawns {
     /// Distance from map center to each base center.
     pub center_radius: i32,
@@ -11,21 +17,196 @@ pub struct PlayerSpawns {
     pub base_radius: i32,
 }
 
+// ---- Terrain classes (template) ----
+/// What the generator (and sim) should treat a class as. Phases that need
+/// "the water class" or "the ground class" look it up by role.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum ClassRole {
+    #[default]
+    None,
+    /// Fill for locked pads, carved corridors and leftovers (exactly one; first wins).
+    Ground,
+    Forest,
+    /// Rivers carve it; biome mode floods low ground with it.
+    Water,
+    /// River sources; biome mode puts it on peaks.
+    Mountain,
+}
+
 #[derive(Deserialize, Clone)]
-pub struct TerrainWeights {
-    pub grassland: f32,
-    pub forest: f32,
-    pub water: f32,
-    pub mountain: f32,
+#[serde(deny_unknown_fields)]
+pub struct TerrainClassDef {
+    pub name: String,
+    pub color: (u8, u8, u8),
+    #[serde(default)] pub role: ClassRole,
+    /// Clump radius range; None = `clumps.*_patch` for its role, else (4, 10).
+    #[serde(default)] pub patch: Option<(i32, i32)>,
+    #[serde(default = "d_class_blend_radius")] pub blend_radius: i32,
+    #[serde(default = "d_class_movement")] pub movement: f32,   // speed multiplier in the sim
+    #[serde(default = "d_class_passable")] pub passable: bool,  // overland reachability
+    #[serde(default = "d_class_carve_cost")] pub carve_cost: f32, // per tile when carving corridors
+    /// Tiles to draw it with (TMX export, iso view); None = stock tiles for its role.
+    #[serde(default)] pub tiles: Option<ClassTiles>,
+}
+
+/// Tiles a class is drawn with, chosen per cell by `terrain::autotile`.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ClassTiles {
+    /// Tileset file stem under `assets/tilesets`, e.g. "forest_256x128".
+    pub tileset: String,
+    /// Local tile ids; with several, each cell picks one (stable per position).
+    pub ids: Vec<u32>,
+    /// Tiles for cells next to another class (empty = `ids`).
+    #[serde(default)] pub edge: Vec<u32>,
+    /// Exact 8-neighbour masks → tile, for tilesets with transition art;
+    /// checked before `edge`/`ids` (see `autotile::blob_mask` for the bits).
+    #[serde(default)] pub blob: Vec<(u8, u32)>,
+}
+fn d_class_blend_radius() -> i32 { 2 }
+fn d_class_movement() -> f32 { 1.0 }
+fn d_class_passable() -> bool { true }
+fn d_class_carve_cost() -> f32 { 1.0 }
+
+/// The four classes every map had before classes were configurable.
+pub fn default_terrain_classes() -> Vec<TerrainClassDef> {
+    let class = |name: &str, color, role, blend_radius, movement, passable, carve_cost| TerrainClassDef {
+        name: name.to_string(), color, role, patch: None, blend_radius, movement, passable, carve_cost, tiles: None,
+    };
+    vec![
+        class("Grassland", (110, 180, 110), ClassRole::Ground,   2, 1.0, true,  1.0),
+        class("Forest",    ( 34, 139,  34), ClassRole::Forest,   3, 1.0, true,  1.0),
+        class("Water",     ( 64, 120, 255), ClassRole::Water,    2, 0.5, false, 8.0),
+        class("Mountain",  (150, 150, 150), ClassRole::Mountain, 3, 0.8, false, 12.0),
+    ]
 }
 
+// Class names are written as bare identifiers (`Forest`, `Sand`) so existing
+// templates keep their enum-like look while accepting any declared class.
+struct ClassKey(String);
+
+impl<'de> Deserialize<'de> for ClassKey {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct KeyVisitor;
+        impl Visitor<'_> for KeyVisitor {
+            type Value = ClassKey;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("a terrain class name") }
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<ClassKey, E> { Ok(ClassKey(v.to_string())) }
+        }
+        d.deserialize_identifier(KeyVisitor)
+    }
+}
+
+/// Per-class weights for an area, e.g. `( grassland: 0.2, forest: 0.5, sand: 0.3 )`.
+/// Keys match class names case-insensitively; missing classes weigh 0.
+#[derive(Clone, Default)]
+pub struct TerrainWeights(pub Vec<(String, f32)>);
+
+impl<'de> Deserialize<'de> for TerrainWeights {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct WeightsVisitor;
+        impl<'de> Visitor<'de> for WeightsVisitor {
+            type Value = TerrainWeights;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("class weights") }
+            fn visit_map<A: MapAccess<'de>>(self, mut m: A) -> Result<TerrainWeights, A::Error> {
+                let mut out = Vec::new();
+                while let Some((k, v)) = m.next_entry::<ClassKey, f32>()? { out.push((k.0, v)); }
+                Ok(TerrainWeights(out))
+            }
+        }
+        d.deserialize_struct("TerrainWeights", &[], WeightsVisitor)
+    }
+}
+
+/// A terrain class referenced by name, written bare: `region: Forest`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Region(pub String);
+
+impl<'de> Deserialize<'de> for Region {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct RegionVisitor;
+        impl<'de> Visitor<'de> for RegionVisitor {
+            type Value = Region;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("a terrain class name") }
+            fn visit_enum<A: EnumAccess<'de>>(self, a: A) -> Result<Region, A::Error> {
+                let (k, v) = a.variant::<ClassKey>()?;
+                v.unit_variant()?;
+                Ok(Region(k.0))
+            }
+        }
+        d.deserialize_enum("Region", &[], RegionVisitor)
+    }
+}
+
+// Per-class radii: accepts the old 4-tuple `(2,3,2,3)` as well as a list `[2,3,2,3,1]`.
+fn de_class_radii<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<i32>, D::Error> {
+    struct RadiiVisitor;
+    impl<'de> Visitor<'de> for RadiiVisitor {
+        type Value = Vec<i32>;
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result { f.write_str("per-class radii") }
+        fn visit_seq<A: SeqAccess<'de>>(self, mut s: A) -> Result<Vec<i32>, A::Error> {
+            let mut out = Vec::new();
+            while let Some(r) = s.next_element()? { out.push(r); }
+            Ok(out)
+        }
+    }
+    d.deserialize_any(RadiiVisitor)
+}
+
+/// What an area is measured from.
 #[derive(Deserialize, Clone)]
-pub enum AreaSource { Center, Spawn }
+pub enum AreaSource {
+    Center,
+    Spawn,
+    /// Every shrine.
+    Shrine,
+    /// Fixed tile coordinates.
+    Point(i32, i32),
+    /// Distance from the ley network; `radius` is the band half-width.
+    Ley,
+}
+
+/// Shape of an area around its source (`radius` is always the outer edge).
+#[derive(Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub enum AreaShape {
+    #[default]
+    Disk,
+    /// Ring between `inner` and `radius` (for `Ley`, a band that skips the lines themselves).
+    Annulus(i32),
+    /// Wedge of the disk, both in degrees. Spawn and shrine sectors are measured
+    /// from the direction pointing away from the map centre (0 = outwards), so every
+    /// base gets the same wedge; Center/Point sectors from +x. Ignored for `Ley`.
+    Sector { angle: f32, width: f32 },
+}
+
+/// Keep only the part of an area where fBm noise is above `threshold`.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
+pub struct AreaNoise {
+    #[serde(default = "default_area_noise_freq")]
+    pub freq: f32,
+    /// Noise is in [-1, 1]; 0 keeps about half the area.
+    #[serde(default)]
+    pub threshold: f32,
+    #[serde(default = "default_area_noise_octaves")]
+    pub octaves: u32,
+    /// Mixed with the terrain seed so different areas get different masks.
+    #[serde(default)]
+    pub seed: u32,
+}
+fn default_area_noise_freq() -> f32 { 0.04 }
+fn default_area_noise_octaves() -> u32 { 3 }
 
 #[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct TerrainArea {
     pub source: AreaSource,
     pub radius: i32,
+    #[serde(default)]
+    pub shape: AreaShape,
+    #[serde(default)]
+    pub noise: Option<AreaNoise>,
     pub weights: TerrainWeights,
     #[serde(default = "default_area_scale")]
     pub scale: f32,
@@ -34,6 +215,7 @@ fn default_area_scale() -> f32 { 1.0 }
 
 // ---- NEW: patch size configuration with sensible defaults ----
 #[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct TerrainClumps {
     pub forest_patch: (i32, i32),   // min,max radius in tiles
     pub water_patch: (i32, i32),
@@ -48,25 +230,113 @@ fn default_clumps() -> TerrainClumps {
 }
 fn default_shrine_grass_radius() -> i32 { 12 }
 
+/// How Phase 3 assigns terrain classes.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+pub enum TerrainMode {
+    /// Random blobs stamped per area by weight (original behaviour).
+    #[default]
+    Clumps,
+    /// Whittaker-style lookup from elevation x moisture.
+    Biomes,
+}
+
+// ---- Biome thresholds (used when mode = Biomes) ----
+// Elevation and moisture are rank-normalised to [0,1] before lookup, so the
+// levels below read roughly as "fraction of the map".
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BiomeConfig {
+    #[serde(default = "d_bio_sea_level")]       pub sea_level: f32,       // below = water
+    #[serde(default = "d_bio_mountain_level")]  pub mountain_level: f32,  // above = mountain
+    #[serde(default = "d_bio_forest_moisture")] pub forest_moisture: f32, // wetter than this = forest
+    #[serde(default = "d_bio_lowland")]         pub lowland: f32,         // elevation counted as lowland
+    #[serde(default = "d_bio_marsh_moisture")]  pub marsh_moisture: f32,  // wet lowland floods to water
+    #[serde(default = "d_bio_lowland_wetness")] pub lowland_wetness: f32, // moisture bonus for low ground
+    #[serde(default = "d_bio_moisture_freq")]   pub moisture_freq: f32,
+    #[serde(default = "d_bio_moisture_octaves")] pub moisture_octaves: u32,
+}
+fn d_bio_sea_level() -> f32 { 0.10 }
+fn d_bio_mountain_level() -> f32 { 0.88 }
+fn d_bio_forest_moisture() -> f32 { 0.55 }
+fn d_bio_lowland() -> f32 { 0.25 }
+fn d_bio_marsh_moisture() -> f32 { 0.85 }
+fn d_bio_lowland_wetness() -> f32 { 0.4 }
+fn d_bio_moisture_freq() -> f32 { 1.0 / 40.0 }
+fn d_bio_moisture_octaves() -> u32 { 4 }
+
+impl Default for BiomeConfig {
+    fn default() -> Self {
+        Self {
+            sea_level: d_bio_sea_level(),
+            mountain_level: d_bio_mountain_level(),
+            forest_moisture: d_bio_forest_moisture(),
+            lowland: d_bio_lowland(),
+            marsh_moisture: d_bio_marsh_moisture(),
+            lowland_wetness: d_bio_lowland_wetness(),
+            moisture_freq: d_bio_moisture_freq(),
+            moisture_octaves: d_bio_moisture_octaves(),
+        }
+    }
+}
+
 // Extend TerrainRules with shrine radius + clumps (both defaulted so your RON keeps working)
 #[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct TerrainRules {
     pub areas: Vec<TerrainArea>,
     #[serde(default = "default_shrine_grass_radius")]
     pub shrine_grass_radius: i32,
     #[serde(default = "default_clumps")]
     pub clumps: TerrainClumps,
+    #[serde(default)]
+    pub mode: TerrainMode,
+    #[serde(default)]
+    pub biomes: BiomeConfig,
+    #[serde(default = "default_terrain_classes")]
+    pub classes: Vec<TerrainClassDef>,
 }
 
 // ---- Ley config (template) ----
+/// How ley edges are drawn between their endpoints.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+pub enum LeyStyle {
+    #[default]
+    Straight,
+    /// Noise-driven sideways wander, pinned at both ends.
+    Meander,
+    /// Cheapest route over the heightmap: prefers high ground, avoids lowlands.
+    Terrain,
+}
+
 #[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct LeyConfig {
-    #[serde(default)] pub shrines_per_base: usize,
-    #[serde(default)] pub shrine_ring: i32,
-    #[serde(default)] pub offset_deg: f32,
-    #[serde(default)] pub connect_cycle: bool,
-    #[serde(default)] pub connect_spokes: bool,
+    #[serde(default = "d_ley_shrines_per_base")] pub shrines_per_base: usize,
+    #[serde(default = "d_ley_shrine_ring")] pub shrine_ring: i32,
+    #[serde(default = "d_ley_offset_deg")] pub offset_deg: f32,
+    #[serde(default = "d_ley_connect_cycle")] pub connect_cycle: bool,
+    #[serde(default = "d_ley_connect_spokes")] pub connect_spokes: bool,
+    #[serde(default)] pub connect_mst: bool,
+    #[serde(default)] pub connect_delaunay: bool,
+    #[serde(default)] pub connect_knn: usize,   // k nearest shrines per shrine (0 = off)
+    #[serde(default)] pub connect_bases: usize, // k nearest shrines per base (0 = off)
+    #[serde(default)] pub style: LeyStyle,
+    #[serde(default = "d_ley_meander")] pub meander: f32,               // max sideways offset in tiles
+    #[serde(default = "d_ley_meander_freq")] pub meander_freq: f32,     // wiggles per tile of line length
+    #[serde(default = "d_ley_terrain_weight")] pub terrain_weight: f32, // how hard Terrain style hugs high ground
+    #[serde(default = "d_ley_sample_spacing")] pub sample_spacing: i32, // tiles between polyline samples
+    #[serde(default = "d_ley_seed")] pub seed: u32, // salt on the map seed's "ley" stream
+    #[serde(default)] pub corridor_width: i32,                         // force terrain this close to a line (0 = off)
+    #[serde(default = "d_ley_corridor_class")] pub corridor_class: Region,
+    #[serde(default = "d_ley_object_reach")] pub object_reach: i32,   // "on the ley" for object affinity rules
 }
+fn d_ley_meander() -> f32 { 10.0 }
+fn d_ley_meander_freq() -> f32 { 1.0 / 48.0 }
+fn d_ley_terrain_weight() -> f32 { 4.0 }
+fn d_ley_sample_spacing() -> i32 { 4 }
+fn d_ley_seed() -> u32 { 23 }
+fn d_ley_corridor_class() -> Region { Region("Grassland".to_string()) }
+fn d_ley_object_reach() -> i32 { 3 }
 fn d_ley_shrines_per_base() -> usize { 1 }
 fn d_ley_shrine_ring() -> i32 { 160 }
 fn d_ley_offset_deg() -> f32 { 15.0 }
@@ -81,34 +351,48 @@ impl Default for LeyConfig {
             offset_deg: d_ley_offset_deg(),
             connect_cycle: d_ley_connect_cycle(),
             connect_spokes: d_ley_connect_spokes(),
+            connect_mst: false,
+            connect_delaunay: false,
+            connect_knn: 0,
+            connect_bases: 0,
+            style: LeyStyle::Straight,
+            meander: d_ley_meander(),
+            meander_freq: d_ley_meander_freq(),
+            terrain_weight: d_ley_terrain_weight(),
+            sample_spacing: d_ley_sample_spacing(),
+            seed: d_ley_seed(),
+            corridor_width: 0,
+            corridor_class: d_ley_corridor_class(),
+            object_reach: d_ley_object_reach(),
         }
     }
 }
 
 // ---- Blend config (template) ----
 #[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct BlendConfig {
     #[serde(default = "d_blend_iterations")] pub iterations: usize,
-    #[serde(default = "d_blend_radii")]      pub radii: (i32,i32,i32,i32), // (grass, forest, water, mountain)
+    #[serde(default, deserialize_with = "de_class_radii")] pub radii: Vec<i32>, // per class; missing = class blend_radius
     #[serde(default = "d_blend_inertia")]     pub inertia: f32,
     #[serde(default = "d_blend_boundary")]    pub boundary_only: bool,
 }
 fn d_blend_iterations() -> usize { 3 }
-fn d_blend_radii() -> (i32,i32,i32,i32) { (2,3,2,3) }
 fn d_blend_inertia() -> f32 { 0.25 }
 fn d_blend_boundary() -> bool { true }
 
 impl Default for BlendConfig {
     fn default() -> Self {
-        Self { iterations: d_blend_iterations(), radii: d_blend_radii(), inertia: d_blend_inertia(), boundary_only: d_blend_boundary() }
+        Self { iterations: d_blend_iterations(), radii: Vec::new(), inertia: d_blend_inertia(), boundary_only: d_blend_boundary() }
     }
 }
 
 // ---- Fractal config (template) ----
 #[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct FractalConfig {
     #[serde(default = "d_fract_iterations")]  pub iterations: usize,
-    #[serde(default = "d_fract_radii")]       pub radii: (i32,i32,i32,i32),
+    #[serde(default, deserialize_with = "de_class_radii")] pub radii: Vec<i32>,
     #[serde(default = "d_fract_inertia")]     pub inertia: f32,
     #[serde(default = "d_fract_boundary")]    pub boundary_only: bool,
     #[serde(default = "d_warp_amp")]          pub warp_amp: f32,
@@ -116,10 +400,9 @@ pub struct FractalConfig {
     #[serde(default = "d_warp_octaves")]      pub warp_octaves: u32,
     #[serde(default = "d_warp_gain")]         pub warp_gain: f32,
     #[serde(default = "d_warp_lacunarity")]   pub warp_lacunarity: f32,
-    #[serde(default = "d_warp_seed")]         pub seed: u32,
+    #[serde(default = "d_warp_seed")]         pub seed: u32,  // salt on the map seed
 }
 fn d_fract_iterations() -> usize { 2 }
-fn d_fract_radii() -> (i32,i32,i32,i32) { (2,3,2,3) }
 fn d_fract_inertia() -> f32 { 0.2 }
 fn d_fract_boundary() -> bool { true }
 fn d_warp_amp() -> f32 { 5.0 }
@@ -133,7 +416,7 @@ impl Default for FractalConfig {
     fn default() -> Self {
         Self {
             iterations: d_fract_iterations(),
-            radii: d_fract_radii(),
+            radii: Vec::new(),
             inertia: d_fract_inertia(),
             boundary_only: d_fract_boundary(),
             warp_amp: d_warp_amp(),
@@ -146,15 +429,163 @@ impl Default for FractalConfig {
     }
 }
 
-#[derive(Deserialize, Clone, Copy)]
-pub enum Region {
-    Grassland,
-    Forest,
-    Water,
-    Mountain,
+// ---- Elevation config (template) ----
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ElevationConfig {
+    #[serde(default = "d_elev_amplitude")]    pub amplitude: f32,     // +/- height around base elevation
+    #[serde(default = "d_elev_frequency")]    pub frequency: f32,     // cycles per tile of the first octave
+    #[serde(default = "d_elev_octaves")]      pub octaves: u32,
+    #[serde(default = "d_elev_gain")]         pub gain: f32,
+    #[serde(default = "d_elev_lacunarity")]   pub lacunarity: f32,
+    #[serde(default = "d_elev_ridge_weight")] pub ridge_weight: f32,  // 0 = rolling hills, 1 = pure ridges
+    #[serde(default = "d_elev_ridge_freq")]   pub ridge_frequency: f32,
+    #[serde(default = "d_elev_feather")]      pub feather: i32,       // tiles of smooth falloff around pads
+    #[serde(default = "d_elev_seed")]         pub seed: u32,  // salt on the map seed
+}
+fn d_elev_amplitude() -> f32 { 12.0 }
+fn d_elev_frequency() -> f32 { 1.0 / 64.0 }
+fn d_elev_octaves() -> u32 { 5 }
+fn d_elev_gain() -> f32 { 0.5 }
+fn d_elev_lacunarity() -> f32 { 2.0 }
+fn d_elev_ridge_weight() -> f32 { 0.35 }
+fn d_elev_ridge_freq() -> f32 { 1.0 / 48.0 }
+fn d_elev_feather() -> i32 { 10 }
+fn d_elev_seed() -> u32 { 7 }
+
+impl Default for ElevationConfig {
+    fn default() -> Self {
+        Self {
+            amplitude: d_elev_amplitude(),
+            frequency: d_elev_frequency(),
+            octaves: d_elev_octaves(),
+            gain: d_elev_gain(),
+            lacunarity: d_elev_lacunarity(),
+            ridge_weight: d_elev_ridge_weight(),
+            ridge_frequency: d_elev_ridge_freq(),
+            feather: d_elev_feather(),
+            seed: d_elev_seed(),
+        }
+    }
+}
+
+// ---- River config (template) ----
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RiverConfig {
+    #[serde(default)]                          pub count: usize,         // 0 = no rivers
+    #[serde(default = "d_river_width")]        pub width: i32,           // carve brush radius (0 = 1-tile stream)
+    #[serde(default = "d_river_meander")]      pub meander: f32,         // 0 = steepest descent, ~0.5 = lazy bends
+    #[serde(default = "d_river_meander_freq")] pub meander_freq: f32,
+    #[serde(default = "d_river_min_length")]   pub min_length: usize,    // shorter traces are dropped
+    #[serde(default = "d_river_spacing")]      pub source_spacing: i32,  // min distance between sources
+    #[serde(default = "d_river_seed")]         pub seed: u32,   // salt on the map seed
+}
+fn d_river_width() -> i32 { 1 }
+fn d_river_meander() -> f32 { 0.35 }
+fn d_river_meander_freq() -> f32 { 1.0 / 12.0 }
+fn d_river_min_length() -> usize { 24 }
+fn d_river_spacing() -> i32 { 40 }
+fn d_river_seed() -> u32 { 11 }
+
+impl Default for RiverConfig {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            width: d_river_width(),
+            meander: d_river_meander(),
+            meander_freq: d_river_meander_freq(),
+            min_length: d_river_min_length(),
+            source_spacing: d_river_spacing(),
+            seed: d_river_seed(),
+        }
+    }
+}
+
+// ---- Connectivity config (template) ----
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ConnectivityConfig {
+    #[serde(default = "d_conn_carve")]         pub carve: bool,        // false = report only
+    #[serde(default = "d_conn_width")]         pub corridor_width: i32, // carve cost per tile comes from the class
+}
+fn d_conn_carve() -> bool { true }
+fn d_conn_width() -> i32 { 1 }
+
+impl Default for ConnectivityConfig {
+    fn default() -> Self {
+        Self {
+            carve: d_conn_carve(),
+            corridor_width: d_conn_width(),
+        }
+    }
+}
+
+/// Fairness symmetry applied to every phase (see `terrain::symmetry`).
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+pub enum SymmetryMode {
+    #[default]
+    None,
+    /// N-fold rotation around the centre, N = number of bases.
+    Rotational,
+    /// Reflection across the line between two bases' halves (2 players only).
+    Mirror,
+}
+
+/// One step of the generation pipeline (see `terrain::pipeline`).
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhaseStep {
+    Bases,
+    Ley,
+    Elevation,
+    /// Reroute ley lines over the heightmap and rebuild the corridor masks.
+    LeyPaths,
+    Terrain,
+    Blend,
+    Fractal,
+    Rivers,
+    Connectivity,
+    Objects,
+    Fairness,
+}
+
+impl PhaseStep {
+    /// Order used when a template leaves `pipeline` empty.
+    pub const DEFAULT: [PhaseStep; 11] = [
+        PhaseStep::Bases, PhaseStep::Ley, PhaseStep::Elevation, PhaseStep::LeyPaths,
+        PhaseStep::Terrain, PhaseStep::Blend, PhaseStep::Fractal, PhaseStep::Rivers,
+        PhaseStep::Connectivity, PhaseStep::Objects, PhaseStep::Fairness,
+    ];
+
+    /// Steps that must have run earlier in the pipeline.
+    pub fn needs(self) -> &'static [PhaseStep] {
+        match self {
+            PhaseStep::Bases => &[],
+            PhaseStep::Ley | PhaseStep::Elevation | PhaseStep::Terrain => &[PhaseStep::Bases],
+            PhaseStep::LeyPaths => &[PhaseStep::Ley],
+            _ => &[PhaseStep::Terrain],
+        }
+    }
+}
+
+// ---- Fairness report config (template) ----
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+pub enum ReportFormat { Ron, Json }
+
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FairnessConfig {
+    #[serde(default = "d_fair_radii")] pub radii: Vec<i32>,      // rings measured around each base
+    #[serde(default)]                  pub write: Option<ReportFormat>, // also save next to the PNGs
+}
+fn d_fair_radii() -> Vec<i32> { vec![20, 40, 60] }
+
+impl Default for FairnessConfig {
+    fn default() -> Self { Self { radii: d_fair_radii(), write: None } }
 }
 
 #[derive(Deserialize, Clone, Copy)]
+#[serde(deny_unknown_fields)]
 pub struct Density {
     pub count: f32, // how many per 'area' units squared
     pub area: f32,  // the reference area for 'count'
@@ -164,34 +595,79 @@ impl Density {
 }
 
 #[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct ObjectRegionRule {
     pub region: Region,
     pub density: Density,
 }
 
+/// How an object type reacts to tiles within `ley.object_reach` of a ley line.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+pub enum LeyAffinity {
+    #[default]
+    Ignore,
+    /// Never placed on the ley.
+    Avoid,
+    /// Off-ley candidates are kept with probability 1/k, so the type clusters along lines.
+    Attract(f32),
+}
+
 #[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct ObjectTypeRule {
     pub name: String,                      // "Tree"
-    pub radius: i32,                       // min distance to ANY object (in map units/tiles)
+    pub radius: i32,                       // min distance to other objects (in map units/tiles), unless `spacing` says otherwise
     #[serde(default)]
     pub per_region: Vec<ObjectRegionRule>, // empty = not placed anywhere
+    #[serde(default)]
+    pub ley: LeyAffinity,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
 pub struct ObjectPlacementRules {
+    /// Salt on the map seed's "objects" stream.
     #[serde(default = "default_seed")]
     pub base_seed: u32,
     #[serde(default)]
     pub types: Vec<ObjectTypeRule>,
+    /// Min distances for particular pairs of types (by name, either order).
+    /// Pairs not listed keep the larger of the two radii.
+    #[serde(default)]
+    pub spacing: Vec<ObjectSpacing>,
 }
 fn default_seed() -> u32 { 0 }
 
-impl Default for ObjectPlacementRules {
-    fn default() -> Self { Self { base_seed: 0, types: Vec::new() } }
+/// `(a: "Bush", b: "Tree", min: 1.5)`; `a` and `b` may be the same type.
+#[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ObjectSpacing {
+    pub a: String,
+    pub b: String,
+    pub min: f32,
+}
+
+impl ObjectPlacementRules {
+    /// Min distance between every pair of types, indexed like `types`.
+    /// Pairs naming unknown types are skipped (validation reports them).
+    pub fn spacing_matrix(&self) -> Vec<Vec<f32>> {
+        let n = self.types.len();
+        let mut m: Vec<Vec<f32>> = (0..n)
+            .map(|i| (0..n).map(|j| self.types[i].radius.max(self.types[j].radius).max(0) as f32).collect())
+            .collect();
+        let find = |name: &str| self.types.iter().position(|t| t.name == name);
+        for s in &self.spacing {
+            let (Some(i), Some(j)) = (find(&s.a), find(&s.b)) else { continue };
+            m[i][j] = s.min.max(0.0);
+            m[j][i] = s.min.max(0.0);
+        }
+        m
+    }
 }
 
 // add to your MapTemplate
 #[derive(Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct MapTemplate {
     pub size: (i32, i32),
     pub player_spawns: super::template::PlayerSpawns,
@@ -201,12 +677,142 @@ pub struct MapTemplate {
     #[serde(default)] pub ley: LeyConfig,
     #[serde(default)] pub blend: BlendConfig,
     #[serde(default)] pub fractal: FractalConfig,
+    #[serde(default)] pub elevation: ElevationConfig,
+    #[serde(default)] pub rivers: RiverConfig,
+    #[serde(default)] pub connectivity: ConnectivityConfig,
+    #[serde(default)] pub symmetry: SymmetryMode,
+    #[serde(default)] pub fairness: FairnessConfig,
+    /// Phases to run, in order (empty = `PhaseStep::DEFAULT`). Steps may repeat.
+    #[serde(default)] pub pipeline: Vec<PhaseStep>,
+}
+
+/// Why a template couldn't be loaded.
+#[derive(Debug)]
+pub enum TemplateError {
+    /// The file, or something it extends/includes, is missing, cyclic or malformed.
+    Compose(ComposeError),
+    /// RON syntax or shape error (unknown field, wrong type, ...); 1-based position.
+    /// For a composed template the position is where `field` was written.
+    Parse { path: PathBuf, line: usize, col: usize, msg: String, field: Option<String> },
+    /// Validation found at least one error (warnings are listed too).
+    Invalid { path: PathBuf, issues: Vec<Issue> },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Compose(e) => write!(f, "{e}"),
+            TemplateError::Parse { path, line, col, msg, field } => {
+                write!(f, "{}:{line}:{col}: {msg}", path.display())?;
+                if let Some(field) = field { write!(f, " (in `{field}`)")?; }
+                Ok(())
+            }
+            TemplateError::Invalid { path, issues } => {
+                write!(f, "{}: invalid template", path.display())?;
+                for i in issues { write!(f, "\n  {i}")?; }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TemplateError::Compose(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ComposeError> for TemplateError {
+    fn from(e: ComposeError) -> Self { TemplateError::Compose(e) }
 }
 
 impl MapTemplate {
-    pub fn from_file(path: &str) -> Self {
-        let s = std::fs::read_to_string(path).expect("read template RON");
-        ron::from_str(&s).expect("parse template RON")
+    /// The steps this template runs.
+    pub fn pipeline_steps(&self) -> &[PhaseStep] {
+        if self.pipeline.is_empty() { &PhaseStep::DEFAULT } else { &self.pipeline }
+    }
+
+    /// Load a template, resolving `extends`/`include` (see `compose`), then
+    /// validate it. Any error rejects the template; warnings are returned for
+    /// the caller to report.
+    pub fn load(path: impl AsRef<Path>) -> Result<Loaded, TemplateError> {
+        let path = path.as_ref();
+        Self::from_composed(&compose_file(path)?, path)
     }
+
+    /// Parse and validate RON text that needs no composition. `path` only labels errors.
+    pub fn from_ron_str(text: &str, path: &Path) -> Result<Loaded, TemplateError> {
+        Self::parse(text, path, None)
+    }
+
+    /// Parse and validate the output of `compose`. Errors in merged text point
+    /// at the file and field they came from.
+    pub fn from_composed(composed: &Composed, path: &Path) -> Result<Loaded, TemplateError> {
+        Self::parse(&composed.text, path, composed.merged.then_some(composed))
+    }
+
+    fn parse(text: &str, path: &Path, merged: Option<&Composed>) -> Result<Loaded, TemplateError> {
+        let template: MapTemplate = ron::from_str(text).map_err(|e| {
+            let msg = e.code.to_string();
+            match merged.map(|c| c.field_at(e.position.line)) {
+                None => TemplateError::Parse {
+                    path: path.to_path_buf(), line: e.position.line, col: e.position.col, msg, field: None,
+                },
+                Some(Some((field, at))) => TemplateError::Parse {
+                    path: at.path.clone(), line: at.line, col: at.col, msg, field: Some(field.to_string()),
+                },
+                // Before the first field: nothing in the sources to point at.
+                Some(None) => TemplateError::Parse { path: path.to_path_buf(), line: 1, col: 1, msg, field: None },
+            }
+        })?;
+        let warnings = validate(&template);
+        if warnings.iter().any(|i| i.severity == Severity::Error) {
+            return Err(TemplateError::Invalid { path: path.to_path_buf(), issues: warnings });
+        }
+        Ok(Loaded { template, warnings })
+    }
+}
+
+/// A template that passed validation, with the warnings it raised.
+pub struct Loaded {
+    pub template: MapTemplate,
+    pub warnings: Vec<Issue>,
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::compose::compose_with;
+
+    fn load_from(files: &[(&str, &str)]) -> Result<Loaded, TemplateError> {
+        let read = |p: &Path| files.iter().find(|(f, _)| Path::new(f) == p)
+            .map(|(_, t)| t.to_string())
+            .ok_or_else(|| std::io::ErrorKind::NotFound.into());
+        let root = Path::new(files[0].0);
+        MapTemplate::from_composed(&compose_with(root, &mut |p| read(p))?, root)
+    }
+
+    #[test]
+    fn merged_errors_point_at_the_file_that_wrote_the_field() {
+        let err = load_from(&[
+            ("maps/a.ron", "(\n  extends: \"base.ron\",\n  size: (64, 64),\n)"),
+            ("maps/base.ron", "(\n  size: (32, 32),\n  blend: ( iterations: \"two\" ),\n)"),
+        ]).err().unwrap();
+        let TemplateError::Parse { path, line, col, field, .. } = &err else { panic!("{err}") };
+        assert_eq!((path.as_path(), *line, *col), (Path::new("maps/base.ron"), 3, 12));
+        assert_eq!(field.as_deref(), Some("blend.iterations"));
+        assert!(err.to_string().ends_with("(in `blend.iterations`)"), "{err}");
+    }
+
+    #[test]
+    fn plain_errors_keep_the_parser_position() {
+        let err = load_from(&[("maps/a.ron", "(\n  size: (64, 64),\n  bogus: 1,\n)")]).err().unwrap();
+        let TemplateError::Parse { path, line, field, .. } = &err else { panic!("{err}") };
+        assert_eq!((path.as_path(), *line), (Path::new("maps/a.ron"), 3));
+        assert!(field.is_none());
+    }
+}
@@ -0,0 +1,217 @@
+//! Export a generated map as an isometric Tiled TMX (256x128 tiles, like
+//! `assets/maps/demo.tmx`) for hand-polishing in Tiled.
+//!
+//! Layers: "Terrain" (tiles picked by `terrain::autotile`), "Objects" (points
+//! for placed objects, bases and shrines, typed by name) and "Rivers"
+//! (polylines). Tilesets are referenced as external `.tsx` files.
+
+use std::fmt::{self, Write as _};
+use std::path::{Path, PathBuf};
+use glam::IVec2;
+use super::pipeline::GeneratedMap;
+use super::autotile::{autotile, class_tiles};
+use super::template::MapTemplate;
+
+pub const TILE_WIDTH: u32 = 256;
+pub const TILE_HEIGHT: u32 = 128;
+
+/// Where the tilesets live.
+pub struct TmxExport<'a> {
+    /// Directory holding the `.tsx` files (read for tile counts).
+    pub tilesets_dir: &'a Path,
+    /// The same directory as written into the TMX, relative to the TMX file.
+    pub tilesets_ref: String,
+}
+
+#[derive(Debug)]
+pub enum TmxError {
+    Io(std::io::Error),
+    /// A class names a tileset that can't be loaded, or a tile it doesn't have.
+    Tileset { path: PathBuf, msg: String },
+}
+
+impl fmt::Display for TmxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TmxError::Io(e) => write!(f, "{e}"),
+            TmxError::Tileset { path, msg } => write!(f, "{}: {msg}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for TmxError {}
+
+impl From<std::io::Error> for TmxError {
+    fn from(e: std::io::Error) -> Self { TmxError::Io(e) }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Object-layer position of a cell centre (isometric objects use tile-height units on both axes).
+fn cell_px(p: IVec2) -> (f32, f32) {
+    ((p.x as f32 + 0.5) * TILE_HEIGHT as f32, (p.y as f32 + 0.5) * TILE_HEIGHT as f32)
+}
+
+struct UsedTileset {
+    stem: String,
+    firstgid: u32,
+    count: u32,
+}
+
+/// Write `map` as a TMX file at `path`.
+pub fn write_tmx(path: &Path, tpl: &MapTemplate, map: &GeneratedMap, ex: &TmxExport) -> Result<(), TmxError> {
+    let tiles = class_tiles(&tpl.terrain.classes);
+    let layer = autotile(&map.classes, &tiles);
+
+    // Load each tileset for its tile count, which sets the next firstgid.
+    let mut loader = tiled::Loader::new();
+    let mut used: Vec<UsedTileset> = Vec::new();
+    let mut next_gid = 1;
+    for stem in &layer.tilesets {
+        let tsx = ex.tilesets_dir.join(format!("{stem}.tsx"));
+        let ts = loader.load_tsx_tileset(&tsx).map_err(|e| TmxError::Tileset { path: tsx.clone(), msg: e.to_string() })?;
+        used.push(UsedTileset { stem: stem.clone(), firstgid: next_gid, count: ts.tilecount });
+        next_gid += ts.tilecount;
+    }
+    for (def, tiles) in tpl.terrain.classes.iter().zip(&tiles) {
+        let Some(u) = used.iter().find(|u| u.stem == tiles.tileset) else { continue };
+        let all = tiles.ids.iter().chain(&tiles.edge).chain(tiles.blob.iter().map(|(_, id)| id));
+        if let Some(id) = all.into_iter().find(|&&id| id >= u.count) {
+            let path = ex.tilesets_dir.join(format!("{}.tsx", u.stem));
+            return Err(TmxError::Tileset { path, msg: format!("class `{}` uses tile {id}, but the tileset has {}", def.name, u.count) });
+        }
+    }
+
+    let (w, h) = (map.classes.w, map.classes.h);
+    let mut xml = String::new();
+    let _ = writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        xml,
+        r#"<map version="1.10" tiledversion="1.11.2" orientation="isometric" renderorder="right-down" width="{w}" height="{h}" tilewidth="{TILE_WIDTH}" tileheight="{TILE_HEIGHT}" infinite="0" nextlayerid="4" nextobjectid="{}">"#,
+        map.objects.len() + map.bases.base_centers.len() + map.ley.shrines.len() + map.rivers.len() + 1,
+    );
+    let _ = writeln!(xml, " <properties>");
+    let _ = writeln!(xml, r#"  <property name="seed" value="{}"/>"#, map.seed);
+    let _ = writeln!(xml, " </properties>");
+    for u in &used {
+        let _ = writeln!(xml, r#" <tileset firstgid="{}" source="{}/{}.tsx"/>"#, u.firstgid, escape(&ex.tilesets_ref), escape(&u.stem));
+    }
+
+    // ---- Terrain ----
+    let _ = writeln!(xml, r#" <layer id="1" name="Terrain" width="{w}" height="{h}">"#);
+    let _ = writeln!(xml, r#"  <data encoding="csv">"#);
+    for y in 0..h {
+        let row: Vec<String> = (0..w).map(|x| {
+            let t = layer.tiles.get(x, y);
+            t.map_or(0, |t| used[t.tileset as usize].firstgid + t.id).to_string()
+        }).collect();
+        let sep = if y + 1 < h { "," } else { "" };
+        let _ = writeln!(xml, "{}{sep}", row.join(","));
+    }
+    let _ = writeln!(xml, "</data>");
+    let _ = writeln!(xml, " </layer>");
+
+    // ---- Objects, bases, shrines ----
+    let mut id = 1;
+    let mut point = |xml: &mut String, name: &str, kind: &str, p: IVec2| {
+        let (px, py) = cell_px(p);
+        let _ = writeln!(xml, r#"  <object id="{id}" name="{}" type="{}" x="{px}" y="{py}">"#, escape(name), escape(kind));
+        let _ = writeln!(xml, "   <point/>");
+        let _ = writeln!(xml, "  </object>");
+        id += 1;
+    };
+    let _ = writeln!(xml, r#" <objectgroup id="2" name="Objects">"#);
+    for (i, &b) in map.bases.base_centers.iter().enumerate() {
+        point(&mut xml, &format!("Base {}", i + 1), "Base", b);
+    }
+    for (i, &s) in map.ley.shrines.iter().enumerate() {
+        point(&mut xml, &format!("Shrine {}", i + 1), "Shrine", s);
+    }
+    for o in &map.objects {
+        let name = tpl.objects.types.get(o.kind as usize).map_or("Object", |t| t.name.as_str());
+        point(&mut xml, name, name, o.pos);
+    }
+    let _ = writeln!(xml, " </objectgroup>");
+
+    // ---- Rivers ----
+    let _ = writeln!(xml, r#" <objectgroup id="3" name="Rivers">"#);
+    for r in map.rivers.iter().filter(|r| !r.points.is_empty()) {
+        let (x0, y0) = cell_px(r.points[0]);
+        let pts: Vec<String> = r.points.iter().map(|&p| {
+            let (px, py) = cell_px(p);
+            format!("{},{}", px - x0, py - y0)
+        }).collect();
+        let _ = writeln!(xml, r#"  <object id="{id}" name="River" type="River" x="{x0}" y="{y0}">"#);
+        let _ = writeln!(xml, r#"   <polyline points="{}"/>"#, pts.join(" "));
+        let _ = writeln!(xml, "  </object>");
+        id += 1;
+    }
+    let _ = writeln!(xml, " </objectgroup>");
+    let _ = writeln!(xml, "</map>");
+
+    std::fs::write(path, xml)?;
+    Ok(())
+}
+
+/// `to` relative to directory `from` (both made absolute first), for writing
+/// tileset references; falls back to `to` as given when that fails.
+pub fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let (Ok(from), Ok(to)) = (std::fs::canonicalize(from), std::fs::canonicalize(to)) else {
+        return to.to_path_buf();
+    };
+    let common = from.components().zip(to.components()).take_while(|(a, b)| a == b).count();
+    let mut out = PathBuf::new();
+    for _ in from.components().skip(common) { out.push(".."); }
+    for c in to.components().skip(common) { out.push(c); }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::generate::template_pipeline;
+    use crate::terrain::pipeline::GenParams;
+    use crate::terrain::seed::MapSeed;
+    use crate::tilemap_bridge::tile_object_for_name;
+    use crate::tmx_import::{load_tmx_tilemap, TmxMapping};
+
+    #[test]
+    fn exported_maps_load_back_unchanged() {
+        let tpl = MapTemplate::load("assets/maps/mt_breyer.ron").expect("preset loads").template;
+        let params = GenParams { num_bases: 4, start_angle_deg: 0.0, seed: MapSeed::from(11), out_dir: None };
+        let map = template_pipeline(&tpl).generate(&tpl, params).expect("preset generates");
+        assert!(!map.rivers.is_empty() && !map.objects.is_empty(), "the round trip should cover rivers and objects");
+
+        let dir = std::env::temp_dir().join(format!("tmx_round_trip_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tilesets = std::fs::canonicalize("assets/tilesets").unwrap();
+        let ex = TmxExport { tilesets_dir: &tilesets, tilesets_ref: tilesets.to_string_lossy().replace('\\', "/") };
+        let path = dir.join("map.tmx");
+        write_tmx(&path, &tpl, &map, &ex).expect("map exports");
+        let (back, seed) = load_tmx_tilemap(&path, &TmxMapping::from_template(&tpl)).expect("export loads");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(seed, Some(map.seed));
+        assert_eq!((back.width, back.height), (map.classes.w, map.classes.h));
+        let cell = |i: usize| IVec2::new(i as i32 % back.width, i as i32 / back.width);
+        for (i, t) in back.tiles().iter().enumerate() {
+            let p = cell(i);
+            assert_eq!(t.terrain.0, *map.classes.get(p.x, p.y), "class at {p}");
+        }
+
+        // Row-major, like the tiles.
+        let mut placed: Vec<_> = map.objects.iter()
+            .filter_map(|o| tile_object_for_name(&tpl.objects.types[o.kind as usize].name).map(|k| (o.pos, k)))
+            .collect();
+        placed.sort_by_key(|(p, _)| (p.y, p.x));
+        let loaded: Vec<_> = back.tiles().iter().enumerate()
+            .filter_map(|(i, t)| t.object.map(|k| (cell(i), k)))
+            .collect();
+        assert_eq!(loaded, placed);
+
+        let rivers: Vec<Vec<IVec2>> = map.rivers.iter().map(|r| r.points.clone()).filter(|r| !r.is_empty()).collect();
+        assert_eq!(back.rivers, rivers);
+    }
+}
@@ -0,0 +1,306 @@
+//! Semantic checks on a parsed `MapTemplate`: values that deserialize fine but
+//! can't produce the map the author meant. Each issue names the field path it
+//! came from, e.g. `terrain.areas[2].weights.sand`.
+
+use std::fmt;
+use super::autotile::is_blob_mask;
+use super::template::{AreaShape, AreaSource, ClassRole, LeyAffinity, MapTemplate, ObjectSpacing, PhaseStep, Region};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// The generator copes (clamps, falls back), but probably not as intended.
+    Warning,
+    /// The template is rejected.
+    Error,
+}
+
+#[derive(Clone, Debug)]
+pub struct Issue {
+    pub severity: Severity,
+    pub path: String,
+    pub msg: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let tag = match self.severity { Severity::Warning => "warning", Severity::Error => "error" };
+        write!(f, "{tag}: {}: {}", self.path, self.msg)
+    }
+}
+
+#[derive(Default)]
+struct Issues(Vec<Issue>);
+
+impl Issues {
+    fn error(&mut self, path: impl Into<String>, msg: impl Into<String>) {
+        self.0.push(Issue { severity: Severity::Error, path: path.into(), msg: msg.into() });
+    }
+    fn warn(&mut self, path: impl Into<String>, msg: impl Into<String>) {
+        self.0.push(Issue { severity: Severity::Warning, path: path.into(), msg: msg.into() });
+    }
+    fn non_negative(&mut self, path: &str, v: i32) {
+        if v < 0 { self.error(path, format!("must be >= 0, got {v}")); }
+    }
+    fn positive(&mut self, path: &str, v: f32) {
+        if v.is_nan() || v <= 0.0 { self.error(path, format!("must be > 0, got {v}")); }
+    }
+}
+
+/// Check every section; issues come out in template order.
+pub fn validate(tpl: &MapTemplate) -> Vec<Issue> {
+    let mut out = Issues::default();
+    let (w, h) = tpl.size;
+    if w <= 0 || h <= 0 {
+        out.error("size", format!("map must be at least 1x1, got {w}x{h}"));
+        return out.0;
+    }
+    let half = w.min(h) / 2;
+
+    // ---- Bases ----
+    let ps = &tpl.player_spawns;
+    out.non_negative("player_spawns.base_radius", ps.base_radius);
+    out.non_negative("player_spawns.center_radius", ps.center_radius);
+    let max_ring = (half - ps.base_radius.max(1) - 1).max(0);
+    if ps.center_radius > max_ring {
+        out.warn("player_spawns.center_radius", format!(
+            "base ring {} doesn't fit a {w}x{h} map with base_radius {}; clamped to {max_ring}",
+            ps.center_radius, ps.base_radius,
+        ));
+    }
+
+    // ---- Terrain classes ----
+    let classes = &tpl.terrain.classes;
+    if classes.is_empty() || classes.len() > u8::MAX as usize {
+        out.error("terrain.classes", format!("need 1..=255 classes, got {}", classes.len()));
+    }
+    for (i, c) in classes.iter().enumerate() {
+        let p = format!("terrain.classes[{i}]");
+        if classes[..i].iter().any(|o| o.name.eq_ignore_ascii_case(&c.name)) {
+            out.error(format!("{p}.name"), format!("duplicate class name `{}`", c.name));
+        }
+        if let Some((lo, hi)) = c.patch {
+            if lo < 1 || hi < lo { out.error(format!("{p}.patch"), format!("need 1 <= min <= max, got ({lo}, {hi})")); }
+        }
+        out.non_negative(&format!("{p}.blend_radius"), c.blend_radius);
+        if c.movement < 0.0 { out.error(format!("{p}.movement"), "must be >= 0"); }
+        if c.carve_cost < 1.0 { out.warn(format!("{p}.carve_cost"), "below 1.0 is treated as 1.0"); }
+        if let Some(t) = &c.tiles {
+            if t.ids.is_empty() { out.error(format!("{p}.tiles.ids"), "need at least one tile id"); }
+            for &(mask, _) in &t.blob {
+                if !is_blob_mask(mask) {
+                    out.warn(format!("{p}.tiles.blob"), format!("mask {mask:#04x} sets a corner without both its sides; it never matches"));
+                }
+            }
+        }
+    }
+    if !classes.is_empty() && !classes.iter().any(|c| c.role == ClassRole::Ground) {
+        out.warn("terrain.classes", format!("no class has role Ground; `{}` is used as ground", classes[0].name));
+    }
+    let known = |name: &str| classes.iter().any(|c| c.name.eq_ignore_ascii_case(name));
+    let region = |out: &mut Issues, path: String, r: &Region| {
+        if !known(&r.0) { out.error(path, format!("unknown terrain class `{}`", r.0)); }
+    };
+
+    // ---- Terrain areas ----
+    let t = &tpl.terrain;
+    out.non_negative("terrain.shrine_grass_radius", t.shrine_grass_radius);
+    for (name, (lo, hi)) in [
+        ("forest_patch", t.clumps.forest_patch),
+        ("water_patch", t.clumps.water_patch),
+        ("mountain_patch", t.clumps.mountain_patch),
+    ] {
+        if lo < 1 || hi < lo { out.error(format!("terrain.clumps.{name}"), format!("need 1 <= min <= max, got ({lo}, {hi})")); }
+    }
+    for (i, a) in t.areas.iter().enumerate() {
+        let p = format!("terrain.areas[{i}]");
+        out.non_negative(&format!("{p}.radius"), a.radius);
+        if let AreaSource::Point(x, y) = a.source {
+            if x < 0 || y < 0 || x >= w || y >= h {
+                out.warn(format!("{p}.source"), format!("point ({x}, {y}) is outside the map"));
+            }
+        }
+        match a.shape {
+            AreaShape::Disk => {}
+            AreaShape::Annulus(inner) => {
+                if inner < 0 || inner > a.radius {
+                    out.error(format!("{p}.shape"), format!("annulus inner radius {inner} must be in 0..={}", a.radius));
+                }
+            }
+            AreaShape::Sector { width, .. } => {
+                out.positive(&format!("{p}.shape.width"), width);
+                if matches!(a.source, AreaSource::Ley) { out.warn(format!("{p}.shape"), "sectors are ignored for Ley areas"); }
+            }
+        }
+        if let Some(n) = a.noise { out.positive(&format!("{p}.noise.freq"), n.freq); }
+        for (name, v) in &a.weights.0 {
+            let wp = format!("{p}.weights.{name}");
+            if !known(name) { out.error(&wp, format!("unknown terrain class `{name}`")); }
+            if *v < 0.0 { out.warn(&wp, "negative weight is treated as 0"); }
+        }
+        if a.scale < 0.0 { out.warn(format!("{p}.scale"), "negative scale is treated as 0"); }
+    }
+
+    // ---- Ley ----
+    let ley = &tpl.ley;
+    if ley.shrines_per_base == 0 {
+        out.error("ley.shrines_per_base", "need at least one shrine per base");
+    } else if ley.shrine_ring < 0 || ley.shrine_ring >= half {
+        out.error("ley.shrine_ring", format!(
+            "shrines on ring {} fall outside a {w}x{h} map (max {})", ley.shrine_ring, half - 1,
+        ));
+    }
+    out.non_negative("ley.corridor_width", ley.corridor_width);
+    out.non_negative("ley.object_reach", ley.object_reach);
+    if ley.sample_spacing < 1 { out.error("ley.sample_spacing", format!("must be >= 1, got {}", ley.sample_spacing)); }
+    region(&mut out, "ley.corridor_class".into(), &ley.corridor_class);
+
+    // ---- Smoothing ----
+    for (sec, radii) in [("blend", &tpl.blend.radii), ("fractal", &tpl.fractal.radii)] {
+        for (k, &r) in radii.iter().enumerate() { out.non_negative(&format!("{sec}.radii[{k}]"), r); }
+        if radii.len() > classes.len() {
+            out.warn(format!("{sec}.radii"), format!("{} radii for {} classes; extras are ignored", radii.len(), classes.len()));
+        }
+    }
+
+    // ---- Rivers / connectivity ----
+    out.non_negative("rivers.width", tpl.rivers.width);
+    out.non_negative("connectivity.corridor_width", tpl.connectivity.corridor_width);
+
+    // ---- Objects ----
+    for (i, o) in tpl.objects.types.iter().enumerate() {
+        let p = format!("objects.types[{i}]");
+        out.non_negative(&format!("{p}.radius"), o.radius);
+        if tpl.objects.types[..i].iter().any(|q| q.name == o.name) {
+            out.warn(format!("{p}.name"), format!("duplicate object type `{}`", o.name));
+        }
+        if let LeyAffinity::Attract(k) = o.ley {
+            if k < 1.0 { out.warn(format!("{p}.ley"), format!("Attract({k}) below 1.0 behaves like Ignore")); }
+        }
+        for (j, r) in o.per_region.iter().enumerate() {
+            let rp = format!("{p}.per_region[{j}]");
+            region(&mut out, format!("{rp}.region"), &r.region);
+            out.positive(&format!("{rp}.density.area"), r.density.area);
+            if r.density.count < 0.0 { out.error(format!("{rp}.density.count"), "must be >= 0"); }
+        }
+    }
+    let objs = &tpl.objects;
+    for (i, s) in objs.spacing.iter().enumerate() {
+        let p = format!("objects.spacing[{i}]");
+        for (field, name) in [("a", &s.a), ("b", &s.b)] {
+            if !objs.types.iter().any(|t| t.name == *name) {
+                out.error(format!("{p}.{field}"), format!("no object type named `{name}`"));
+            }
+        }
+        if s.min.is_nan() || s.min < 0.0 { out.error(format!("{p}.min"), format!("must be >= 0, got {}", s.min)); }
+        let same = |q: &ObjectSpacing| (q.a == s.a && q.b == s.b) || (q.a == s.b && q.b == s.a);
+        if objs.spacing[..i].iter().any(same) {
+            out.warn(p.clone(), format!("`{}`/`{}` already has a spacing; the last one wins", s.a, s.b));
+        }
+    }
+
+    // ---- Fairness ----
+    for (k, &r) in tpl.fairness.radii.iter().enumerate() {
+        if r <= 0 { out.error(format!("fairness.radii[{k}]"), format!("must be > 0, got {r}")); }
+    }
+
+    // ---- Pipeline ----
+    let steps = tpl.pipeline_steps();
+    for (i, step) in steps.iter().enumerate() {
+        for need in step.needs() {
+            if !steps[..i].contains(need) {
+                out.error(format!("pipeline[{i}]"), format!("{step:?} needs {need:?} earlier in the pipeline"));
+            }
+        }
+    }
+    if !steps.contains(&PhaseStep::Terrain) {
+        out.error("pipeline", "no Terrain step; the map would have no terrain classes");
+    }
+
+    out.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terrain::template::TerrainWeights;
+
+    fn preset() -> MapTemplate {
+        MapTemplate::load("assets/maps/haunted_woods.ron").expect("preset loads").template
+    }
+
+    fn errors(tpl: &MapTemplate) -> Vec<(String, String)> {
+        validate(tpl).into_iter()
+            .filter(|i| i.severity == Severity::Error)
+            .map(|i| (i.path, i.msg))
+            .collect()
+    }
+
+    #[test]
+    fn preset_is_clean() {
+        assert!(errors(&preset()).is_empty());
+    }
+
+    #[test]
+    fn unknown_class_names_are_errors() {
+        let mut tpl = preset();
+        tpl.terrain.areas[0].weights = TerrainWeights(vec![("lava".into(), 1.0)]);
+        assert_eq!(errors(&tpl), [("terrain.areas[0].weights.lava".into(), "unknown terrain class `lava`".into())]);
+    }
+
+    #[test]
+    fn steps_before_their_inputs_are_errors() {
+        let mut tpl = preset();
+        tpl.pipeline = vec![PhaseStep::Bases, PhaseStep::Blend, PhaseStep::Terrain];
+        assert_eq!(errors(&tpl), [("pipeline[1]".into(), "Blend needs Terrain earlier in the pipeline".into())]);
+
+        tpl.pipeline = vec![PhaseStep::Bases, PhaseStep::Ley];
+        assert_eq!(errors(&tpl), [("pipeline".into(), "no Terrain step; the map would have no terrain classes".into())]);
+    }
+
+    #[test]
+    fn spacing_on_unknown_types_is_an_error() {
+        let mut tpl = preset();
+        let known = tpl.objects.types[0].name.clone();
+        tpl.objects.spacing = vec![ObjectSpacing { a: known, b: "boulder".into(), min: 2.0 }];
+        assert_eq!(errors(&tpl), [("objects.spacing[0].b".into(), "no object type named `boulder`".into())]);
+    }
+
+    #[test]
+    fn zero_shrines_are_an_error() {
+        let mut tpl = preset();
+        tpl.ley.shrines_per_base = 0;
+        assert_eq!(errors(&tpl), [("ley.shrines_per_base".into(), "need at least one shrine per base".into())]);
+    }
+
+    #[test]
+    fn partial_ley_block_keeps_the_defaults() {
+        let mut tpl = preset();
+        tpl.size = (512, 512);
+        tpl.ley = ron::from_str("(connect_mst: true)").unwrap();
+        let d = crate::terrain::template::LeyConfig::default();
+        assert_eq!((tpl.ley.shrines_per_base, tpl.ley.shrine_ring), (d.shrines_per_base, d.shrine_ring));
+        assert!(tpl.ley.shrines_per_base >= 1 && tpl.ley.shrine_ring > 0);
+        assert!(errors(&tpl).is_empty(), "{:?}", errors(&tpl));
+    }
+
+    #[test]
+    fn shrine_ring_off_the_map_is_an_error() {
+        let mut tpl = preset();
+        tpl.ley = ron::from_str("(shrines_per_base: 2)").unwrap();
+        let half = tpl.size.0.min(tpl.size.1) / 2;
+        assert_eq!(errors(&tpl), [("ley.shrine_ring".into(), format!(
+            "shrines on ring 160 fall outside a {0}x{1} map (max {2})", tpl.size.0, tpl.size.1, half - 1,
+        ))]);
+    }
+
+    #[test]
+    fn annulus_inner_past_outer_is_an_error() {
+        let mut tpl = preset();
+        tpl.terrain.areas[0].radius = 6;
+        tpl.terrain.areas[0].shape = AreaShape::Annulus(8);
+        assert_eq!(errors(&tpl), [("terrain.areas[0].shape".into(), "annulus inner radius 8 must be in 0..=6".into())]);
+
+        tpl.terrain.areas[0].shape = AreaShape::Annulus(6);
+        assert!(errors(&tpl).is_empty());
+    }
+}
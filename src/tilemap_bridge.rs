@@ -1,50 +1,59 @@
-use bevy::prelude::*; // brings IVec2/Vec2 types
-use crate::terrain::landscape::{
-    TERRAIN_GRASSLAND, TERRAIN_FOREST, TERRAIN_WATER, TERRAIN_MOUNTAIN,
-};
+use crate::terrain::classes::TerrainClasses;
 use crate::terrain::objects::PlacedObject;
+use crate::terrain::rivers::River;
 use crate::terrain::template::MapTemplate;
 use crate::terrain::grid::Grid;
 
-use crate::units::world::{TileMap, Tile, Terrain, TileObject};
+use crate::units::world::{empty_tile, TileMap, Tile, Terrain, TerrainInfo, TileObject, TREE_NUTS_MAX, BUSH_BERRIES_MAX};
 
-#[inline]
-fn tile_from_class(class: u8) -> Tile {
-    let terrain = match class {
-        TERRAIN_GRASSLAND => Terrain::Grassland,
-        TERRAIN_FOREST    => Terrain::Forest,
-        TERRAIN_WATER     => Terrain::Water,
-        TERRAIN_MOUNTAIN  => Terrain::Mountain,
-        _                 => Terrain::Grassland,
-    };
-    Tile {
-        terrain,
-        object: None,
-        nuts: 0.0,
-        berries: 0.0,
-        nuts_max: 0.0,
-        berries_max: 0.0,
-    }
-}
-
-/// Convert a class grid into a TileMap (terrain only).
-pub fn classes_to_tilemap(classes: &Grid<u8>) -> TileMap {
+/// Convert a class grid + heightmap into a TileMap (terrain only).
+/// The map's class table comes from the template registry.
+pub fn classes_to_tilemap(classes: &Grid<u8>, height: &Grid<f32>, reg: &TerrainClasses) -> TileMap {
     let w = classes.w;
     let h = classes.h;
-    // Fill with grass; replace each tile below.
-    let mut map = TileMap::new(w, h, tile_from_class(TERRAIN_GRASSLAND));
+    // Fill with ground; replace each tile below.
+    let mut map = TileMap::new(w, h, empty_tile(Terrain(reg.ground())));
+    map.classes = reg.defs.iter().map(TerrainInfo::from).collect();
     for y in 0..h {
         for x in 0..w {
             let i = (y * w + x) as usize;
             let class = *classes.get(x, y);
-            map.tiles[i] = tile_from_class(class);
+            let tile = &mut map.tiles_mut_untracked()[i];
+            *tile = empty_tile(Terrain(class));
+            tile.elevation = *height.get(x, y);
         }
     }
     map
 }
 
+/// Which sim object a template object type or Tiled object stands for, by
+/// name ("Oak Tree" → Tree); None for anything the sim doesn't model.
+pub fn tile_object_for_name(name: &str) -> Option<TileObject> {
+    let lname = name.to_lowercase();
+    if lname.contains("tree") {
+        Some(TileObject::Tree)
+    } else if lname.contains("bush") {
+        Some(TileObject::Bush)
+    } else if lname.contains("cave") {
+        Some(TileObject::Cave)
+    } else {
+        None
+    }
+}
+
+/// Put `obj` on `tile` with a full food stock.
+pub fn place_object(tile: &mut Tile, obj: TileObject) {
+    tile.object = Some(obj);
+    if obj == TileObject::Tree {
+        tile.nuts_max = TREE_NUTS_MAX;
+        tile.nuts = tile.nuts_max;
+    } else if obj == TileObject::Bush {
+        tile.berries_max = BUSH_BERRIES_MAX;
+        tile.berries = tile.berries_max;
+    }
+}
+
 /// Apply generated objects (trees/bushes/caves) into the TileMap tiles.
-/// Caves are applied if your TileObject has a Cave variant; otherwise they’re skipped.
 pub fn apply_objects_to_tilemap(
     map: &mut TileMap,
     tpl: &MapTemplate,
@@ -53,32 +62,15 @@ pub fn apply_objects_to_tilemap(
     for o in objects {
         if let Some(i) = map.idx(o.pos) {
             let tdef = &tpl.objects.types[o.kind as usize];
-            let lname = tdef.name.to_lowercase();
-
-            // Decide which TileObject to set
-            let obj = if lname.contains("tree") {
-                Some(TileObject::Tree)
-            } else if lname.contains("bush") {
-                Some(TileObject::Bush)
-            } else if lname.contains("cave") {
-                // comment this line out if you didn't add TileObject::Cave
-                Some(TileObject::Cave)
-            } else {
-                None
-            };
-
-            if let Some(objk) = obj {
-                let tile = &mut map.tiles[i];
-                tile.object = Some(objk);
-
-                if objk == TileObject::Tree {
-                    tile.nuts_max = 8.0;
-                    tile.nuts = tile.nuts_max;
-                } else if objk == TileObject::Bush {
-                    tile.berries_max = 6.0;
-                    tile.berries = tile.berries_max;
-                }
+            if let Some(objk) = tile_object_for_name(&tdef.name) {
+                place_object(&mut map.tiles_mut_untracked()[i], objk);
             }
         }
     }
 }
+
+/// Keep each river's course (source → mouth) on the TileMap; its tiles are
+/// already water in the class grid.
+pub fn apply_rivers_to_tilemap(map: &mut TileMap, rivers: &[River]) {
+    map.rivers = rivers.iter().map(|r| r.points.clone()).collect();
+}
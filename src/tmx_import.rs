@@ -0,0 +1,229 @@
+//! Hand-made Tiled maps for the sim. `load_tmx_tilemap` turns a TMX file into
+//! a `TileMap`; `TmxMapPlugin` uses it in place of `MapGenPlugin`.
+//!
+//! Tile layers are read bottom to top (a tile on a higher layer wins) and each
+//! tile becomes a terrain class through `TmxMapping`. Objects on any object
+//! layer become trees/bushes/caves by type (or name), with optional `nuts`,
+//! `berries`, `nuts_max` and `berries_max` properties; `River` polylines
+//! become rivers. Maps written by `terrain::tmx` read back as they were.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use tiled::{LayerType, ObjectShape, Orientation, PropertyValue, Properties};
+
+use crate::map_plugin::MapGenerated;
+use crate::terrain::autotile::class_tiles;
+use crate::terrain::seed::MapSeed;
+use crate::terrain::template::{ClassRole, MapTemplate, TerrainClassDef, default_terrain_classes};
+use crate::tilemap_bridge::{place_object, tile_object_for_name};
+use crate::units::world::{empty_tile, SimRng, Terrain, TerrainInfo, TileMap, TileMapPlugin};
+
+/// How tiles become terrain. For each tile, in order:
+/// 1. a `terrain` string property on the tile in Tiled (class or role name),
+/// 2. the tiles registered here (every tile the classes draw with, see
+///    `autotile::class_tiles`, plus anything added with `with_tiles`),
+/// 3. `fallback`.
+///
+/// Tilesets are named by their image's file stem (`terrain1_256x128`), so
+/// embedded and external copies of a tileset map the same way.
+#[derive(Clone, Debug)]
+pub struct TmxMapping {
+    pub classes: Vec<TerrainInfo>,
+    tiles: HashMap<(String, u32), Terrain>,
+    /// Class for tiles nothing else matches (and empty cells).
+    pub fallback: Terrain,
+}
+
+impl Default for TmxMapping {
+    /// The classic four classes and their stock tiles.
+    fn default() -> Self { Self::from_classes(&default_terrain_classes()) }
+}
+
+impl TmxMapping {
+    /// Classes (and tiles) from a template, to match maps exported from it.
+    pub fn from_template(tpl: &MapTemplate) -> Self { Self::from_classes(&tpl.terrain.classes) }
+
+    pub fn from_classes(defs: &[TerrainClassDef]) -> Self {
+        let mut tiles = HashMap::new();
+        for (k, t) in class_tiles(defs).into_iter().enumerate() {
+            for id in t.ids.iter().chain(&t.edge).chain(t.blob.iter().map(|(_, id)| id)) {
+                tiles.entry((t.tileset.clone(), *id)).or_insert(Terrain(k as u8));
+            }
+        }
+        let fallback = defs.iter().position(|d| d.role == ClassRole::Ground).unwrap_or(0);
+        Self { classes: defs.iter().map(TerrainInfo::from).collect(), tiles, fallback: Terrain(fallback as u8) }
+    }
+
+    /// Map tiles `ids` of `tileset` to `class`, replacing earlier rules.
+    pub fn with_tiles(mut self, tileset: &str, ids: impl IntoIterator<Item = u32>, class: Terrain) -> Self {
+        for id in ids {
+            self.tiles.insert((tileset.to_string(), id), class);
+        }
+        self
+    }
+
+    /// Class called `name`, else the first class whose role is `name`
+    /// (case-insensitive either way).
+    pub fn class_named(&self, name: &str) -> Option<Terrain> {
+        let by_name = self.classes.iter().position(|c| c.name.eq_ignore_ascii_case(name));
+        let by_role = || self.classes.iter().position(|c| format!("{:?}", c.role).eq_ignore_ascii_case(name));
+        by_name.or_else(by_role).map(|k| Terrain(k as u8))
+    }
+}
+
+#[derive(Debug)]
+pub enum TmxImportError {
+    Load(tiled::Error),
+    /// Infinite maps have no fixed size to give the sim.
+    Infinite,
+    /// A tile's `terrain` property names no class.
+    UnknownTerrain { tileset: String, id: u32, name: String },
+}
+
+impl fmt::Display for TmxImportError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TmxImportError::Load(e) => write!(f, "{e}"),
+            TmxImportError::Infinite => write!(f, "infinite maps are not supported"),
+            TmxImportError::UnknownTerrain { tileset, id, name } => {
+                write!(f, "tile {id} of {tileset}: terrain `{name}` is not a class or role")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TmxImportError {}
+
+impl From<tiled::Error> for TmxImportError {
+    fn from(e: tiled::Error) -> Self { TmxImportError::Load(e) }
+}
+
+fn tileset_key(ts: &tiled::Tileset) -> String {
+    ts.image.as_ref()
+        .and_then(|i| i.source.file_stem())
+        .map_or_else(|| ts.name.clone(), |s| s.to_string_lossy().into_owned())
+}
+
+fn float_prop(props: &Properties, name: &str) -> Option<f32> {
+    match props.get(name)? {
+        PropertyValue::FloatValue(v) => Some(*v),
+        PropertyValue::IntValue(v) => Some(*v as f32),
+        _ => None,
+    }
+}
+
+/// Build a `TileMap` from the TMX file at `path`. Also returns the map's
+/// `seed` property (set by `terrain::tmx`), if it has one.
+pub fn load_tmx_tilemap(path: &Path, mapping: &TmxMapping) -> Result<(TileMap, Option<MapSeed>), TmxImportError> {
+    let tmx = tiled::Loader::new().load_tmx_map(path)?;
+    let (w, h) = (tmx.width as i32, tmx.height as i32);
+    let mut map = TileMap::new(w, h, empty_tile(mapping.fallback));
+    map.classes = mapping.classes.clone();
+
+    // Resolve each tile the first time it's seen.
+    let mut resolved: HashMap<(usize, u32), Terrain> = HashMap::new();
+    for layer in tmx.layers().filter(|l| l.visible) {
+        let LayerType::Tiles(tiles) = layer.layer_type() else { continue };
+        let tiled::TileLayer::Finite(tiles) = tiles else { return Err(TmxImportError::Infinite) };
+        for y in 0..h {
+            for x in 0..w {
+                let Some(t) = tiles.get_tile(x, y) else { continue };
+                let key = (t.tileset_index(), t.id());
+                let terrain = match resolved.get(&key) {
+                    Some(&c) => c,
+                    None => {
+                        let ts = t.get_tileset();
+                        let by_prop = t.get_tile().and_then(|d| match d.properties.get("terrain") {
+                            Some(PropertyValue::StringValue(s)) => Some(s.clone()),
+                            _ => None,
+                        });
+                        let c = match by_prop {
+                            Some(name) => mapping.class_named(&name).ok_or_else(|| TmxImportError::UnknownTerrain {
+                                tileset: ts.name.clone(), id: t.id(), name,
+                            })?,
+                            None => mapping.tiles.get(&(tileset_key(ts), t.id())).copied().unwrap_or(mapping.fallback),
+                        };
+                        *resolved.entry(key).or_insert(c)
+                    }
+                };
+                map.tiles_mut_untracked()[(y * w + x) as usize].terrain = terrain;
+            }
+        }
+    }
+
+    // Isometric object positions are in tile-height units along both axes.
+    let cell_w = match tmx.orientation {
+        Orientation::Isometric => tmx.tile_height,
+        _ => tmx.tile_width,
+    } as f32;
+    let cell_h = tmx.tile_height as f32;
+    let cell = |x: f32, y: f32| IVec2::new((x / cell_w).floor() as i32, (y / cell_h).floor() as i32);
+
+    for layer in tmx.layers().filter(|l| l.visible) {
+        let LayerType::Objects(objects) = layer.layer_type() else { continue };
+        for obj in objects.objects() {
+            let kind = if obj.user_type.is_empty() { &obj.name } else { &obj.user_type };
+            if kind.eq_ignore_ascii_case("river") {
+                if let ObjectShape::Polyline { points } = &obj.shape {
+                    let river: Vec<IVec2> = points.iter().map(|&(px, py)| cell(obj.x + px, obj.y + py)).collect();
+                    map.rivers.push(river);
+                }
+                continue;
+            }
+            let (Some(o), Some(i)) = (tile_object_for_name(kind), map.idx(cell(obj.x, obj.y))) else { continue };
+            let tile = &mut map.tiles_mut_untracked()[i];
+            place_object(tile, o);
+            if let Some(v) = float_prop(&obj.properties, "nuts_max") { tile.nuts_max = v; }
+            if let Some(v) = float_prop(&obj.properties, "berries_max") { tile.berries_max = v; }
+            tile.nuts = float_prop(&obj.properties, "nuts").unwrap_or(tile.nuts_max).min(tile.nuts_max);
+            tile.berries = float_prop(&obj.properties, "berries").unwrap_or(tile.berries_max).min(tile.berries_max);
+        }
+    }
+
+    let seed = match tmx.properties.get("seed") {
+        Some(PropertyValue::StringValue(s)) => s.parse().ok(),
+        _ => None,
+    };
+    Ok((map, seed))
+}
+
+/// Runs the sim on a hand-made map: loads the TMX at startup, inserts the
+/// `TileMap` and sends `MapGenerated`, like `MapGenPlugin` does for templates.
+pub struct TmxMapPlugin {
+    pub path: PathBuf,
+    pub mapping: TmxMapping,
+}
+
+#[derive(Resource)]
+struct TmxSource {
+    path: PathBuf,
+    mapping: TmxMapping,
+}
+
+impl Plugin for TmxMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MapGenerated>()
+            .insert_resource(TmxSource { path: self.path.clone(), mapping: self.mapping.clone() })
+            .insert_resource(TileMap::empty())
+            .insert_resource(SimRng(fastrand::Rng::with_seed(MapSeed::default().stream("sim"))))
+            .add_systems(Startup, load_tmx_map);
+        if !app.is_plugin_added::<TileMapPlugin>() {
+            app.add_plugins(TileMapPlugin);
+        }
+    }
+}
+
+fn load_tmx_map(mut commands: Commands, mut generated: EventWriter<MapGenerated>, source: Res<TmxSource>) {
+    match load_tmx_tilemap(&source.path, &source.mapping) {
+        Ok((map, seed)) => {
+            info!("loaded {} ({}x{})", source.path.display(), map.width, map.height);
+            commands.insert_resource(map);
+            commands.insert_resource(SimRng(fastrand::Rng::with_seed(seed.unwrap_or_default().stream("sim"))));
+            generated.write(MapGenerated);
+        }
+        Err(e) => error!("{}: {e}", source.path.display()),
+    }
+}
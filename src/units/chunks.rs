@@ -0,0 +1,30 @@
+//! Square blocks of tiles the views draw as one texture or mesh each, so the
+//! entity count follows the map's area in chunks rather than tiles.
+
+use bevy::prelude::*;
+
+/// Tiles along each side of a chunk.
+pub const CHUNK_SIZE: i32 = 32;
+
+/// Chunk holding `cell`.
+pub fn chunk_of(cell: IVec2) -> IVec2 {
+    cell.div_euclid(IVec2::splat(CHUNK_SIZE))
+}
+
+/// Chunks along each axis for a `w` x `h` map.
+pub fn chunk_grid(w: i32, h: i32) -> IVec2 {
+    IVec2::new((w + CHUNK_SIZE - 1) / CHUNK_SIZE, (h + CHUNK_SIZE - 1) / CHUNK_SIZE)
+}
+
+/// First cell of `chunk` and its size in tiles (smaller along the map's far edges).
+pub fn chunk_cells(chunk: IVec2, w: i32, h: i32) -> (IVec2, IVec2) {
+    let min = chunk * CHUNK_SIZE;
+    let max = (min + IVec2::splat(CHUNK_SIZE)).min(IVec2::new(w, h));
+    (min, max - min)
+}
+
+/// Every chunk of a `w` x `h` map, row by row.
+pub fn all_chunks(w: i32, h: i32) -> impl Iterator<Item = IVec2> {
+    let n = chunk_grid(w, h);
+    (0..n.y).flat_map(move |y| (0..n.x).map(move |x| IVec2::new(x, y)))
+}
@@ -1,7 +1,7 @@
 use bevy::prelude::*;
 use std::collections::HashMap;
 use super::base::{Position, Velocity, Kinematics, Species, BrainState, Brain};
-use super::world::{TILE_SIZE};
+use super::world::{FOOD_EMPTY, TILE_SIZE};
 use super::route::{Route, route_system};
 use super::forage::{forage_system, cell_center, is_predator, is_prey_of};
 use super::movement::{movement_system};
@@ -111,9 +111,11 @@ impl Plugin for WildlifeSimPlugin {
 fn decision_system(
     time: Res<Time>,
     map: Res<super::world::TileMap>,
+    mut rng: ResMut<super::world::SimRng>,
     mut q: Query<(&Needs, &Position, &mut Brain)>
 ) {
     let dt = time.delta_secs();
+    let rng = &mut rng.0;
 
     for (needs, pos, mut brain) in &mut q {
         brain.replan_cd -= dt;
@@ -128,10 +130,10 @@ fn decision_system(
             } else {
                 // satiated → wander
                 if brain.replan_cd > 0.0 && brain.desired_target.is_some() { continue; }
-                let jitter = Vec2::new(fastrand::f32() - 0.5, fastrand::f32() - 0.5)
+                let jitter = Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5)
                     .normalize_or_zero() * 6.0;
                 brain.desired_target = Some(map.clamp_target(pos.p + jitter));
-                brain.replan_cd = 2.0 + fastrand::f32() * 2.0;
+                brain.replan_cd = 2.0 + rng.f32() * 2.0;
             }
             continue;
         }
@@ -163,7 +165,6 @@ fn eat_system(
     mut q: Query<(&Species, &Position, &mut Route, &mut Needs, &mut Brain)>,
 ) {
     let dt = time.delta_secs();
-    const EMPTY_EPS: f32    = 0.02;      // consider empty below this
 
     for (sp, pos, mut route, mut needs, mut brain) in &mut q {
         // arrival → start Eating (freeze movement)
@@ -195,7 +196,7 @@ fn eat_system(
         }
 
         // Eat from the tile
-        let Some(tile) = map.tile_at_cell_mut(cell) else {
+        let Some(tile) = map.tile_at_cell(cell) else {
             // cell vanished? go wander
             brain.state = BrainState::Wander;
             brain.target_cell = None;
@@ -207,7 +208,7 @@ fn eat_system(
         if matches!(sp, Species::Squirrel | Species::Bird) { edible += tile.nuts.max(0.0); }
         if matches!(sp, Species::Squirrel | Species::Bird | Species::Deer) { edible += tile.berries.max(0.0); }
 
-        if edible <= EMPTY_EPS {
+        if edible <= FOOD_EMPTY {
             // Out of stock → remember this cell and avoid for a while
             // brain.last_food_cell = Some(cell);
             // brain.last_food_cooldown = AVOID_SECONDS;
@@ -217,27 +218,31 @@ fn eat_system(
         }
 
         // Consume up to eat_rate*dt, preferring the richer resource
-        let mut to_take = needs.eat_rate * dt;
-
-        // helper to drain one resource
-        let mut drain = |store: &mut f32, want: bool| -> f32 {
-            if !want || *store <= 0.0 || to_take <= 0.0 { return 0.0; }
-            let take = to_take.min(*store);
-            *store -= take;
-            to_take -= take;
-            take
-        };
-
-        // choose order by which has more
-        if matches!(sp, Species::Squirrel | Species::Bird) && tile.nuts >= tile.berries {
-            let _ = drain(&mut tile.nuts, true);
-            let _ = drain(&mut tile.berries, matches!(sp, Species::Squirrel | Species::Bird | Species::Deer));
-        } else {
-            let _ = drain(&mut tile.berries, matches!(sp, Species::Squirrel | Species::Bird | Species::Deer));
-            let _ = drain(&mut tile.nuts, matches!(sp, Species::Squirrel | Species::Bird));
-        }
+        let bite = needs.eat_rate * dt;
+        let left = map.update(cell, |tile| {
+            let mut to_take = bite;
+
+            // helper to drain one resource
+            let mut drain = |store: &mut f32, want: bool| -> f32 {
+                if !want || *store <= 0.0 || to_take <= 0.0 { return 0.0; }
+                let take = to_take.min(*store);
+                *store -= take;
+                to_take -= take;
+                take
+            };
+
+            // choose order by which has more
+            if matches!(sp, Species::Squirrel | Species::Bird) && tile.nuts >= tile.berries {
+                let _ = drain(&mut tile.nuts, true);
+                let _ = drain(&mut tile.berries, matches!(sp, Species::Squirrel | Species::Bird | Species::Deer));
+            } else {
+                let _ = drain(&mut tile.berries, matches!(sp, Species::Squirrel | Species::Bird | Species::Deer));
+                let _ = drain(&mut tile.nuts, matches!(sp, Species::Squirrel | Species::Bird));
+            }
+            to_take
+        }).unwrap_or(bite);
 
-        let gained = (needs.eat_rate * dt) - to_take;
+        let gained = bite - left;
         if gained > 0.0 {
             needs.satiation = (needs.satiation + gained).min(needs.cap);
         }
@@ -362,16 +367,21 @@ fn repro_cooldown_system(time: Res<Time>, mut q: Query<&mut Repro>) {
     }
 }
 
+type MateScanQuery<'w, 's> =
+    Query<'w, 's, (Entity, &'static Species, &'static Position, &'static Kinematics, &'static Needs, &'static Brain, &'static Repro)>;
+type MateWriteQuery<'w, 's> = Query<'w, 's, (&'static mut Needs, &'static mut Brain, &'static mut Repro)>;
+
 fn mating_system(
     mut commands: Commands,
     map: Res<super::world::TileMap>,
+    mut rng: ResMut<super::world::SimRng>,
 
     // ParamSet avoids B0001 by separating read & write phases
     mut ps: ParamSet<(
         // p0: read-only scan to collect candidates
-        Query<(Entity, &Species, &Position, &Kinematics, &Needs, &Brain, &Repro)>,
+        MateScanQuery,
         // p1: write parents when we commit a pair
-        Query<(&mut Needs, &mut Brain, &mut Repro)>,
+        MateWriteQuery,
     )>,
 ) {
     let mate_r2 = (MATE_RANGE_TILES * TILE_SIZE).powi(2);
@@ -441,7 +451,7 @@ fn mating_system(
 
         // Offspring placement & speed
         let mid = (a.pos + b.pos) * 0.5
-            + Vec2::new(fastrand::f32() - 0.5, fastrand::f32() - 0.5)
+            + Vec2::new(rng.0.f32() - 0.5, rng.0.f32() - 0.5)
                 .normalize_or_zero() * jitter_r;
         let child_pos = map.clamp_target(mid);
         let child_speed = (a.speed + b.speed) * 0.5;
@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 use super::base::{Species, Position, BrainState, Brain, FoodKind};
-use super::world::{TILE_SIZE};
+use super::world::{SimRng, TILE_SIZE};
 
 // Plant foraging hysteresis
 pub const HYSTERESIS_RATIO: f32 = 0.45;
@@ -10,10 +10,12 @@ pub const PREDATOR_SENSE_RANGE: f32 = 10.0;         // tiles
 pub fn forage_system(
     time: Res<Time>,
     map: Res<super::world::TileMap>,
+    mut rng: ResMut<SimRng>,
     mut q: Query<(&Species, &Position, &mut Brain)>,
     prey_scan: Query<(Entity, &Species, &Position)>, // read-only; used by predators
 ) {
     let dt = time.delta_secs();
+    let rng = &mut rng.0;
 
     for (sp, pos, mut brain) in &mut q {
         // // tick cooldowns
@@ -50,7 +52,7 @@ pub fn forage_system(
 
             // no prey seen → hungry wander
             if brain.replan_cd <= 0.0 || brain.desired_target.is_none() {
-                let jitter = Vec2::new(fastrand::f32() - 0.5, fastrand::f32() - 0.5)
+                let jitter = Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5)
                     .normalize_or_zero() * 5.0;
                 brain.replan_cd = 0.6;
                 brain.desired_target = Some(map.clamp_target(pos.p + jitter));
@@ -70,7 +72,7 @@ pub fn forage_system(
                 brain.replan_cd = 0.75;
             } else {
                 // hungry wander step
-                let jitter = Vec2::new(fastrand::f32() - 0.5, fastrand::f32() - 0.5)
+                let jitter = Vec2::new(rng.f32() - 0.5, rng.f32() - 0.5)
                     .normalize_or_zero() * 4.0;
                 brain.state = BrainState::Forage;
                 brain.target_cell = None;
@@ -97,7 +99,7 @@ fn nearest_food_cell(
     for y in 0..map.height {
         for x in 0..map.width {
             let cell = IVec2::new(x, y);
-            let tile = &map.tiles[(y * map.width + x) as usize];
+            let tile = &map.tiles()[(y * map.width + x) as usize];
             
             if tile_food_ratio_for_species(tile, sp) < hysteresis_ratio {
                 continue;
@@ -0,0 +1,326 @@
+//! Isometric view: the map drawn with the terrain tilesets on 256x128
+//! diamonds, as in Tiled (`demo.tmx`, `terrain::tmx` exports), with objects
+//! and creatures depth-sorted so whatever stands lower on screen draws in
+//! front. Add `IsoViewPlugin` next to `SimViewPlugin`; Tab switches views.
+//!
+//! Terrain is one mesh per chunk and tileset. Objects stay sprites so they
+//! sort against the creatures walking between them. `TileChanged` events
+//! rebuild just the meshes and sprites they touch.
+//!
+//! Grid coordinates are the sim's (tile units, `Position / TILE_SIZE`), with
+//! y growing down-screen like Tiled's rows.
+
+use std::collections::HashMap;
+use std::path::{Component as PathPart, Path, PathBuf};
+
+use bevy::asset::io::file::FileAssetReader;
+use bevy::asset::RenderAssetUsages;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::sprite::{AlphaMode2d, Anchor};
+
+use super::chunks::{all_chunks, chunk_cells, chunk_grid, chunk_of};
+use super::simview::{map_view_stale, object_color, object_fill, MapSprite, MapView};
+use super::world::{TileChange, TileChanged, TileMap};
+use crate::terrain::autotile::{autotile, TileLayer};
+use crate::terrain::tmx::{TILE_HEIGHT, TILE_WIDTH};
+
+/// Screen size of one tile.
+pub const ISO_TILE: Vec2 = Vec2::new(TILE_WIDTH as f32, TILE_HEIGHT as f32);
+/// Creature marker size in the iso view.
+pub const ISO_ANIMAL: f32 = 40.0;
+/// Object marker (standing on its tile's centre).
+const ISO_OBJECT: Vec2 = Vec2::new(48.0, 96.0);
+// Terrain sits at 0; standing things at ISO_DEPTH_Z and up.
+const ISO_DEPTH_Z: f32 = 1.0;
+
+/// World position of grid point `g` on the ground plane (Bevy y up).
+pub fn grid_to_iso(g: Vec2) -> Vec2 {
+    Vec2::new((g.x - g.y) * ISO_TILE.x * 0.5, -(g.x + g.y) * ISO_TILE.y * 0.5)
+}
+
+/// Grid point under world position `s` (inverse of `grid_to_iso`).
+pub fn iso_to_grid(s: Vec2) -> Vec2 {
+    let a = s.x / (ISO_TILE.x * 0.5);
+    let b = -s.y / (ISO_TILE.y * 0.5);
+    Vec2::new((a + b) * 0.5, (b - a) * 0.5)
+}
+
+/// Draw order for something standing at grid point `g`: further down the
+/// screen (larger x + y) draws in front.
+pub fn iso_depth(g: Vec2) -> f32 {
+    ISO_DEPTH_Z + (g.x + g.y).max(0.0) * 1e-4
+}
+
+pub struct IsoViewPlugin;
+
+impl Plugin for IsoViewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<IsoTilesets>()
+            .init_resource::<IsoObjects>()
+            .add_systems(Update, toggle_view)
+            .add_systems(Update, (
+                spawn_iso_map.run_if(map_view_stale),
+                apply_tile_changes,
+            ).chain().run_if(resource_equals(MapView::Iso)))
+            .add_systems(Update, key_out_transparent_colour);
+    }
+}
+
+fn toggle_view(keys: Res<ButtonInput<KeyCode>>, mut view: ResMut<MapView>) {
+    if keys.just_pressed(KeyCode::Tab) {
+        *view = match *view {
+            MapView::Debug => MapView::Iso,
+            MapView::Iso => MapView::Debug,
+        };
+    }
+}
+
+// --- tilesets ---
+
+struct IsoTileset {
+    image: Handle<Image>,
+    /// Draws the image with its keyed-out background cut away.
+    material: Handle<ColorMaterial>,
+    /// The tileset's `trans` colour, made transparent once the image loads.
+    trans: Option<[u8; 3]>,
+    tile: UVec2,
+    columns: u32,
+    spacing: u32,
+    margin: u32,
+    image_size: Vec2,
+}
+
+impl IsoTileset {
+    /// UV rectangle (min, max) of local tile `id`.
+    fn uv_rect(&self, id: u32) -> (Vec2, Vec2) {
+        let (col, row) = (id % self.columns.max(1), id / self.columns.max(1));
+        let px = UVec2::new(col, row) * (self.tile + UVec2::splat(self.spacing)) + UVec2::splat(self.margin);
+        let min = px.as_vec2() / self.image_size;
+        (min, min + self.tile.as_vec2() / self.image_size)
+    }
+}
+
+/// Tilesets by file stem, read from `assets/tilesets/<stem>.tsx` on first use
+/// (None if that failed; it's only reported once).
+#[derive(Resource, Default)]
+struct IsoTilesets(HashMap<String, Option<IsoTileset>>);
+
+// `a/b/../c` → `a/c`, so the image path can be made relative to the asset root.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for part in path.components() {
+        match part {
+            PathPart::ParentDir => { out.pop(); }
+            PathPart::CurDir => {}
+            p => out.push(p),
+        }
+    }
+    out
+}
+
+fn load_tileset(stem: &str, server: &AssetServer, materials: &mut Assets<ColorMaterial>) -> Result<IsoTileset, String> {
+    let assets = normalize(&FileAssetReader::get_base_path().join("assets"));
+    let tsx = assets.join("tilesets").join(format!("{stem}.tsx"));
+    let ts = tiled::Loader::new().load_tsx_tileset(&tsx).map_err(|e| format!("{}: {e}", tsx.display()))?;
+    let img = ts.image.as_ref().ok_or(format!("{}: not a single-image tileset", tsx.display()))?;
+    let source = normalize(&img.source);
+    let asset_path = source.strip_prefix(&assets)
+        .map_err(|_| format!("{}: image {} is outside the assets folder", tsx.display(), source.display()))?;
+
+    let image: Handle<Image> = server.load(asset_path.to_path_buf());
+    let material = materials.add(ColorMaterial {
+        texture: Some(image.clone()),
+        alpha_mode: AlphaMode2d::Mask(0.5),
+        ..default()
+    });
+    Ok(IsoTileset {
+        image,
+        material,
+        trans: img.transparent_colour.map(|c| [c.red, c.green, c.blue]),
+        tile: UVec2::new(ts.tile_width, ts.tile_height),
+        columns: ts.columns,
+        spacing: ts.spacing,
+        margin: ts.margin,
+        image_size: Vec2::new(img.width as f32, img.height as f32),
+    })
+}
+
+// Tilesets mark their background with a colour key (black or magenta) rather
+// than alpha; clear it so neighbouring diamonds don't paint over each other.
+fn key_out_transparent_colour(
+    mut events: EventReader<AssetEvent<Image>>,
+    tilesets: Res<IsoTilesets>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    for ev in events.read() {
+        let AssetEvent::LoadedWithDependencies { id } = ev else { continue };
+        let Some(key) = tilesets.0.values().flatten().find(|t| t.image.id() == *id).and_then(|t| t.trans) else { continue };
+        let Some(data) = images.get_mut(*id).and_then(|img| img.data.as_mut()) else { continue };
+        for px in data.chunks_exact_mut(4) {
+            if px[..3].iter().zip(key).all(|(&a, b)| a.abs_diff(b) <= 8) {
+                px[3] = 0;
+            }
+        }
+    }
+}
+
+// --- map ---
+
+/// Terrain meshes of one chunk.
+#[derive(Component)]
+struct IsoChunk(IVec2);
+
+/// The object sprite standing on each cell.
+#[derive(Resource, Default)]
+struct IsoObjects(HashMap<IVec2, Entity>);
+
+// Quads for the tiles of `chunk` drawn from tileset `set_index`; None if it has none.
+fn chunk_mesh(layer: &TileLayer, set_index: u16, set: &IsoTileset, chunk: IVec2) -> Option<Mesh> {
+    let (min, size) = chunk_cells(chunk, layer.tiles.w, layer.tiles.h);
+    let half = set.tile.as_vec2() * 0.5;
+    let (mut positions, mut uvs, mut indices) = (Vec::new(), Vec::new(), Vec::new());
+    for y in min.y..min.y + size.y {
+        for x in min.x..min.x + size.x {
+            let Some(t) = layer.tiles.get(x, y).filter(|t| t.tileset == set_index) else { continue };
+            let c = grid_to_iso(Vec2::new(x as f32 + 0.5, y as f32 + 0.5));
+            let (uv0, uv1) = set.uv_rect(t.id);
+            let base = positions.len() as u32;
+            positions.extend([
+                [c.x - half.x, c.y - half.y, 0.0],
+                [c.x + half.x, c.y - half.y, 0.0],
+                [c.x + half.x, c.y + half.y, 0.0],
+                [c.x - half.x, c.y + half.y, 0.0],
+            ]);
+            uvs.extend([[uv0.x, uv1.y], [uv1.x, uv1.y], [uv1.x, uv0.y], [uv0.x, uv0.y]]);
+            indices.extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+    }
+    if positions.is_empty() { return None; }
+    Some(
+        Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::default())
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, uvs)
+            .with_inserted_indices(Indices::U32(indices)),
+    )
+}
+
+// Start loading any tileset `layer` draws with that isn't loaded yet.
+fn load_layer_tilesets(layer: &TileLayer, tilesets: &mut IsoTilesets, server: &AssetServer, materials: &mut Assets<ColorMaterial>) {
+    for stem in &layer.tilesets {
+        tilesets.0.entry(stem.clone()).or_insert_with(|| {
+            load_tileset(stem, server, materials).map_err(|e| warn!("iso view: {e}")).ok()
+        });
+    }
+}
+
+fn spawn_chunk_meshes(commands: &mut Commands, meshes: &mut Assets<Mesh>, layer: &TileLayer, tilesets: &IsoTilesets, chunk: IVec2) {
+    for (k, stem) in layer.tilesets.iter().enumerate() {
+        let Some(set) = &tilesets.0[stem] else { continue };
+        let Some(mesh) = chunk_mesh(layer, k as u16, set, chunk) else { continue };
+        commands.spawn((Mesh2d(meshes.add(mesh)), MeshMaterial2d(set.material.clone()), Transform::default(), IsoChunk(chunk), MapSprite));
+    }
+}
+
+// Marker for the object on `cell`, tinted by how much food it has left.
+fn spawn_object_sprite(commands: &mut Commands, map: &TileMap, cell: IVec2) -> Option<Entity> {
+    let t = map.tile_at_cell(cell)?;
+    let obj = t.object?;
+    let g = cell.as_vec2() + Vec2::splat(0.5);
+    let sprite = Sprite {
+        custom_size: Some(ISO_OBJECT),
+        color: object_color(obj).with_alpha(object_fill(t, obj)),
+        anchor: Anchor::BottomCenter,
+        ..default()
+    };
+    Some(commands.spawn((sprite, Transform::from_translation(grid_to_iso(g).extend(iso_depth(g))), MapSprite)).id())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_iso_map(
+    mut commands: Commands,
+    map: Res<TileMap>,
+    server: Res<AssetServer>,
+    mut tilesets: ResMut<IsoTilesets>,
+    mut objects: ResMut<IsoObjects>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    old: Query<Entity, With<MapSprite>>,
+) {
+    for e in &old {
+        commands.entity(e).despawn();
+    }
+
+    let class_tiles: Vec<_> = map.classes.iter().map(|c| c.tiles.clone()).collect();
+    let layer = autotile(&map.class_grid(), &class_tiles);
+    load_layer_tilesets(&layer, &mut tilesets, &server, &mut materials);
+    for chunk in all_chunks(map.width, map.height) {
+        spawn_chunk_meshes(&mut commands, &mut meshes, &layer, &tilesets, chunk);
+    }
+
+    objects.0.clear();
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let cell = IVec2::new(x, y);
+            if let Some(e) = spawn_object_sprite(&mut commands, &map, cell) {
+                objects.0.insert(cell, e);
+            }
+        }
+    }
+}
+
+// Respawn the object sprites of changed cells, and the terrain meshes of
+// chunks whose tiles a terrain change can re-pick (its own and, along chunk
+// borders, its neighbours').
+#[allow(clippy::too_many_arguments)]
+fn apply_tile_changes(
+    mut commands: Commands,
+    mut changes: EventReader<TileChanged>,
+    map: Res<TileMap>,
+    server: Res<AssetServer>,
+    mut tilesets: ResMut<IsoTilesets>,
+    mut objects: ResMut<IsoObjects>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    chunks: Query<(Entity, &IsoChunk)>,
+) {
+    let (mut cells, mut dirty) = (Vec::new(), Vec::new());
+    for ev in changes.read() {
+        match ev.change {
+            TileChange::Terrain { .. } => {
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        dirty.push(chunk_of(ev.cell + IVec2::new(dx, dy)));
+                    }
+                }
+            }
+            _ => cells.push(ev.cell),
+        }
+    }
+
+    cells.sort_by_key(|c| (c.y, c.x));
+    cells.dedup();
+    for cell in cells {
+        if let Some(e) = objects.0.remove(&cell) {
+            commands.entity(e).despawn();
+        }
+        if let Some(e) = spawn_object_sprite(&mut commands, &map, cell) {
+            objects.0.insert(cell, e);
+        }
+    }
+
+    if dirty.is_empty() { return; }
+    dirty.sort_by_key(|c| (c.y, c.x));
+    dirty.dedup();
+    let grid = chunk_grid(map.width, map.height);
+    dirty.retain(|c| c.x >= 0 && c.y >= 0 && c.x < grid.x && c.y < grid.y);
+    for (e, chunk) in &chunks {
+        if dirty.contains(&chunk.0) { commands.entity(e).despawn(); }
+    }
+    let class_tiles: Vec<_> = map.classes.iter().map(|c| c.tiles.clone()).collect();
+    let layer = autotile(&map.class_grid(), &class_tiles);
+    load_layer_tilesets(&layer, &mut tilesets, &server, &mut materials);
+    for chunk in dirty {
+        spawn_chunk_meshes(&mut commands, &mut meshes, &layer, &tilesets, chunk);
+    }
+}
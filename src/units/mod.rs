@@ -4,4 +4,5 @@ pub mod world;
 pub mod simview;
 pub mod route;
 pub mod forage;
-pub mod movement;
\ No newline at end of file
+pub mod movement;pub mod isoview;
+pub mod chunks;
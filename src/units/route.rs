@@ -17,10 +17,8 @@ pub fn route_system(
             (Some(cur), Some(goal)) if cur.distance_squared(pos.p) < 0.25 => {
                 route.current_target = Some(goal);
             }
-            (Some(cur), Some(goal)) => {
-                if cur.distance_squared(goal) > 9.0 {
-                    route.current_target = Some(goal);
-                }
+            (Some(cur), Some(goal)) if cur.distance_squared(goal) > 9.0 => {
+                route.current_target = Some(goal);
             }
             _ => {}
         }
@@ -1,62 +1,115 @@
+use bevy::asset::RenderAssetUsages;
+use bevy::image::ImageSampler;
 use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::sprite::Anchor;
 use bevy::ui::{UiRect, PositionType, BackgroundColor, BorderColor};
 
 use super::base::{Position, Species};
-use super::world::{TileMap, TileObject, Terrain, food_totals, TILE_SIZE};
+use super::chunks::{all_chunks, chunk_cells};
+use super::isoview::{grid_to_iso, iso_depth, ISO_ANIMAL};
+use super::world::{Tile, TileChange, TileChanged, TileMap, TileObject, Terrain, food_totals, TILE_SIZE};
+use crate::map_plugin::MapGenerated;
 
 
 const VIS_TILE_PIXELS: f32 = 16.0;
 const ANIMAL_DOT: f32 = 10.0;
 const OBJECT_DOT: f32 = 8.0;
+/// Overlay texels per tile side; the object dot covers the middle of them.
+const OBJECT_TEXELS: i32 = 4;
 
-#[derive(Component)] struct TileSprite;
-#[derive(Component)] struct ObjectSprite(IVec2);
+/// Which view draws the map. `SimViewPlugin` starts in `Debug`; with
+/// `IsoViewPlugin` added too, Tab switches between them.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MapView {
+    /// Flat 16px squares in each class's colour.
+    #[default]
+    Debug,
+    /// Tileset art on 256x128 diamonds (see `isoview`).
+    Iso,
+}
+
+/// Every tile/object entity either view spawns; cleared on regenerate or switch.
+#[derive(Component)] pub(super) struct MapSprite;
+/// Terrain texture for one chunk of the flat view.
+#[derive(Component)] struct TerrainChunk(IVec2);
+/// Object overlay texture for one chunk of the flat view.
+#[derive(Component)] struct ObjectChunk(IVec2);
 #[derive(Component)] struct AnimalSprite;
 #[derive(Component)] struct MetricsText;
 
 #[derive(Resource)]
 struct MetricsTimer(Timer);
 
+/// Run condition: the map was regenerated or the view switched.
+pub(super) fn map_view_stale(mut generated: EventReader<MapGenerated>, view: Res<MapView>) -> bool {
+    generated.read().count() > 0 || view.is_changed()
+}
+
 pub struct SimViewPlugin;
 
 impl Plugin for SimViewPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(MetricsTimer(Timer::from_seconds(0.25, TimerMode::Repeating)))
-            .add_systems(Startup, (setup_camera, spawn_map_sprites, spawn_metrics_panel))
-            .add_systems(Update, attach_animal_sprites)
-            .add_systems(Update, (sync_animal_sprites, update_object_alpha, update_metrics).chain());
+            .init_resource::<MapView>()
+            .add_systems(Startup, (setup_camera, spawn_metrics_panel))
+            .add_systems(Update, center_camera.run_if(map_view_stale))
+            .add_systems(Update, spawn_map_sprites.run_if(map_view_stale).run_if(resource_equals(MapView::Debug)))
+            .add_systems(Update, (attach_animal_sprites, resize_animal_sprites.run_if(resource_changed::<MapView>)))
+            .add_systems(Update, (
+                sync_animal_sprites,
+                redraw_changed_chunks.run_if(resource_equals(MapView::Debug)),
+                update_metrics,
+            ).chain());
     }
 }
 
-fn setup_camera(mut commands: Commands, map: Res<TileMap>) {
-    const Z: f32 = 1000.0;
-    let cx = map.width as f32 * VIS_TILE_PIXELS * 0.5;
-    let cy = map.height as f32 * VIS_TILE_PIXELS * 0.5;
+const CAMERA_Z: f32 = 1000.0;
 
+fn setup_camera(mut commands: Commands) {
     commands.spawn((
         Camera2d,                          // 2D camera component
         Projection::Orthographic(OrthographicProjection {
             scale: 6.0,                          // start zoomed out for big iso tiles
             ..OrthographicProjection::default_2d()
         }),
-        Transform::from_xyz(cx, cy, Z),    // center on the map
+        Transform::from_xyz(0.0, 0.0, CAMERA_Z), // centred once the map exists
         Visibility::default(),             // optional; Bevy will add required bits
     ));
 }
 
+// Centre on the map when it first appears, changes size or the view switches;
+// plain reloads keep the view.
+fn center_camera(
+    map: Res<TileMap>,
+    view: Res<MapView>,
+    mut last: Local<Option<(IVec2, MapView)>>,
+    mut q: Query<&mut Transform, With<Camera2d>>,
+) {
+    let size = IVec2::new(map.width, map.height);
+    if *last == Some((size, *view)) { return; }
+    *last = Some((size, *view));
+    let centre = match *view {
+        MapView::Debug => size.as_vec2() * VIS_TILE_PIXELS * 0.5,
+        MapView::Iso => grid_to_iso(size.as_vec2() * 0.5),
+    };
+    for mut tf in &mut q {
+        tf.translation.x = centre.x;
+        tf.translation.y = centre.y;
+    }
+}
+
 // --- map + objects ---
 
-fn terrain_color(t: Terrain) -> Color {
-    match t {
-        Terrain::Grassland => Color::srgb(0.56, 0.76, 0.45),
-        Terrain::Forest    => Color::srgb(0.30, 0.55, 0.35),
-        Terrain::Mountain  => Color::srgb(0.55, 0.52, 0.48),
-        Terrain::Water     => Color::srgb(0.20, 0.45, 0.85),
+fn terrain_color(map: &TileMap, t: Terrain) -> Color {
+    match map.terrain_info(t) {
+        Some(info) => Color::srgb_u8(info.color[0], info.color[1], info.color[2]),
+        None => Color::srgb(1.0, 0.0, 1.0),
     }
 }
 
 // user-preferred object colors:
-fn object_color(obj: TileObject) -> Color {
+pub(super) fn object_color(obj: TileObject) -> Color {
     match obj {
         TileObject::Tree => Color::srgba_u8(255, 220,   0, 255), // bright yellow
         TileObject::Bush => Color::srgba_u8(255, 120,   0, 255), // vivid orange
@@ -64,6 +117,15 @@ fn object_color(obj: TileObject) -> Color {
     }
 }
 
+/// How full an object's food stock is, as marker alpha.
+pub(super) fn object_fill(tile: &Tile, obj: TileObject) -> f32 {
+    match obj {
+        TileObject::Tree => if tile.nuts_max > 0.0 { tile.nuts / tile.nuts_max } else { 0.0 },
+        TileObject::Bush => if tile.berries_max > 0.0 { tile.berries / tile.berries_max } else { 0.0 },
+        TileObject::Cave => 1.0,
+    }.clamp(0.1, 1.0)
+}
+
 fn species_color(sp: Species) -> Color {
     match sp {
         Species::Squirrel => Color::srgb(0.72, 0.40, 0.10), // brown-ish
@@ -74,65 +136,151 @@ fn species_color(sp: Species) -> Color {
     }
 }
 
-fn tile_to_world(x: i32, y: i32) -> Vec3 {
-    Vec3::new(
-        (x as f32 + 0.5) * VIS_TILE_PIXELS,
-        (y as f32 + 0.5) * VIS_TILE_PIXELS,
-        0.0,
-    )
+// Transparent texture, sampled without smoothing so tiles stay crisp squares.
+fn chunk_image(size: UVec2) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d { width: size.x, height: size.y, depth_or_array_layers: 1 },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+    image
 }
 
-fn spawn_map_sprites(mut commands: Commands, map: Res<TileMap>) {
-    // background tiles
-    for y in 0..map.height {
-        for x in 0..map.width {
-            let idx = (y * map.width + x) as usize;
-            let t = &map.tiles[idx];
-
-            commands.spawn((
-                Sprite {
-                    custom_size: Some(Vec2::splat(VIS_TILE_PIXELS)),
-                    color: terrain_color(t.terrain),
-                    ..Default::default()
-                },
-                Transform::from_translation(tile_to_world(x, y)),
-                Visibility::default(),
-                TileSprite,
-            ));
+// One texel per tile. Rows run top-down while tile y grows up the screen.
+fn draw_terrain_chunk(map: &TileMap, chunk: IVec2) -> Image {
+    let (min, size) = chunk_cells(chunk, map.width, map.height);
+    let mut image = chunk_image(size.as_uvec2());
+    let data = image.data.as_mut().expect("new image has data");
+    for ly in 0..size.y {
+        for lx in 0..size.x {
+            let t = &map.tiles()[((min.y + ly) * map.width + min.x + lx) as usize];
+            let rgba = terrain_color(map, t.terrain).to_srgba().to_u8_array();
+            let i = (((size.y - 1 - ly) * size.x + lx) * 4) as usize;
+            data[i..i + 4].copy_from_slice(&rgba);
+        }
+    }
+    image
+}
 
-            // overlay object dot (tree/bush)
-            if let Some(obj) = t.object {
-                let pct = match obj {
-                    TileObject::Tree => if t.nuts_max > 0.0 { t.nuts / t.nuts_max } else { 0.0 },
-                    TileObject::Bush => if t.berries_max > 0.0 { t.berries / t.berries_max } else { 0.0 },
-                    TileObject::Cave => 1.0,
-                }.clamp(0.1, 1.0);
-
-                commands.spawn((
-                    Sprite {
-                        custom_size: Some(Vec2::splat(OBJECT_DOT)),
-                        color: object_color(obj).with_alpha(pct),
-                        ..Default::default()
-                    },
-                    Transform::from_translation(tile_to_world(x, y) + Vec3::new(0.0, 0.0, 1.0)),
-                    Visibility::default(),
-                    ObjectSprite(IVec2::new(x, y)),
-                ));
+// OBJECT_TEXELS per tile, with a centred dot per object.
+fn draw_object_chunk(map: &TileMap, chunk: IVec2, data: &mut [u8]) {
+    let (min, size) = chunk_cells(chunk, map.width, map.height);
+    let row = size.x * OBJECT_TEXELS;
+    let dot = ((OBJECT_DOT / VIS_TILE_PIXELS) * OBJECT_TEXELS as f32).round() as i32;
+    let inset = (OBJECT_TEXELS - dot) / 2;
+    data.fill(0);
+    for ly in 0..size.y {
+        for lx in 0..size.x {
+            let t = &map.tiles()[((min.y + ly) * map.width + min.x + lx) as usize];
+            let Some(obj) = t.object else { continue };
+            let rgba = object_color(obj).with_alpha(object_fill(t, obj)).to_srgba().to_u8_array();
+            let top = (size.y - 1 - ly) * OBJECT_TEXELS + inset;
+            let left = lx * OBJECT_TEXELS + inset;
+            for py in top..top + dot {
+                for px in left..left + dot {
+                    let i = ((py * row + px) * 4) as usize;
+                    data[i..i + 4].copy_from_slice(&rgba);
+                }
             }
+        }
+    }
+}
 
+// Two sprites per chunk: terrain, and object dots above it.
+fn spawn_map_sprites(
+    mut commands: Commands,
+    map: Res<TileMap>,
+    mut images: ResMut<Assets<Image>>,
+    old: Query<Entity, With<MapSprite>>,
+) {
+    for e in &old {
+        commands.entity(e).despawn();
+    }
+
+    for chunk in all_chunks(map.width, map.height) {
+        let (min, size) = chunk_cells(chunk, map.width, map.height);
+        let origin = min.as_vec2() * VIS_TILE_PIXELS;
+        let sprite = |image| Sprite {
+            image,
+            custom_size: Some(size.as_vec2() * VIS_TILE_PIXELS),
+            anchor: Anchor::BottomLeft,
+            ..default()
+        };
+
+        let terrain = images.add(draw_terrain_chunk(&map, chunk));
+        commands.spawn((sprite(terrain), Transform::from_translation(origin.extend(0.0)), TerrainChunk(chunk), MapSprite));
+
+        let mut overlay = chunk_image((size * OBJECT_TEXELS).as_uvec2());
+        draw_object_chunk(&map, chunk, overlay.data.as_mut().expect("new image has data"));
+        commands.spawn((
+            sprite(images.add(overlay)),
+            Transform::from_translation(origin.extend(1.0)),
+            ObjectChunk(chunk),
+            MapSprite,
+        ));
+    }
+}
+
+// Redraw the chunks `TileChanged` touched: terrain textures for terrain
+// changes, object overlays for everything else.
+fn redraw_changed_chunks(
+    map: Res<TileMap>,
+    mut changes: EventReader<TileChanged>,
+    mut images: ResMut<Assets<Image>>,
+    terrain: Query<(&TerrainChunk, &Sprite)>,
+    objects: Query<(&ObjectChunk, &Sprite)>,
+) {
+    let (mut terrain_dirty, mut object_dirty) = (Vec::new(), Vec::new());
+    for ev in changes.read() {
+        match ev.change {
+            TileChange::Terrain { .. } => terrain_dirty.push(ev.chunk()),
+            _ => object_dirty.push(ev.chunk()),
+        }
+    }
+    for dirty in [&mut terrain_dirty, &mut object_dirty] {
+        dirty.sort_by_key(|c| (c.y, c.x));
+        dirty.dedup();
+    }
+    let is_dirty = |dirty: &[IVec2], c: IVec2| dirty.binary_search_by_key(&(c.y, c.x), |c| (c.y, c.x)).is_ok();
+
+    if !terrain_dirty.is_empty() {
+        for (chunk, sprite) in &terrain {
+            if !is_dirty(&terrain_dirty, chunk.0) { continue; }
+            if let Some(image) = images.get_mut(&sprite.image) {
+                *image = draw_terrain_chunk(&map, chunk.0);
+            }
+        }
+    }
+    if !object_dirty.is_empty() {
+        for (chunk, sprite) in &objects {
+            if !is_dirty(&object_dirty, chunk.0) { continue; }
+            if let Some(data) = images.get_mut(&sprite.image).and_then(|img| img.data.as_mut()) {
+                draw_object_chunk(&map, chunk.0, data);
+            }
         }
     }
 }
 
 // --- animals ---
+fn animal_size(view: MapView) -> Vec2 {
+    match view {
+        MapView::Debug => Vec2::splat(ANIMAL_DOT),
+        MapView::Iso => Vec2::splat(ISO_ANIMAL),
+    }
+}
+
 fn attach_animal_sprites(
     mut commands: Commands,
+    view: Res<MapView>,
     q: Query<(Entity, &Species), Added<Species>>
 ) {
     for (e, sp) in &q {
         commands.entity(e).insert((
             Sprite {
-                custom_size: Some(Vec2::splat(ANIMAL_DOT)),
+                custom_size: Some(animal_size(*view)),
                 color: species_color(*sp),
                 ..Default::default()
             },
@@ -144,26 +292,23 @@ fn attach_animal_sprites(
 }
 
 
-fn sync_animal_sprites(mut q: Query<(&Position, &mut Transform), With<AnimalSprite>>) {
+fn resize_animal_sprites(view: Res<MapView>, mut q: Query<&mut Sprite, With<AnimalSprite>>) {
+    for mut sprite in &mut q {
+        sprite.custom_size = Some(animal_size(*view));
+    }
+}
+
+fn sync_animal_sprites(view: Res<MapView>, mut q: Query<(&Position, &mut Transform), With<AnimalSprite>>) {
     for (pos, mut tf) in &mut q {
-        // sim positions are in TILE_SIZE units; scale to viz pixels
-        let scale = VIS_TILE_PIXELS / TILE_SIZE;
-        tf.translation.x = pos.p.x * scale;
-        tf.translation.y = pos.p.y * scale;
-        // z already set to 2.0
-    }
-}
-
-fn update_object_alpha(map: Res<TileMap>, mut q: Query<(&ObjectSprite, &mut Sprite)>) {
-    for (mark, mut sprite) in &mut q {
-        if let Some(tile) = map.tile_at_cell(mark.0) {
-            if let Some(obj) = tile.object {
-                let pct = match obj {
-                    TileObject::Tree => if tile.nuts_max > 0.0 { tile.nuts / tile.nuts_max } else { 0.0 },
-                    TileObject::Bush => if tile.berries_max > 0.0 { tile.berries / tile.berries_max } else { 0.0 },
-                    TileObject::Cave => 1.0,
-                }.clamp(0.1, 1.0);
-                sprite.color = object_color(obj).with_alpha(pct);
+        match *view {
+            MapView::Debug => {
+                // sim positions are in TILE_SIZE units; scale to viz pixels
+                let scale = VIS_TILE_PIXELS / TILE_SIZE;
+                tf.translation = Vec3::new(pos.p.x * scale, pos.p.y * scale, 2.0);
+            }
+            MapView::Iso => {
+                let g = pos.p / TILE_SIZE;
+                tf.translation = grid_to_iso(g).extend(iso_depth(g));
             }
         }
     }
@@ -1,9 +1,41 @@
 use bevy::prelude::*;
+use super::chunks::chunk_of;
+use crate::terrain::autotile::stock_tiles;
+use crate::terrain::grid::Grid;
+use crate::terrain::seed::MapSeed;
+use crate::terrain::template::{ClassRole, ClassTiles, TerrainClassDef, default_terrain_classes};
 
 pub const TILE_SIZE: f32 = 1.0; // sim unit per tile
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
-pub enum Terrain { Forest, Grassland, Mountain, Water }
+/// Terrain class id; details live in `TileMap::classes`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Terrain(pub u8);
+
+/// What the sim needs to know about one terrain class (mirrors the template registry).
+#[derive(Clone, Debug)]
+pub struct TerrainInfo {
+    pub name: String,
+    pub color: [u8; 3],
+    pub role: ClassRole,
+    /// Movement multiplier (<= 1.0 slows you down)
+    pub movement: f32,
+    pub passable: bool,
+    /// What the iso view draws it with (its own tiles or its role's stock ones).
+    pub tiles: ClassTiles,
+}
+
+impl From<&TerrainClassDef> for TerrainInfo {
+    fn from(d: &TerrainClassDef) -> Self {
+        Self {
+            name: d.name.clone(),
+            color: [d.color.0, d.color.1, d.color.2],
+            role: d.role,
+            movement: d.movement,
+            passable: d.passable,
+            tiles: d.tiles.clone().unwrap_or_else(|| stock_tiles(d.role)),
+        }
+    }
+}
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TileObject { Tree, Bush, Cave }
@@ -11,6 +43,8 @@ pub enum TileObject { Tree, Bush, Cave }
 #[derive(Clone, Copy, Debug)]
 pub struct Tile {
     pub terrain: Terrain,
+    /// Height from the elevation phase (0.0 on hand-made/demo maps).
+    pub elevation: f32,
     pub object: Option<TileObject>,
     // simple food stocks (only meaningful if object is Tree/Bush)
     pub nuts: f32,
@@ -19,17 +53,148 @@ pub struct Tile {
     pub berries_max: f32,
 }
 
+/// A flat tile of `terrain` with no object or food.
+pub(crate) fn empty_tile(terrain: Terrain) -> Tile {
+    Tile {
+        terrain,
+        elevation: 0.0,
+        object: None,
+        nuts: 0.0,
+        berries: 0.0,
+        nuts_max: 0.0,
+        berries_max: 0.0,
+    }
+}
+
+/// Randomness for the sim. Reseeded from the map seed whenever a map is
+/// generated, so a seed code replays the creatures as well as the terrain.
+#[derive(Resource)]
+pub struct SimRng(pub fastrand::Rng);
+
+/// Food stock as a fraction of its max, in 16 steps: what views draw and
+/// what counts as a visible food change.
+pub fn food_level(tile: &Tile) -> u8 {
+    let (stock, max) = match tile.object {
+        Some(TileObject::Tree) => (tile.nuts, tile.nuts_max),
+        Some(TileObject::Bush) => (tile.berries, tile.berries_max),
+        _ => return 0,
+    };
+    if max <= 0.0 { 0 } else { ((stock / max).clamp(0.0, 1.0) * 15.0).round() as u8 }
+}
+
+/// Below this much of either food a tile counts as eaten out.
+pub const FOOD_EMPTY: f32 = 0.02;
+
+fn has_food(tile: &Tile) -> bool {
+    tile.nuts > FOOD_EMPTY || tile.berries > FOOD_EMPTY
+}
+
+/// What a tracked tile edit changed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileChange {
+    Terrain { from: Terrain, to: Terrain },
+    ObjectPlaced(TileObject),
+    ObjectRemoved(TileObject),
+    /// The food level (see `food_level`) moved.
+    Food,
+    /// Nuts and berries both dropped to `FOOD_EMPTY` or below.
+    FoodDepleted,
+    /// Food grew back past `FOOD_EMPTY` after being eaten out.
+    FoodReplenished,
+}
+
+/// Sent (by `TileMapPlugin`) for every change made through `TileMap::update`.
+#[derive(Event, Clone, Copy, Debug, PartialEq)]
+pub struct TileChanged {
+    pub cell: IVec2,
+    pub change: TileChange,
+}
+
+impl TileChanged {
+    pub fn chunk(&self) -> IVec2 { chunk_of(self.cell) }
+}
+
 #[derive(Resource)]
 pub struct TileMap {
     pub width: i32,
     pub height: i32,
-    pub tiles: Vec<Tile>,
+    /// Read with `tiles()`; change live tiles with `update` so views and AI hear about it.
+    tiles: Vec<Tile>,
+    /// River polylines (tile cells, source → mouth).
+    pub rivers: Vec<Vec<IVec2>>,
+    /// Terrain classes indexed by `Terrain` id (the classic four unless replaced).
+    pub classes: Vec<TerrainInfo>,
+    /// Changes since `TileMapPlugin` last sent them.
+    pending: Vec<TileChanged>,
 }
 
 impl TileMap {
     pub fn new(width: i32, height: i32, fill: Tile) -> Self {
         let len = (width * height) as usize;
-        Self { width, height, tiles: vec![fill; len] }
+        let classes = default_terrain_classes().iter().map(TerrainInfo::from).collect();
+        Self { width, height, tiles: vec![fill; len], rivers: Vec::new(), classes, pending: Vec::new() }
+    }
+
+    /// Every tile, row by row.
+    pub fn tiles(&self) -> &[Tile] { &self.tiles }
+
+    /// Direct access for building a map before anyone watches it; nothing is
+    /// recorded, so use `update` once the map is live.
+    pub fn tiles_mut_untracked(&mut self) -> &mut [Tile] { &mut self.tiles }
+
+    /// Edit the tile at `cell` through `f` and record what changed. None
+    /// (without calling `f`) if `cell` is off the map.
+    pub fn update<R>(&mut self, cell: IVec2, f: impl FnOnce(&mut Tile) -> R) -> Option<R> {
+        let i = self.idx(cell)?;
+        let before = self.tiles[i];
+        let out = f(&mut self.tiles[i]);
+        let after = self.tiles[i];
+        record_changes(&mut self.pending, cell, &before, &after);
+        Some(out)
+    }
+
+    /// `update` every tile in turn.
+    pub fn update_each(&mut self, mut f: impl FnMut(&mut Tile)) {
+        for (i, tile) in self.tiles.iter_mut().enumerate() {
+            let before = *tile;
+            f(tile);
+            let cell = IVec2::new(i as i32 % self.width, i as i32 / self.width);
+            record_changes(&mut self.pending, cell, &before, tile);
+        }
+    }
+
+    /// Take the changes recorded since the last call.
+    pub fn take_changes(&mut self) -> Vec<TileChanged> { std::mem::take(&mut self.pending) }
+
+    pub fn has_changes(&self) -> bool { !self.pending.is_empty() }
+
+    /// 0x0 placeholder for before the first map is generated.
+    pub fn empty() -> Self {
+        Self::new(0, 0, empty_tile(Terrain::default()))
+    }
+
+    pub fn terrain_info(&self, t: Terrain) -> Option<&TerrainInfo> {
+        self.classes.get(t.0 as usize)
+    }
+
+    pub fn role_of(&self, t: Terrain) -> ClassRole {
+        self.terrain_info(t).map(|i| i.role).unwrap_or_default()
+    }
+
+    /// First class with `role`, if the map has one.
+    pub fn class_with_role(&self, role: ClassRole) -> Option<Terrain> {
+        self.classes.iter().position(|i| i.role == role).map(|k| Terrain(k as u8))
+    }
+
+    /// Terrain class of every tile, for autotiling.
+    pub fn class_grid(&self) -> Grid<u8> {
+        let mut g = Grid::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                g.set(x, y, self.tiles[(y * self.width + x) as usize].terrain.0);
+            }
+        }
+        g
     }
 
     #[inline]
@@ -43,10 +208,6 @@ impl TileMap {
         self.idx(cell).map(|i| &self.tiles[i])
     }
 
-    pub fn tile_at_cell_mut(&mut self, cell: IVec2) -> Option<&mut Tile> {
-        if let Some(i) = self.idx(cell) { Some(&mut self.tiles[i]) } else { None }
-    }
-
     pub fn cell_at_world(&self, pos: Vec2) -> IVec2 {
         IVec2::new((pos.x / TILE_SIZE).floor() as i32, (pos.y / TILE_SIZE).floor() as i32)
     }
@@ -54,17 +215,12 @@ impl TileMap {
     pub fn terrain_at_world(&self, pos: Vec2) -> Terrain {
         self.tile_at_cell(self.cell_at_world(pos))
             .map(|t| t.terrain)
-            .unwrap_or(Terrain::Grassland)
+            .unwrap_or_else(|| self.class_with_role(ClassRole::Ground).unwrap_or_default())
     }
 
     /// Movement multiplier (<= 1.0 slows you down)
     pub fn speed_multiplier(&self, pos: Vec2) -> f32 {
-        match self.terrain_at_world(pos) {
-            Terrain::Water     => 0.5,
-            Terrain::Mountain  => 0.8,
-            Terrain::Forest    => 1.0,
-            Terrain::Grassland => 1.0,
-        }
+        self.terrain_info(self.terrain_at_world(pos)).map(|i| i.movement).unwrap_or(1.0)
     }
     
     /// World-space bounds (origin at 0,0)
@@ -73,7 +229,7 @@ impl TileMap {
         Vec2::new(self.width as f32 * TILE_SIZE, self.height as f32 * TILE_SIZE)
     }
 
-    /// Clamp any world pos just inside the edges (epsilon avoids flicker at -0/width)
+    // Clamp any world pos just inside the edges (epsilon avoids flicker at -0/width)
     // pub fn clamp_world(&self, p: Vec2) -> Vec2 {
     //     let eps = 1e-3;
     //     let min = self.world_min() + Vec2::splat(eps);
@@ -91,17 +247,51 @@ impl TileMap {
     }
 }
 
+fn record_changes(out: &mut Vec<TileChanged>, cell: IVec2, before: &Tile, after: &Tile) {
+    let mut push = |change| out.push(TileChanged { cell, change });
+    if before.terrain != after.terrain {
+        push(TileChange::Terrain { from: before.terrain, to: after.terrain });
+    }
+    if before.object != after.object {
+        if let Some(o) = before.object { push(TileChange::ObjectRemoved(o)); }
+        if let Some(o) = after.object { push(TileChange::ObjectPlaced(o)); }
+    } else if food_level(before) != food_level(after) {
+        push(TileChange::Food);
+    }
+    match (has_food(before), has_food(after)) {
+        (true, false) => push(TileChange::FoodDepleted),
+        (false, true) => push(TileChange::FoodReplenished),
+        _ => {}
+    }
+}
+
+/// Sends the `TileMap`'s recorded changes as `TileChanged` events. Added by
+/// the map plugins.
+pub struct TileMapPlugin;
+
+impl Plugin for TileMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TileChanged>()
+            .add_systems(PostUpdate, send_tile_changes);
+    }
+}
+
+fn send_tile_changes(mut map: ResMut<TileMap>, mut out: EventWriter<TileChanged>) {
+    if !map.has_changes() { return; }
+    out.write_batch(map.take_changes());
+}
+
 // --- plant regen ---
 
-const TREE_NUTS_MAX: f32 = 8.0;
+pub const TREE_NUTS_MAX: f32 = 8.0;
 const TREE_NUTS_REGEN_PER_SEC: f32 = 0.03;
 
-const BUSH_BERRIES_MAX: f32 = 6.0;
+pub const BUSH_BERRIES_MAX: f32 = 6.0;
 const BERRIES_REGEN_PER_SEC: f32 = 0.04;
 
 pub fn plants_regrow_system(mut map: ResMut<TileMap>, time: Res<Time>) {
     let dt = time.delta_secs();
-    for t in &mut map.tiles {
+    map.update_each(|t| {
         match t.object {
             Some(TileObject::Tree) => {
                 t.nuts = (t.nuts + TREE_NUTS_REGEN_PER_SEC * dt).min(t.nuts_max);
@@ -111,32 +301,27 @@ pub fn plants_regrow_system(mut map: ResMut<TileMap>, time: Res<Time>) {
             }
             _ => {}
         }
-    }
+    });
 }
 
 // --- demo map helpers ---
 
-fn empty_tile(terrain: Terrain) -> Tile {
-    Tile {
-        terrain,
-        object: None,
-        nuts: 0.0,
-        berries: 0.0,
-        nuts_max: 0.0,
-        berries_max: 0.0,
-    }
-}
-
-pub fn make_demo_map(width: i32, height: i32) -> TileMap {
-    let mut map = TileMap::new(width, height, empty_tile(Terrain::Grassland));
+/// Striped test map with scattered trees and bushes; the same seed gives the same map.
+#[allow(dead_code)]
+pub fn make_demo_map(width: i32, height: i32, seed: MapSeed) -> TileMap {
+    let mut rng = fastrand::Rng::with_seed(seed.stream("demo"));
+    let mut map = TileMap::new(width, height, empty_tile(Terrain::default()));
+    let class = |role| map.class_with_role(role).unwrap_or_default();
+    let (grass, forest, water, mountain) =
+        (class(ClassRole::Ground), class(ClassRole::Forest), class(ClassRole::Water), class(ClassRole::Mountain));
     // simple terrain pattern
     for y in 0..map.height {
         for x in 0..map.width {
             let idx = (y * map.width + x) as usize;
-            let terrain = if x % 13 == 0 { Terrain::Water }
-            else if y % 17 == 0 { Terrain::Mountain }
-            else if (x + y) % 7 == 0 { Terrain::Forest }
-            else { Terrain::Grassland };
+            let terrain = if x % 13 == 0 { water }
+            else if y % 17 == 0 { mountain }
+            else if (x + y) % 7 == 0 { forest }
+            else { grass };
             map.tiles[idx] = empty_tile(terrain);
         }
     }
@@ -144,10 +329,10 @@ pub fn make_demo_map(width: i32, height: i32) -> TileMap {
     for y in 0..map.height {
         for x in 0..map.width {
             let idx = (y * map.width + x) as usize;
-            let terrain = map.tiles[idx].terrain;
-            let roll = fastrand::f32();
-            match terrain {
-                Terrain::Forest => {
+            let role = map.role_of(map.tiles[idx].terrain);
+            let roll = rng.f32();
+            match role {
+                ClassRole::Forest => {
                     if roll < 0.10 {
                         map.tiles[idx].object = Some(TileObject::Tree);
                         map.tiles[idx].nuts_max = TREE_NUTS_MAX;
@@ -158,12 +343,10 @@ pub fn make_demo_map(width: i32, height: i32) -> TileMap {
                         map.tiles[idx].berries = BUSH_BERRIES_MAX;
                     }
                 }
-                Terrain::Grassland => {
-                    if roll < 0.06 {
-                        map.tiles[idx].object = Some(TileObject::Bush);
-                        map.tiles[idx].berries_max = BUSH_BERRIES_MAX;
-                        map.tiles[idx].berries = BUSH_BERRIES_MAX;
-                    }
+                ClassRole::Ground if roll < 0.06 => {
+                    map.tiles[idx].object = Some(TileObject::Bush);
+                    map.tiles[idx].berries_max = BUSH_BERRIES_MAX;
+                    map.tiles[idx].berries = BUSH_BERRIES_MAX;
                 }
                 _ => {}
             }
@@ -176,3 +359,67 @@ pub fn make_demo_map(width: i32, height: i32) -> TileMap {
 pub fn food_totals(map: &TileMap) -> (f32, f32) {
     map.tiles.iter().fold((0.0, 0.0), |(n, b), t| (n + t.nuts, b + t.berries))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 3x2 grassland with a full tree at (1, 0).
+    fn map() -> TileMap {
+        let mut map = TileMap::new(3, 2, empty_tile(Terrain(0)));
+        let tree = &mut map.tiles_mut_untracked()[1];
+        tree.object = Some(TileObject::Tree);
+        (tree.nuts, tree.nuts_max) = (TREE_NUTS_MAX, TREE_NUTS_MAX);
+        map
+    }
+
+    fn changes(map: &mut TileMap) -> Vec<(IVec2, TileChange)> {
+        map.take_changes().into_iter().map(|c| (c.cell, c.change)).collect()
+    }
+
+    const TREE: IVec2 = IVec2::new(1, 0);
+
+    #[test]
+    fn terrain_edits_are_recorded() {
+        let mut map = map();
+        let cell = IVec2::new(2, 1);
+        map.update(cell, |t| t.terrain = Terrain(2));
+        assert_eq!(changes(&mut map), [(cell, TileChange::Terrain { from: Terrain(0), to: Terrain(2) })]);
+        assert_eq!(map.update(IVec2::new(3, 0), |t| t.terrain = Terrain(2)), None);
+        map.update(cell, |_| ());
+        assert!(!map.has_changes());
+    }
+
+    #[test]
+    fn placing_and_removing_objects() {
+        let mut map = map();
+        let cell = IVec2::new(0, 1);
+        map.update(cell, |t| {
+            t.object = Some(TileObject::Bush);
+            (t.berries, t.berries_max) = (BUSH_BERRIES_MAX, BUSH_BERRIES_MAX);
+        });
+        assert_eq!(changes(&mut map), [(cell, TileChange::ObjectPlaced(TileObject::Bush)), (cell, TileChange::FoodReplenished)]);
+
+        map.update(TREE, |t| { t.object = None; t.nuts = 0.0; });
+        assert_eq!(changes(&mut map), [(TREE, TileChange::ObjectRemoved(TileObject::Tree)), (TREE, TileChange::FoodDepleted)]);
+    }
+
+    #[test]
+    fn food_changes_only_on_a_level_step() {
+        let mut map = map();
+        map.update(TREE, |t| t.nuts -= 0.1);
+        assert!(!map.has_changes(), "0.1 nuts is less than one food level");
+        map.update(TREE, |t| t.nuts -= 1.0);
+        assert_eq!(changes(&mut map), [(TREE, TileChange::Food)]);
+    }
+
+    #[test]
+    fn eating_out_and_regrowing() {
+        let mut map = map();
+        map.update(TREE, |t| t.nuts = 0.0);
+        assert_eq!(changes(&mut map), [(TREE, TileChange::Food), (TREE, TileChange::FoodDepleted)]);
+
+        map.update_each(|t| if t.object.is_some() { t.nuts = 0.5 });
+        assert_eq!(changes(&mut map), [(TREE, TileChange::Food), (TREE, TileChange::FoodReplenished)]);
+    }
+}